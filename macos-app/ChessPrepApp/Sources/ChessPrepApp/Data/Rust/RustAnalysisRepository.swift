@@ -302,7 +302,8 @@ struct RustAnalysisRepository: AnalysisRepository {
         columns: [Substring],
         rawRow: String
     ) throws -> AnalysisWorkspaceNodeRecord {
-        guard columns.count == 9 else {
+        // The 10th column (opaque node metadata) is optional for older CLIs.
+        guard columns.count >= 9 else {
             throw RepositoryError.failure("Unexpected node row format: \(rawRow)")
         }
         guard let nodeID = UUID(uuidString: String(columns[1])) else {
@@ -326,6 +327,7 @@ struct RustAnalysisRepository: AnalysisRepository {
         let san = columns[3].isEmpty ? nil : String(columns[3])
         let uci = columns[4].isEmpty ? nil : String(columns[4])
         let nags = parseNags(String(columns[7]))
+        let metadata = columns.count > 9 && !columns[9].isEmpty ? tsvDecoded(String(columns[9])) : nil
 
         return AnalysisWorkspaceNodeRecord(
             id: nodeID,
@@ -335,7 +337,8 @@ struct RustAnalysisRepository: AnalysisRepository {
             fen: String(columns[5]),
             comment: String(columns[6]),
             nags: nags,
-            sortIndex: sortIndex
+            sortIndex: sortIndex,
+            metadata: metadata
         )
     }
 
@@ -393,7 +396,7 @@ struct RustAnalysisRepository: AnalysisRepository {
             let uci = node.uci ?? ""
             let nags = node.nags.joined(separator: ",")
 
-            return [
+            let columns = [
                 node.id.uuidString.lowercased(),
                 parent,
                 san,
@@ -404,7 +407,9 @@ struct RustAnalysisRepository: AnalysisRepository {
                 String(node.sortIndex),
             ]
             .map(tsvSanitized)
-            .joined(separator: "\t")
+
+            return (columns + [tsvEncoded(node.metadata ?? "")])
+                .joined(separator: "\t")
         }
 
         let body = lines.joined(separator: "\n")
@@ -417,6 +422,39 @@ struct RustAnalysisRepository: AnalysisRepository {
             .replacingOccurrences(of: "\r", with: " ")
     }
 
+    // Reversible counterpart of `tsvSanitized` for opaque node metadata; matches
+    // the CLI's `\\`, `\t`, `\n` and `\r` escapes.
+    private func tsvEncoded(_ value: String) -> String {
+        value.replacingOccurrences(of: "\\", with: "\\\\")
+            .replacingOccurrences(of: "\t", with: "\\t")
+            .replacingOccurrences(of: "\n", with: "\\n")
+            .replacingOccurrences(of: "\r", with: "\\r")
+    }
+
+    private func tsvDecoded(_ value: String) -> String {
+        var decoded = ""
+        var escaping = false
+        for character in value {
+            if escaping {
+                switch character {
+                case "t": decoded.append("\t")
+                case "n": decoded.append("\n")
+                case "r": decoded.append("\r")
+                default: decoded.append(character)
+                }
+                escaping = false
+            } else if character == "\\" {
+                escaping = true
+            } else {
+                decoded.append(character)
+            }
+        }
+        if escaping {
+            decoded.append("\\")
+        }
+        return decoded
+    }
+
     private func parseAppliedMove(_ output: String) throws -> AnalysisAppliedMove {
         let line = output
             .split(whereSeparator: \.isNewline)
@@ -33,6 +33,7 @@ private func normalizeEngineAnalysis(
         scoreCp: normalizeScore(analysis.scoreCp, factor: factor),
         scoreMate: normalizeScore(analysis.scoreMate, factor: factor),
         bestMove: analysis.bestMove,
+        bestMoveSan: analysis.bestMoveSan,
         pv: analysis.pv,
         lines: lines.map { normalizeEngineLine($0, factor: factor) }
     )
@@ -277,6 +278,7 @@ private final class RustEngineSession {
                     scoreCp: normalizedSummary.scoreCp,
                     scoreMate: normalizedSummary.scoreMate,
                     bestMove: normalizedSummary.bestMove,
+                    bestMoveSan: normalizedSummary.bestMoveSan,
                     pv: normalizedSummary.pv,
                     lines: normalizedLines
                 )
@@ -320,7 +322,7 @@ private final class RustEngineSession {
 
     private func parseMultipvSummary(_ line: String) throws -> EngineAnalysis {
         let columns = line.split(separator: "\t", omittingEmptySubsequences: false)
-        guard columns.count == 6, let depth = Int(columns[1]) else {
+        guard columns.count >= 6, let depth = Int(columns[1]) else {
             throw RepositoryError.failure("Unexpected engine session output format: \(line)")
         }
 
@@ -328,12 +330,16 @@ private final class RustEngineSession {
         let mate = Int(columns[3])
         let bestMoveText = String(columns[4]).trimmingCharacters(in: .whitespacesAndNewlines)
         let pvText = String(columns[5]).trimmingCharacters(in: .whitespacesAndNewlines)
+        let bestMoveSanText = columns.count >= 7
+            ? String(columns[6]).trimmingCharacters(in: .whitespacesAndNewlines)
+            : ""
 
         return EngineAnalysis(
             depth: depth,
             scoreCp: cp,
             scoreMate: mate,
             bestMove: bestMoveText.isEmpty ? nil : bestMoveText,
+            bestMoveSan: bestMoveSanText.isEmpty ? nil : bestMoveSanText,
             pv: pvText.isEmpty ? [] : pvText.split(separator: " ").map(String.init),
             lines: []
         )
@@ -393,7 +399,7 @@ private final class RustEngineSession {
 
     private func parseLegacyAnalysis(_ line: String) throws -> EngineAnalysis {
         let columns = line.split(separator: "\t", omittingEmptySubsequences: false)
-        guard columns.count == 6, let depth = Int(columns[1]) else {
+        guard columns.count >= 6, let depth = Int(columns[1]) else {
             throw RepositoryError.failure("Unexpected engine session output format: \(line)")
         }
 
@@ -402,12 +408,16 @@ private final class RustEngineSession {
         let bestMoveText = String(columns[4]).trimmingCharacters(in: .whitespacesAndNewlines)
         let pvText = String(columns[5]).trimmingCharacters(in: .whitespacesAndNewlines)
         let pv = pvText.isEmpty ? [] : pvText.split(separator: " ").map(String.init)
+        let bestMoveSanText = columns.count >= 7
+            ? String(columns[6]).trimmingCharacters(in: .whitespacesAndNewlines)
+            : ""
 
         return EngineAnalysis(
             depth: depth,
             scoreCp: cp,
             scoreMate: mate,
             bestMove: bestMoveText.isEmpty ? nil : bestMoveText,
+            bestMoveSan: bestMoveSanText.isEmpty ? nil : bestMoveSanText,
             pv: pv,
             lines: [
                 EngineLine(
@@ -566,13 +576,14 @@ struct RustEngineRepository: EngineRepository {
                 scoreCp: normalizedSummary.scoreCp,
                 scoreMate: normalizedSummary.scoreMate,
                 bestMove: normalizedSummary.bestMove,
+                bestMoveSan: normalizedSummary.bestMoveSan,
                 pv: normalizedSummary.pv,
                 lines: normalizedLines
             )
         }
 
         // Backward compatibility for old analyze output:
-        // depth\tcp\tmate\tbestmove\tpv
+        // depth\tcp\tmate\tbestmove\tpv[\tbestmove_san]
         guard let legacyLine = rows.last else {
             throw RepositoryError.failure("Engine did not return analysis output.")
         }
@@ -582,7 +593,7 @@ struct RustEngineRepository: EngineRepository {
 
     private func parseOneShotSummary(_ line: String) throws -> EngineAnalysis {
         let columns = line.split(separator: "\t", omittingEmptySubsequences: false)
-        guard columns.count == 6, let depth = Int(columns[1]) else {
+        guard columns.count >= 6, let depth = Int(columns[1]) else {
             throw RepositoryError.failure("Unexpected engine output format: \(line)")
         }
 
@@ -590,12 +601,16 @@ struct RustEngineRepository: EngineRepository {
         let mate = Int(columns[3])
         let bestMoveText = String(columns[4]).trimmingCharacters(in: .whitespacesAndNewlines)
         let pvText = String(columns[5]).trimmingCharacters(in: .whitespacesAndNewlines)
+        let bestMoveSanText = columns.count >= 7
+            ? String(columns[6]).trimmingCharacters(in: .whitespacesAndNewlines)
+            : ""
 
         return EngineAnalysis(
             depth: depth,
             scoreCp: cp,
             scoreMate: mate,
             bestMove: bestMoveText.isEmpty ? nil : bestMoveText,
+            bestMoveSan: bestMoveSanText.isEmpty ? nil : bestMoveSanText,
             pv: pvText.isEmpty ? [] : pvText.split(separator: " ").map(String.init),
             lines: []
         )
@@ -655,7 +670,7 @@ struct RustEngineRepository: EngineRepository {
 
     private func parseLegacyOneShotSummary(_ line: String) throws -> EngineAnalysis {
         let columns = line.split(separator: "\t", omittingEmptySubsequences: false)
-        guard columns.count == 5, let depth = Int(columns[0]) else {
+        guard columns.count >= 5, let depth = Int(columns[0]) else {
             throw RepositoryError.failure("Unexpected engine output format: \(line)")
         }
 
@@ -664,12 +679,16 @@ struct RustEngineRepository: EngineRepository {
         let bestMoveText = String(columns[3]).trimmingCharacters(in: .whitespacesAndNewlines)
         let pvText = String(columns[4]).trimmingCharacters(in: .whitespacesAndNewlines)
         let pv = pvText.isEmpty ? [] : pvText.split(separator: " ").map(String.init)
+        let bestMoveSanText = columns.count >= 6
+            ? String(columns[5]).trimmingCharacters(in: .whitespacesAndNewlines)
+            : ""
 
         return EngineAnalysis(
             depth: depth,
             scoreCp: cp,
             scoreMate: mate,
             bestMove: bestMoveText.isEmpty ? nil : bestMoveText,
+            bestMoveSan: bestMoveSanText.isEmpty ? nil : bestMoveSanText,
             pv: pv,
             lines: [
                 EngineLine(
@@ -167,7 +167,8 @@ struct RustImportRepository: ImportRepository {
         guard !clean.isEmpty else { return nil }
 
         let parts = clean.split(separator: "\t", omittingEmptySubsequences: false)
-        guard parts.count == 5 else { return nil }
+        // Newer CLI versions append extra counters (e.g. `invalid`) after `errors`.
+        guard parts.count >= 5 else { return nil }
         guard let total = Int(parts[1]),
               let inserted = Int(parts[2]),
               let skipped = Int(parts[3]),
@@ -204,7 +205,7 @@ struct RustImportRepository: ImportRepository {
         let skipped: Int
         let errors: Int
 
-        if parts.count == 5, parts[0] == "summary",
+        if parts.count >= 5, parts[0] == "summary",
            let parsedTotal = Int(parts[1]),
            let parsedInserted = Int(parts[2]),
            let parsedSkipped = Int(parts[3]),
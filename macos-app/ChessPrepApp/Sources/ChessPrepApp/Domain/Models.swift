@@ -181,6 +181,7 @@ struct AnalysisWorkspaceNodeRecord: Identifiable, Equatable, Sendable {
     let comment: String
     let nags: [String]
     let sortIndex: Int
+    var metadata: String? = nil
 }
 
 struct AnalysisWorkspaceSummary: Identifiable, Equatable, Sendable {
@@ -268,6 +269,7 @@ struct EngineAnalysis: Equatable, Sendable {
     let scoreCp: Int?
     let scoreMate: Int?
     let bestMove: String?
+    var bestMoveSan: String? = nil
     let pv: [String]
     let lines: [EngineLine]
 
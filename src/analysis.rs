@@ -1,17 +1,51 @@
 use std::str::FromStr;
 
+use shakmaty::san::SanPlus;
 use shakmaty::uci::UciMove;
-use shakmaty::{CastlingMode, Chess, EnPassantMode, Position, fen::Fen, san::San};
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{
+    CastlingMode, CastlingSide, Chess, Color, EnPassantMode, Move, Position, Square, fen::Fen,
+    san::San,
+};
 
-use crate::types::{AnalysisError, AppliedMove};
+use crate::types::{AnalysisError, AppliedMove, GameAccuracy, MoveEval, MoveKind, PositionInfo};
+
+/// Evaluations are clamped to this many centipawns before computing loss, so
+/// a move from +15 to +9 in a won position does not count as a blunder.
+const ACCURACY_EVAL_CAP_CP: i32 = 1000;
+const INACCURACY_CP_LOSS: u32 = 50;
+const MISTAKE_CP_LOSS: u32 = 100;
+const BLUNDER_CP_LOSS: u32 = 300;
+
+fn parse_position(fen: &str) -> Result<Chess, AnalysisError> {
+    let parsed_fen = Fen::from_str(fen).map_err(|_| AnalysisError::InvalidFen(fen.to_owned()))?;
+    parsed_fen
+        .into_position(CastlingMode::Standard)
+        .map_err(|_| AnalysisError::InvalidFen(fen.to_owned()))
+}
+
+fn move_kind(mv: Move) -> MoveKind {
+    match mv {
+        Move::Normal {
+            promotion: Some(role),
+            ..
+        } => MoveKind::Promotion(role.char()),
+        Move::Normal {
+            capture: Some(_), ..
+        } => MoveKind::Capture,
+        Move::EnPassant { .. } => MoveKind::EnPassant,
+        Move::Castle { .. } => match mv.castling_side() {
+            Some(CastlingSide::QueenSide) => MoveKind::CastleQueenside,
+            _ => MoveKind::CastleKingside,
+        },
+        Move::Normal { .. } | Move::Put { .. } => MoveKind::Normal,
+    }
+}
 
 // fen is the current position, uci is the candidate move
 pub fn apply_uci_to_fen(fen: &str, uci: &str) -> Result<AppliedMove, AnalysisError> {
     // parses fen format correctly
-    let parsed_fen = Fen::from_str(fen).map_err(|_| AnalysisError::InvalidFen(fen.to_owned()))?;
-    let mut position: Chess = parsed_fen
-        .into_position(CastlingMode::Standard)
-        .map_err(|_| AnalysisError::InvalidFen(fen.to_owned()))?;
+    let mut position = parse_position(fen)?;
 
     // checks move legality
     let parsed_uci = UciMove::from_ascii(uci.as_bytes())
@@ -22,26 +56,87 @@ pub fn apply_uci_to_fen(fen: &str, uci: &str) -> Result<AppliedMove, AnalysisErr
         .to_move(&position)
         .map_err(|_| AnalysisError::IllegalMove(uci.to_owned()))?;
 
+    Ok(play_applied_move(&mut position, mv))
+}
+
+/// Like [`apply_uci_to_fen`], but the move is given in SAN. Text that is not
+/// SAN fails with `InvalidSan`; a well-formed but illegal or ambiguous move
+/// with `IllegalMove`.
+pub fn apply_san_to_fen(fen: &str, san: &str) -> Result<AppliedMove, AnalysisError> {
+    let mut position = parse_position(fen)?;
+    let parsed =
+        SanPlus::from_ascii(san.trim().as_bytes()).map_err(|_| AnalysisError::InvalidSan {
+            index: 0,
+            san: san.to_owned(),
+        })?;
+    let mv = parsed
+        .san
+        .to_move(&position)
+        .map_err(|_| AnalysisError::IllegalMove(san.to_owned()))?;
+
+    Ok(play_applied_move(&mut position, mv))
+}
+
+fn play_applied_move(position: &mut Chess, mv: Move) -> AppliedMove {
     // to be displayed on the frontend
-    let san = San::from_move(&position, mv).to_string();
+    let san = San::from_move(position, mv).to_string();
     let canonical_uci = UciMove::from_move(mv, position.castles().mode()).to_string();
+    let kind = move_kind(mv);
 
     // mutates the position by playing the move
     position.play_unchecked(mv);
-    let next_fen = Fen::from_position(&position, EnPassantMode::Legal).to_string();
+    let next_fen = Fen::from_position(position, EnPassantMode::Legal).to_string();
 
-    Ok(AppliedMove {
+    AppliedMove {
         san,
         uci: canonical_uci,
         fen: next_fen,
-    })
+        kind,
+    }
+}
+
+/// Plays `sans` from the standard starting position and returns the final FEN.
+pub fn fen_after_sans(sans: &[&str]) -> Result<String, AnalysisError> {
+    let mut position = Chess::default();
+    for (index, san) in sans.iter().enumerate() {
+        let invalid_san = || AnalysisError::InvalidSan {
+            index,
+            san: (*san).to_owned(),
+        };
+        let mv = SanPlus::from_ascii(san.trim().as_bytes())
+            .map_err(|_| invalid_san())?
+            .san
+            .to_move(&position)
+            .map_err(|_| invalid_san())?;
+        position.play_unchecked(mv);
+    }
+
+    Ok(Fen::from_position(&position, EnPassantMode::Legal).to_string())
+}
+
+/// Whether `san` is a legal move in the position. Errors only for an invalid
+/// FEN or text that is not SAN; ambiguous SAN counts as illegal.
+pub fn is_legal_san(fen: &str, san: &str) -> Result<bool, AnalysisError> {
+    let position = parse_position(fen)?;
+    let parsed =
+        SanPlus::from_ascii(san.trim().as_bytes()).map_err(|_| AnalysisError::InvalidSan {
+            index: 0,
+            san: san.to_owned(),
+        })?;
+    Ok(parsed.san.to_move(&position).is_ok())
+}
+
+/// Whether `uci` is a legal move in the position. Errors only for an invalid
+/// FEN or text that is not UCI notation.
+pub fn is_legal_uci(fen: &str, uci: &str) -> Result<bool, AnalysisError> {
+    let position = parse_position(fen)?;
+    let parsed = UciMove::from_ascii(uci.as_bytes())
+        .map_err(|_| AnalysisError::InvalidUci(uci.to_owned()))?;
+    Ok(parsed.to_move(&position).is_ok())
 }
 
 pub fn legal_uci_moves_for_fen(fen: &str) -> Result<Vec<String>, AnalysisError> {
-    let parsed_fen = Fen::from_str(fen).map_err(|_| AnalysisError::InvalidFen(fen.to_owned()))?;
-    let position: Chess = parsed_fen
-        .into_position(CastlingMode::Standard)
-        .map_err(|_| AnalysisError::InvalidFen(fen.to_owned()))?;
+    let position = parse_position(fen)?;
 
     let castling_mode = position.castles().mode();
     let legal_moves = position.legal_moves();
@@ -52,6 +147,107 @@ pub fn legal_uci_moves_for_fen(fen: &str) -> Result<Vec<String>, AnalysisError>
         .collect())
 }
 
+/// The single legal move in UCI form when the position is forced, otherwise
+/// `None` (including checkmate and stalemate).
+pub fn only_legal_move(fen: &str) -> Result<Option<String>, AnalysisError> {
+    let mut moves = legal_uci_moves_for_fen(fen)?;
+    Ok(if moves.len() == 1 { moves.pop() } else { None })
+}
+
+/// Parses a lowercase UCI square name such as `e4`. The crate itself only
+/// parses whole UCI moves, so this is for callers that split them up.
+pub fn square_from_uci(square: &str) -> Result<Square, AnalysisError> {
+    Square::from_ascii(square.as_bytes())
+        .map_err(|_| AnalysisError::InvalidSquare(square.to_owned()))
+}
+
+/// Per-player centipawn loss and move-quality tallies. A move losing at least
+/// 300cp is a blunder, 100cp a mistake and 50cp an inaccuracy, after clamping
+/// both evaluations to ±1000cp.
+pub fn game_accuracy(evals: &[MoveEval]) -> GameAccuracy {
+    let mut accuracy = GameAccuracy::default();
+    let mut total_loss = [0u64; 2];
+
+    for eval in evals {
+        let before = eval
+            .eval_before_cp
+            .clamp(-ACCURACY_EVAL_CAP_CP, ACCURACY_EVAL_CAP_CP);
+        let after = eval
+            .eval_after_cp
+            .clamp(-ACCURACY_EVAL_CAP_CP, ACCURACY_EVAL_CAP_CP);
+        let (player, loss_total, delta) = match eval.mover {
+            Color::White => (&mut accuracy.white, &mut total_loss[0], before - after),
+            Color::Black => (&mut accuracy.black, &mut total_loss[1], after - before),
+        };
+        let loss = delta.max(0).unsigned_abs();
+
+        player.moves += 1;
+        *loss_total += u64::from(loss);
+        if loss >= BLUNDER_CP_LOSS {
+            player.blunders += 1;
+        } else if loss >= MISTAKE_CP_LOSS {
+            player.mistakes += 1;
+        } else if loss >= INACCURACY_CP_LOSS {
+            player.inaccuracies += 1;
+        }
+    }
+
+    for (player, loss_total) in [
+        (&mut accuracy.white, total_loss[0]),
+        (&mut accuracy.black, total_loss[1]),
+    ] {
+        if player.moves > 0 {
+            player.average_cp_loss = loss_total as f64 / player.moves as f64;
+        }
+    }
+    accuracy
+}
+
+/// Polyglot-compatible 64-bit Zobrist key of the position. Move counters are
+/// not part of the key, so transpositions share it.
+pub fn position_key(fen: &str) -> Result<u64, AnalysisError> {
+    Ok(zobrist_key(&parse_position(fen)?))
+}
+
+pub(crate) fn zobrist_key<P: Position>(position: &P) -> u64 {
+    position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0
+}
+
+/// The PGN result a finished position implies: checkmate loses for the side
+/// to move, and stalemate or insufficient material is a draw. `None` while
+/// the game can go on.
+pub fn result_from_position(fen: &str) -> Result<Option<String>, AnalysisError> {
+    Ok(position_result(&parse_position(fen)?).map(ToOwned::to_owned))
+}
+
+/// [`result_from_position`] for an already-replayed position.
+pub(crate) fn position_result<P: Position>(position: &P) -> Option<&'static str> {
+    if position.is_checkmate() {
+        Some(match position.turn() {
+            Color::White => "0-1",
+            Color::Black => "1-0",
+        })
+    } else if position.is_stalemate() || position.is_insufficient_material() {
+        Some("1/2-1/2")
+    } else {
+        None
+    }
+}
+
+/// Side to move, check, legal move count and move counters of the position,
+/// for display without replaying it in the caller.
+pub fn position_info(fen: &str) -> Result<PositionInfo, AnalysisError> {
+    let position = parse_position(fen)?;
+
+    Ok(PositionInfo {
+        side_to_move: position.turn(),
+        in_check: position.is_check(),
+        legal_move_count: position.legal_moves().len(),
+        fullmove: position.fullmoves().get(),
+        halfmove: position.halfmoves(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,6 +261,46 @@ mod tests {
         assert!(!out.fen.is_empty());
     }
 
+    #[test]
+    fn applied_move_reports_move_kind() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(
+            apply_uci_to_fen(start, "e2e4").expect("legal move").kind,
+            MoveKind::Normal
+        );
+
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let kingside = apply_uci_to_fen(castling, "e1g1").expect("castling is legal");
+        assert_eq!(kingside.kind, MoveKind::CastleKingside);
+        assert_eq!(kingside.san, "O-O");
+        assert_eq!(
+            apply_uci_to_fen(castling, "e1c1")
+                .expect("castling is legal")
+                .kind,
+            MoveKind::CastleQueenside
+        );
+
+        let en_passant = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let applied = apply_uci_to_fen(en_passant, "e5f6").expect("en passant is legal");
+        assert_eq!(applied.kind, MoveKind::EnPassant);
+        assert!(matches!(
+            apply_uci_to_fen(en_passant, "e5d6").unwrap_err(),
+            AnalysisError::IllegalMove(_)
+        ));
+
+        let promotion = "8/P6k/8/8/8/8/8/K7 w - - 0 1";
+        let applied = apply_uci_to_fen(promotion, "a7a8n").expect("promotion is legal");
+        assert_eq!(applied.kind, MoveKind::Promotion('n'));
+
+        let capture = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+        assert_eq!(
+            apply_uci_to_fen(capture, "e4d5")
+                .expect("capture is legal")
+                .kind,
+            MoveKind::Capture
+        );
+    }
+
     #[test]
     fn rejects_invalid_fen() {
         let err = apply_uci_to_fen("not-a-fen", "e2e4").unwrap_err();
@@ -84,6 +320,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn apply_san_matches_the_uci_equivalent() {
+        let start = "rn1qkbnr/pppbpppp/8/3p4/8/3P4/PPP1PPPP/RNBQKBNR w KQkq - 0 2";
+        let out = apply_san_to_fen(start, "Nf3").expect("legal move");
+        assert_eq!(out, apply_uci_to_fen(start, "g1f3").expect("legal move"));
+        assert_eq!(out.san, "Nf3");
+        assert_eq!(out.uci, "g1f3");
+
+        assert!(matches!(
+            apply_san_to_fen(start, "Nf6").unwrap_err(),
+            AnalysisError::IllegalMove(san) if san == "Nf6"
+        ));
+        assert!(matches!(
+            apply_san_to_fen(start, "Qz9").unwrap_err(),
+            AnalysisError::InvalidSan { .. }
+        ));
+    }
+
     #[test]
     fn rejects_illegal_uci() {
         let start = "rn1qkbnr/pppbpppp/8/3p4/8/3P4/PPP1PPPP/RNBQKBNR w KQkq - 0 2";
@@ -111,4 +365,203 @@ mod tests {
             other => panic!("unexpected error: {other:?}"),
         }
     }
+
+    #[test]
+    fn fen_after_sans_plays_a_legal_line() {
+        let fen = fen_after_sans(&["e4", "e5", "Nf3"]).expect("legal line");
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(
+            fen_after_sans(&[]).expect("empty line"),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn fen_after_sans_reports_the_illegal_move_index() {
+        let err = fen_after_sans(&["e4", "e5", "Ke3"]).unwrap_err();
+        assert!(matches!(
+            err,
+            AnalysisError::InvalidSan { index: 2, ref san } if san == "Ke3"
+        ));
+
+        let err = fen_after_sans(&["e4", "??"]).unwrap_err();
+        assert!(matches!(err, AnalysisError::InvalidSan { index: 1, .. }));
+    }
+
+    #[test]
+    fn is_legal_san_checks_without_applying() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(is_legal_san(start, "Nf3").expect("valid input"));
+        assert!(is_legal_san(start, "e4").expect("valid input"));
+        assert!(!is_legal_san(start, "e5").expect("valid input"));
+        assert!(!is_legal_san(start, "Qh5").expect("valid input"));
+
+        assert!(matches!(
+            is_legal_san(start, "Zz9").unwrap_err(),
+            AnalysisError::InvalidSan { .. }
+        ));
+        assert!(matches!(
+            is_legal_san("not-a-fen", "e4").unwrap_err(),
+            AnalysisError::InvalidFen(_)
+        ));
+    }
+
+    #[test]
+    fn is_legal_uci_checks_without_applying() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(is_legal_uci(start, "g1f3").expect("valid input"));
+        assert!(!is_legal_uci(start, "e2e5").expect("valid input"));
+        assert!(!is_legal_uci(start, "e7e5").expect("valid input"));
+
+        assert!(matches!(
+            is_legal_uci(start, "bad").unwrap_err(),
+            AnalysisError::InvalidUci(_)
+        ));
+        assert!(matches!(
+            is_legal_uci("not-a-fen", "e2e4").unwrap_err(),
+            AnalysisError::InvalidFen(_)
+        ));
+    }
+
+    #[test]
+    fn only_legal_move_detects_forced_positions() {
+        let forced = "k7/2K5/8/8/8/8/8/1R6 b - - 0 1";
+        assert_eq!(
+            only_legal_move(forced).expect("valid fen").as_deref(),
+            Some("a8a7")
+        );
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(only_legal_move(start).expect("valid fen"), None);
+    }
+
+    #[test]
+    fn square_from_uci_parses_valid_squares() {
+        let square = square_from_uci("e4").expect("e4 is a square");
+        assert_eq!(square, Square::E4);
+        assert_eq!(square.to_string(), "e4");
+    }
+
+    #[test]
+    fn square_from_uci_rejects_invalid_input() {
+        for input in ["e9", "i1", "e", "e4e5", ""] {
+            let err = square_from_uci(input).unwrap_err();
+            assert!(matches!(err, AnalysisError::InvalidSquare(value) if value == input));
+        }
+    }
+
+    fn move_eval(ply: usize, mover: Color, before: i32, after: i32) -> MoveEval {
+        MoveEval {
+            ply,
+            mover,
+            san: String::new(),
+            eval_before_cp: before,
+            eval_after_cp: after,
+        }
+    }
+
+    #[test]
+    fn game_accuracy_tallies_losses_per_color() {
+        let evals = [
+            move_eval(1, Color::White, 20, 30),
+            move_eval(2, Color::Black, 30, 90),
+            move_eval(3, Color::White, 90, -60),
+            move_eval(4, Color::Black, -60, 300),
+            move_eval(5, Color::White, 300, 230),
+            move_eval(6, Color::Black, 230, 240),
+            move_eval(7, Color::White, 1500, 1100),
+        ];
+
+        let accuracy = game_accuracy(&evals);
+        assert_eq!(accuracy.white.moves, 4);
+        assert_eq!(accuracy.white.inaccuracies, 1);
+        assert_eq!(accuracy.white.mistakes, 1);
+        assert_eq!(accuracy.white.blunders, 0);
+        assert_eq!(accuracy.white.average_cp_loss, 220.0 / 4.0);
+
+        assert_eq!(accuracy.black.moves, 3);
+        assert_eq!(accuracy.black.inaccuracies, 1);
+        assert_eq!(accuracy.black.mistakes, 0);
+        assert_eq!(accuracy.black.blunders, 1);
+        assert_eq!(accuracy.black.average_cp_loss, 430.0 / 3.0);
+
+        assert_eq!(game_accuracy(&[]), GameAccuracy::default());
+    }
+
+    #[test]
+    fn position_key_matches_for_transpositions() {
+        let via_kingside = fen_after_sans(&["Nf3", "Nf6", "Nc3"]).expect("legal line");
+        let via_queenside = fen_after_sans(&["Nc3", "Nf6", "Nf3"]).expect("legal line");
+        let key = position_key(&via_kingside).expect("valid fen");
+        assert_eq!(key, position_key(&via_queenside).expect("valid fen"));
+
+        let other = fen_after_sans(&["Nc3", "Nc6", "Nf3"]).expect("legal line");
+        assert_ne!(key, position_key(&other).expect("valid fen"));
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(
+            position_key(start).expect("valid fen"),
+            0x463b_9618_1691_fc9c
+        );
+        assert!(matches!(
+            position_key("not-a-fen").unwrap_err(),
+            AnalysisError::InvalidFen(_)
+        ));
+    }
+
+    #[test]
+    fn result_from_position_covers_each_terminal_case() {
+        let fools_mate = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        assert_eq!(
+            result_from_position(fools_mate).expect("valid fen"),
+            Some("0-1".to_string())
+        );
+        let scholars_mate = "r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4";
+        assert_eq!(
+            result_from_position(scholars_mate).expect("valid fen"),
+            Some("1-0".to_string())
+        );
+        let stalemate = "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1";
+        assert_eq!(
+            result_from_position(stalemate).expect("valid fen"),
+            Some("1/2-1/2".to_string())
+        );
+        let bare_kings = "8/8/4k3/8/8/3K4/8/8 w - - 0 1";
+        assert_eq!(
+            result_from_position(bare_kings).expect("valid fen"),
+            Some("1/2-1/2".to_string())
+        );
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(result_from_position(start).expect("valid fen"), None);
+        assert!(matches!(
+            result_from_position("not-a-fen").unwrap_err(),
+            AnalysisError::InvalidFen(_)
+        ));
+    }
+
+    #[test]
+    fn position_info_for_startpos() {
+        let info = position_info("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .expect("startpos should parse");
+        assert_eq!(info.side_to_move, shakmaty::Color::White);
+        assert!(!info.in_check);
+        assert_eq!(info.legal_move_count, 20);
+        assert_eq!(info.fullmove, 1);
+        assert_eq!(info.halfmove, 0);
+    }
+
+    #[test]
+    fn position_info_reports_check() {
+        // 1. e4 d6 2. Bb5+: c6, Nc6, Nd7, Bd7 and Qd7 all block.
+        let info = position_info("rnbqkbnr/ppp1pppp/3p4/1B6/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 1 2")
+            .expect("fen should parse");
+        assert_eq!(info.side_to_move, shakmaty::Color::Black);
+        assert!(info.in_check);
+        assert_eq!(info.legal_move_count, 5);
+        assert_eq!(info.fullmove, 2);
+        assert_eq!(info.halfmove, 1);
+    }
 }
@@ -1,10 +1,12 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    path::Path,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use rusqlite::{Connection, OptionalExtension, params};
 
+use crate::db::with_attached_db;
 use crate::types::{
     AnalysisWorkspaceError, AnalysisWorkspaceNode, AnalysisWorkspaceSummary,
     LoadedAnalysisWorkspace,
@@ -34,6 +36,9 @@ fn ensure_schema(conn: &Connection) -> Result<(), AnalysisWorkspaceError> {
         CREATE INDEX IF NOT EXISTS idx_analysis_workspaces_game
         ON analysis_workspaces(source_db_path, game_id, updated_at DESC, id DESC);
 
+        CREATE INDEX IF NOT EXISTS idx_analysis_workspaces_updated
+        ON analysis_workspaces(updated_at DESC, id DESC);
+
         CREATE TABLE IF NOT EXISTS analysis_nodes (
             workspace_id INTEGER NOT NULL,
             node_id TEXT NOT NULL,
@@ -44,6 +49,7 @@ fn ensure_schema(conn: &Connection) -> Result<(), AnalysisWorkspaceError> {
             comment TEXT NOT NULL DEFAULT '',
             nags TEXT NOT NULL DEFAULT '',
             sort_index INTEGER NOT NULL DEFAULT 0,
+            metadata TEXT,
             PRIMARY KEY (workspace_id, node_id),
             FOREIGN KEY (workspace_id) REFERENCES analysis_workspaces(id) ON DELETE CASCADE
         );
@@ -52,9 +58,94 @@ fn ensure_schema(conn: &Connection) -> Result<(), AnalysisWorkspaceError> {
         ON analysis_nodes(workspace_id, parent_node_id, sort_index, node_id);
         ",
     )?;
+
+    let has_metadata: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('analysis_nodes') WHERE name = 'metadata')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_metadata {
+        conn.execute_batch("ALTER TABLE analysis_nodes ADD COLUMN metadata TEXT;")?;
+    }
     Ok(())
 }
 
+/// Like [`save_analysis_workspace`], but first attaches `source_db_path` and
+/// rejects the save unless `game_id` exists in its `games` table.
+pub fn save_analysis_workspace_verified(
+    analysis_db_path: &str,
+    source_db_path: &str,
+    game_id: i64,
+    name: &str,
+    root_node_id: &str,
+    current_node_id: Option<&str>,
+    nodes: &[AnalysisWorkspaceNode],
+) -> Result<i64, AnalysisWorkspaceError> {
+    let mut conn = Connection::open(analysis_db_path)?;
+    verify_source_game(&mut conn, source_db_path.trim(), game_id)?;
+    drop(conn);
+
+    save_analysis_workspace(
+        analysis_db_path,
+        source_db_path,
+        game_id,
+        name,
+        root_node_id,
+        current_node_id,
+        nodes,
+    )
+}
+
+/// Attaches the games database at `source_db_path` as `source_games` and runs
+/// `f` with whether it has a `games` table, detaching it again afterwards.
+/// Fails without touching the path when the file does not exist, since
+/// ATTACH would create it.
+fn with_source_games<T>(
+    conn: &mut Connection,
+    source_db_path: &str,
+    f: impl FnOnce(&Connection, bool) -> Result<T, AnalysisWorkspaceError>,
+) -> Result<T, AnalysisWorkspaceError> {
+    if !Path::new(source_db_path).is_file() {
+        return Err(AnalysisWorkspaceError::InvalidInput(format!(
+            "source database '{source_db_path}' does not exist"
+        )));
+    }
+
+    with_attached_db(conn, source_db_path, "source_games", |conn| {
+        let has_games: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM source_games.sqlite_master WHERE type = 'table' AND name = 'games')",
+            [],
+            |row| row.get(0),
+        )?;
+        f(conn, has_games)
+    })
+}
+
+fn verify_source_game(
+    conn: &mut Connection,
+    source_db_path: &str,
+    game_id: i64,
+) -> Result<(), AnalysisWorkspaceError> {
+    let found = with_source_games(conn, source_db_path, |conn, has_games| {
+        if !has_games {
+            return Ok(false);
+        }
+        Ok(conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM source_games.games WHERE rowid = ?1)",
+            params![game_id],
+            |row| row.get(0),
+        )?)
+    })?;
+
+    if found {
+        Ok(())
+    } else {
+        Err(AnalysisWorkspaceError::InvalidInput(format!(
+            "game {game_id} was not found in source database '{source_db_path}'"
+        )))
+    }
+}
+
 pub fn save_analysis_workspace(
     analysis_db_path: &str,
     source_db_path: &str,
@@ -106,7 +197,15 @@ pub fn save_analysis_workspace(
         }
     }
 
-    let node_ids: HashSet<&str> = nodes.iter().map(|node| node.id.trim()).collect();
+    let mut node_ids: HashSet<&str> = HashSet::with_capacity(nodes.len());
+    for node in nodes {
+        let node_id = node.id.trim();
+        if !node_ids.insert(node_id) {
+            return Err(AnalysisWorkspaceError::InvalidInput(format!(
+                "duplicate node id '{node_id}' in node payload"
+            )));
+        }
+    }
 
     if !node_ids.contains(root_node_id) {
         return Err(AnalysisWorkspaceError::InvalidInput(format!(
@@ -137,6 +236,8 @@ pub fn save_analysis_workspace(
         }
     }
 
+    validate_node_tree(root_node_id, nodes)?;
+
     let now = now_unix_seconds()?;
 
     let mut conn = Connection::open(analysis_db_path)?;
@@ -165,8 +266,8 @@ pub fn save_analysis_workspace(
         let mut stmt = tx.prepare(
             "
             INSERT INTO analysis_nodes (
-                workspace_id, node_id, parent_node_id, san, uci, fen, comment, nags, sort_index
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                workspace_id, node_id, parent_node_id, san, uci, fen, comment, nags, sort_index, metadata
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
             ",
         )?;
 
@@ -200,7 +301,8 @@ pub fn save_analysis_workspace(
                 fen,
                 comment,
                 nags,
-                node.sort_index
+                node.sort_index,
+                node.metadata.as_deref()
             ])?;
         }
     }
@@ -265,6 +367,69 @@ pub fn delete_analysis_workspace(
     Ok(())
 }
 
+/// Deletes every workspace saved for `game_id` in `source_db_path` and
+/// returns how many were removed. Nodes are removed by the cascading FK.
+pub fn delete_workspaces_for_game(
+    analysis_db_path: &str,
+    source_db_path: &str,
+    game_id: i64,
+) -> Result<u64, AnalysisWorkspaceError> {
+    let conn = Connection::open(analysis_db_path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    ensure_schema(&conn)?;
+
+    let changed = conn.execute(
+        "
+        DELETE FROM analysis_workspaces
+        WHERE source_db_path = ?1 AND game_id = ?2
+        ",
+        params![source_db_path.trim(), game_id],
+    )?;
+
+    Ok(changed as u64)
+}
+
+/// Deletes nodes whose workspace no longer exists and nodes whose parent is
+/// missing, repeating until no such node is left so whole detached subtrees
+/// go. Returns the number of nodes removed. Such rows only appear if writes
+/// ran with foreign keys off.
+pub fn repair_analysis_db(analysis_db_path: &str) -> Result<u64, AnalysisWorkspaceError> {
+    let mut conn = Connection::open(analysis_db_path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    ensure_schema(&conn)?;
+
+    let tx = conn.transaction()?;
+    let mut removed = tx.execute(
+        "
+        DELETE FROM analysis_nodes
+        WHERE workspace_id NOT IN (SELECT id FROM analysis_workspaces)
+        ",
+        [],
+    )? as u64;
+    loop {
+        let detached = tx.execute(
+            "
+            DELETE FROM analysis_nodes
+            WHERE parent_node_id IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1
+                  FROM analysis_nodes AS parent
+                  WHERE parent.workspace_id = analysis_nodes.workspace_id
+                    AND parent.node_id = analysis_nodes.parent_node_id
+              )
+            ",
+            [],
+        )?;
+        if detached == 0 {
+            break;
+        }
+        removed += detached as u64;
+    }
+    tx.commit()?;
+
+    Ok(removed)
+}
+
 pub fn list_analysis_workspaces(
     analysis_db_path: &str,
     source_db_path: &str,
@@ -283,18 +448,37 @@ pub fn list_analysis_workspaces(
         ",
     )?;
 
-    let rows = stmt.query_map(params![source_db_path.trim(), game_id], |row| {
-        Ok(AnalysisWorkspaceSummary {
-            id: row.get(0)?,
-            source_db_path: row.get(1)?,
-            game_id: row.get(2)?,
-            name: row.get(3)?,
-            root_node_id: row.get(4)?,
-            current_node_id: row.get(5)?,
-            created_at: row.get(6)?,
-            updated_at: row.get(7)?,
-        })
-    })?;
+    let rows = stmt.query_map(
+        params![source_db_path.trim(), game_id],
+        workspace_summary_from_row,
+    )?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+/// Most recently updated workspaces across every source database and game.
+pub fn list_recent_workspaces(
+    analysis_db_path: &str,
+    limit: u32,
+) -> Result<Vec<AnalysisWorkspaceSummary>, AnalysisWorkspaceError> {
+    let conn = Connection::open(analysis_db_path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    ensure_schema(&conn)?;
+
+    let mut stmt = conn.prepare(
+        "
+        SELECT id, source_db_path, game_id, name, root_node_id, current_node_id, created_at, updated_at
+        FROM analysis_workspaces
+        ORDER BY updated_at DESC, id DESC
+        LIMIT ?1
+        ",
+    )?;
+
+    let rows = stmt.query_map(params![limit], workspace_summary_from_row)?;
 
     let mut out = Vec::new();
     for row in rows {
@@ -303,6 +487,73 @@ pub fn list_analysis_workspaces(
     Ok(out)
 }
 
+/// Workspaces saved against `games_db_path` whose game is no longer in its
+/// `games` table, most recently updated first.
+pub fn list_dangling_workspaces(
+    analysis_db_path: &str,
+    games_db_path: &str,
+) -> Result<Vec<AnalysisWorkspaceSummary>, AnalysisWorkspaceError> {
+    let games_db_path = games_db_path.trim();
+    let mut conn = Connection::open(analysis_db_path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    ensure_schema(&conn)?;
+
+    with_source_games(&mut conn, games_db_path, |conn, has_games| {
+        if !has_games {
+            return Err(AnalysisWorkspaceError::InvalidInput(format!(
+                "source database '{games_db_path}' has no games table"
+            )));
+        }
+
+        let mut stmt = conn.prepare(
+            "
+            SELECT id, source_db_path, game_id, name, root_node_id, current_node_id, created_at, updated_at
+            FROM analysis_workspaces
+            WHERE source_db_path = ?1
+              AND NOT EXISTS (SELECT 1 FROM source_games.games WHERE rowid = analysis_workspaces.game_id)
+            ORDER BY updated_at DESC, id DESC
+            ",
+        )?;
+        let rows = stmt.query_map(params![games_db_path], workspace_summary_from_row)?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
+        }
+        Ok(out)
+    })
+}
+
+/// Maps each game of `source_db_path` that has a workspace to its most
+/// recently updated workspace id.
+pub(crate) fn latest_workspace_ids(
+    analysis_db_path: &str,
+    source_db_path: &str,
+) -> Result<HashMap<i64, i64>, AnalysisWorkspaceError> {
+    let conn = Connection::open(analysis_db_path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    ensure_schema(&conn)?;
+
+    let mut stmt = conn.prepare(
+        "
+        SELECT game_id, id
+        FROM analysis_workspaces
+        WHERE source_db_path = ?1
+        ORDER BY updated_at DESC, id DESC
+        ",
+    )?;
+    let rows = stmt.query_map(params![source_db_path.trim()], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut latest = HashMap::new();
+    for row in rows {
+        let (game_id, workspace_id) = row?;
+        latest.entry(game_id).or_insert(workspace_id);
+    }
+    Ok(latest)
+}
+
 pub fn load_analysis_workspace(
     analysis_db_path: &str,
     workspace_id: i64,
@@ -319,25 +570,14 @@ pub fn load_analysis_workspace(
             WHERE id = ?1
             ",
             params![workspace_id],
-            |row| {
-                Ok(AnalysisWorkspaceSummary {
-                    id: row.get(0)?,
-                    source_db_path: row.get(1)?,
-                    game_id: row.get(2)?,
-                    name: row.get(3)?,
-                    root_node_id: row.get(4)?,
-                    current_node_id: row.get(5)?,
-                    created_at: row.get(6)?,
-                    updated_at: row.get(7)?,
-                })
-            },
+            workspace_summary_from_row,
         )
         .optional()?
         .ok_or(AnalysisWorkspaceError::NotFound(workspace_id))?;
 
     let mut stmt = conn.prepare(
         "
-        SELECT node_id, parent_node_id, san, uci, fen, comment, nags, sort_index
+        SELECT node_id, parent_node_id, san, uci, fen, comment, nags, sort_index, metadata
         FROM analysis_nodes
         WHERE workspace_id = ?1
         ORDER BY
@@ -359,6 +599,7 @@ pub fn load_analysis_workspace(
             comment: row.get(5)?,
             nags: parse_nags(&nags_text),
             sort_index: row.get(7)?,
+            metadata: row.get(8)?,
         })
     })?;
 
@@ -367,9 +608,106 @@ pub fn load_analysis_workspace(
         nodes.push(row?);
     }
 
+    if nodes.is_empty() {
+        return Err(AnalysisWorkspaceError::Corrupt { workspace_id });
+    }
+
     Ok(LoadedAnalysisWorkspace { workspace, nodes })
 }
 
+fn workspace_summary_from_row(
+    row: &rusqlite::Row<'_>,
+) -> rusqlite::Result<AnalysisWorkspaceSummary> {
+    Ok(AnalysisWorkspaceSummary {
+        id: row.get(0)?,
+        source_db_path: row.get(1)?,
+        game_id: row.get(2)?,
+        name: row.get(3)?,
+        root_node_id: row.get(4)?,
+        current_node_id: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+/// Groups of node ids whose positions are identical once the halfmove and
+/// fullmove counters are ignored, i.e. lines that transpose. Groups follow
+/// the order in which their first node appears; unique positions are omitted.
+pub fn find_transpositions(loaded: &LoadedAnalysisWorkspace) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut group_by_position: HashMap<String, usize> = HashMap::new();
+    for node in &loaded.nodes {
+        let position = node
+            .fen
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let index = *group_by_position.entry(position).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(node.id.clone());
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+fn validate_node_tree(
+    root_node_id: &str,
+    nodes: &[AnalysisWorkspaceNode],
+) -> Result<(), AnalysisWorkspaceError> {
+    let parents: HashMap<&str, Option<&str>> = nodes
+        .iter()
+        .map(|node| {
+            let parent = node
+                .parent_id
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty());
+            (node.id.trim(), parent)
+        })
+        .collect();
+
+    if let Some(Some(parent)) = parents.get(root_node_id) {
+        return Err(AnalysisWorkspaceError::InvalidInput(format!(
+            "root node '{root_node_id}' must not have a parent, found '{parent}'"
+        )));
+    }
+
+    if let Some(extra_root) = nodes
+        .iter()
+        .map(|node| node.id.trim())
+        .find(|id| *id != root_node_id && parents.get(id).copied().flatten().is_none())
+    {
+        return Err(AnalysisWorkspaceError::InvalidInput(format!(
+            "node '{extra_root}' has no parent but is not the root node '{root_node_id}'"
+        )));
+    }
+
+    for node in nodes {
+        let node_id = node.id.trim();
+        let mut current = node_id;
+        let mut steps = 0usize;
+        while let Some(Some(parent)) = parents.get(current) {
+            steps += 1;
+            if steps > nodes.len() {
+                return Err(AnalysisWorkspaceError::InvalidInput(format!(
+                    "parent cycle detected involving node '{node_id}'"
+                )));
+            }
+            current = parent;
+        }
+        if current != root_node_id {
+            return Err(AnalysisWorkspaceError::InvalidInput(format!(
+                "node '{node_id}' is not reachable from root node '{root_node_id}'"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 fn serialize_nags(nags: &[String]) -> String {
     nags.iter()
         .map(|value| value.trim())
@@ -434,6 +772,7 @@ mod tests {
                 comment: "".to_string(),
                 nags: vec![],
                 sort_index: 0,
+                metadata: None,
             },
             AnalysisWorkspaceNode {
                 id: "n1".to_string(),
@@ -444,6 +783,7 @@ mod tests {
                 comment: "good practical move".to_string(),
                 nags: vec!["!".to_string()],
                 sort_index: 0,
+                metadata: None,
             },
         ];
 
@@ -514,6 +854,7 @@ mod tests {
                 comment: "".to_string(),
                 nags: vec![],
                 sort_index: 0,
+                metadata: None,
             },
             AnalysisWorkspaceNode {
                 id: "n1".to_string(),
@@ -524,6 +865,7 @@ mod tests {
                 comment: "".to_string(),
                 nags: vec![],
                 sort_index: 0,
+                metadata: None,
             },
         ];
 
@@ -552,4 +894,317 @@ mod tests {
             .expect("list after delete should succeed");
         assert!(list_after_delete.is_empty());
     }
+
+    fn tree_node(id: &str, parent_id: Option<&str>) -> AnalysisWorkspaceNode {
+        AnalysisWorkspaceNode {
+            id: id.to_string(),
+            parent_id: parent_id.map(ToOwned::to_owned),
+            san: None,
+            uci: None,
+            fen: "startfen".to_string(),
+            comment: "".to_string(),
+            nags: vec![],
+            sort_index: 0,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_node_ids_on_save() {
+        let db_path = unique_temp_db_path();
+        let db_path_str = db_path.to_str().expect("db path should be utf-8");
+
+        let nodes = vec![
+            tree_node("root", None),
+            tree_node("n1", Some("root")),
+            tree_node(" n1 ", Some("root")),
+        ];
+
+        let err = save_analysis_workspace(
+            db_path_str,
+            "/tmp/source.sqlite",
+            5,
+            "Duplicate Ids",
+            "root",
+            None,
+            &nodes,
+        )
+        .expect_err("save should fail");
+
+        match err {
+            AnalysisWorkspaceError::InvalidInput(message) => {
+                assert!(message.contains("duplicate node id 'n1'"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_parent_cycles_on_save() {
+        let db_path = unique_temp_db_path();
+        let db_path_str = db_path.to_str().expect("db path should be utf-8");
+
+        let nodes = vec![
+            tree_node("root", None),
+            tree_node("a", Some("b")),
+            tree_node("b", Some("a")),
+        ];
+
+        let err = save_analysis_workspace(
+            db_path_str,
+            "/tmp/source.sqlite",
+            6,
+            "Cycle",
+            "root",
+            None,
+            &nodes,
+        )
+        .expect_err("save should fail");
+
+        match err {
+            AnalysisWorkspaceError::InvalidInput(message) => {
+                assert!(message.contains("parent cycle"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_multiple_roots_on_save() {
+        let db_path = unique_temp_db_path();
+        let db_path_str = db_path.to_str().expect("db path should be utf-8");
+
+        let nodes = vec![
+            tree_node("root", None),
+            tree_node("n1", Some("root")),
+            tree_node("stray", None),
+        ];
+
+        let err = save_analysis_workspace(
+            db_path_str,
+            "/tmp/source.sqlite",
+            6,
+            "Two Roots",
+            "root",
+            None,
+            &nodes,
+        )
+        .expect_err("save should fail");
+
+        match err {
+            AnalysisWorkspaceError::InvalidInput(message) => {
+                assert!(message.contains("'stray' has no parent"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn loaded_workspace_children_and_mainline() {
+        let mut sideline = tree_node("n1b", Some("root"));
+        sideline.sort_index = 1;
+        let mut mainline_move = tree_node("n1a", Some("root"));
+        mainline_move.sort_index = 0;
+
+        let loaded = LoadedAnalysisWorkspace {
+            workspace: AnalysisWorkspaceSummary {
+                id: 1,
+                source_db_path: "/tmp/source.sqlite".to_string(),
+                game_id: 1,
+                name: "Branched".to_string(),
+                root_node_id: "root".to_string(),
+                current_node_id: None,
+                created_at: 0,
+                updated_at: 0,
+            },
+            nodes: vec![
+                tree_node("root", None),
+                sideline,
+                tree_node("n2b", Some("n1b")),
+                mainline_move,
+                tree_node("n2a", Some("n1a")),
+            ],
+        };
+
+        let children: Vec<&str> = loaded
+            .children_of("root")
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        assert_eq!(children, vec!["n1a", "n1b"]);
+        assert!(loaded.children_of("n2a").is_empty());
+
+        let mainline: Vec<&str> = loaded
+            .mainline()
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        assert_eq!(mainline, vec!["root", "n1a", "n2a"]);
+    }
+
+    #[test]
+    fn recent_workspaces_span_all_games() {
+        let db_path = unique_temp_db_path();
+        let db_path_str = db_path.to_str().expect("db path should be utf-8");
+
+        let nodes = vec![tree_node("root", None)];
+        let first = save_analysis_workspace(
+            db_path_str,
+            "/tmp/source.sqlite",
+            1,
+            "Game One",
+            "root",
+            None,
+            &nodes,
+        )
+        .expect("first save should succeed");
+        let second = save_analysis_workspace(
+            db_path_str,
+            "/tmp/other.sqlite",
+            2,
+            "Game Two",
+            "root",
+            None,
+            &nodes,
+        )
+        .expect("second save should succeed");
+
+        let recent = list_recent_workspaces(db_path_str, 10).expect("recent list should work");
+        let ids: Vec<i64> = recent.iter().map(|workspace| workspace.id).collect();
+        assert_eq!(ids, vec![second, first]);
+        assert_eq!(recent[0].game_id, 2);
+        assert_eq!(recent[1].game_id, 1);
+
+        let limited = list_recent_workspaces(db_path_str, 1).expect("limited list should work");
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].id, second);
+
+        fs::remove_file(db_path).expect("cleanup should work");
+    }
+
+    #[test]
+    fn delete_workspaces_for_game_removes_all_and_cascades_nodes() {
+        let db_path = unique_temp_db_path();
+        let db_path_str = db_path.to_str().expect("db path should be utf-8");
+
+        let nodes = vec![tree_node("root", None), tree_node("n1", Some("root"))];
+        for name in ["Prep A", "Prep B"] {
+            save_analysis_workspace(
+                db_path_str,
+                "/tmp/source.sqlite",
+                7,
+                name,
+                "root",
+                None,
+                &nodes,
+            )
+            .expect("save should succeed");
+        }
+        let kept = save_analysis_workspace(
+            db_path_str,
+            "/tmp/source.sqlite",
+            8,
+            "Other Game",
+            "root",
+            None,
+            &nodes,
+        )
+        .expect("save should succeed");
+
+        let removed = delete_workspaces_for_game(db_path_str, "/tmp/source.sqlite", 7)
+            .expect("bulk delete should succeed");
+        assert_eq!(removed, 2);
+        assert!(
+            list_analysis_workspaces(db_path_str, "/tmp/source.sqlite", 7)
+                .expect("list should work")
+                .is_empty()
+        );
+
+        let conn = Connection::open(db_path_str).expect("open analysis db");
+        let orphaned_nodes: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM analysis_nodes WHERE workspace_id != ?1",
+                params![kept],
+                |row| row.get(0),
+            )
+            .expect("count nodes");
+        assert_eq!(orphaned_nodes, 0);
+
+        fs::remove_file(db_path).expect("cleanup should work");
+    }
+
+    #[test]
+    fn find_transpositions_groups_nodes_reaching_the_same_position() {
+        let mut nodes = vec![tree_node("root", None)];
+        let lines = [("a", ["Nf3", "Nf6", "Nc3"]), ("b", ["Nc3", "Nf6", "Nf3"])];
+        for (line, sans) in lines {
+            let mut parent = "root".to_string();
+            for ply in 1..=sans.len() {
+                let id = format!("{line}{ply}");
+                let mut node = tree_node(&id, Some(&parent));
+                node.san = Some(sans[ply - 1].to_string());
+                node.fen = crate::analysis::fen_after_sans(&sans[..ply]).expect("legal line");
+                nodes.push(node);
+                parent = id;
+            }
+        }
+        nodes[0].fen = crate::analysis::fen_after_sans(&[]).expect("start position");
+
+        let loaded = LoadedAnalysisWorkspace {
+            workspace: AnalysisWorkspaceSummary {
+                id: 1,
+                source_db_path: "/tmp/source.sqlite".to_string(),
+                game_id: 1,
+                name: "Transpositions".to_string(),
+                root_node_id: "root".to_string(),
+                current_node_id: None,
+                created_at: 0,
+                updated_at: 0,
+            },
+            nodes,
+        };
+
+        assert_eq!(
+            find_transpositions(&loaded),
+            vec![vec!["a3".to_string(), "b3".to_string()]]
+        );
+    }
+
+    #[test]
+    fn node_metadata_roundtrips_verbatim() {
+        let db_path = unique_temp_db_path();
+        let db_path_str = db_path.to_str().expect("db path should be utf-8");
+        let metadata = r#"{"arrows":[["e2","e4","green"]],"note":"é é"}"#;
+
+        let mut root = tree_node("root", None);
+        root.metadata = Some(metadata.to_string());
+        let child = tree_node("n1", Some("root"));
+
+        let workspace_id = save_analysis_workspace(
+            db_path_str,
+            "/tmp/source.sqlite",
+            5,
+            "Metadata",
+            "root",
+            None,
+            &[root, child],
+        )
+        .expect("save workspace should succeed");
+
+        let loaded = load_analysis_workspace(db_path_str, workspace_id).expect("load should work");
+        let root = loaded
+            .nodes
+            .iter()
+            .find(|node| node.id == "root")
+            .expect("root should load");
+        assert_eq!(root.metadata.as_deref(), Some(metadata));
+        let child = loaded
+            .nodes
+            .iter()
+            .find(|node| node.id == "n1")
+            .expect("child should load");
+        assert_eq!(child.metadata, None);
+
+        fs::remove_file(db_path).expect("cleanup should work");
+    }
 }
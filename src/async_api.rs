@@ -0,0 +1,115 @@
+//! `spawn_blocking` wrappers around the blocking API, for callers running on a
+//! tokio runtime. Enabled with the `async` feature.
+
+use crate::types::{
+    GameFilter, GameRow, ImportError, ImportOptions, ImportSummary, Pagination, QueryError,
+    ReplayError, ReplayTimeline,
+};
+
+async fn run_blocking<T, F>(work: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => panic!("blocking chess-prep task was cancelled: {err}"),
+    }
+}
+
+pub async fn init_db_async(path: &str) -> rusqlite::Result<()> {
+    let path = path.to_owned();
+    run_blocking(move || crate::db::init_db(&path)).await
+}
+
+pub async fn import_pgn_file_async(
+    db_path: &str,
+    pgn_path: &str,
+) -> Result<ImportSummary, ImportError> {
+    import_pgn_file_with_options_async(db_path, pgn_path, ImportOptions::default()).await
+}
+
+pub async fn import_pgn_file_with_options_async(
+    db_path: &str,
+    pgn_path: &str,
+    options: ImportOptions,
+) -> Result<ImportSummary, ImportError> {
+    let (db_path, pgn_path) = (db_path.to_owned(), pgn_path.to_owned());
+    run_blocking(move || {
+        crate::import::import_pgn_file_with_options(&db_path, &pgn_path, options, |_| {})
+    })
+    .await
+}
+
+pub async fn search_games_async(
+    db_path: &str,
+    filter: &GameFilter,
+    page: Pagination,
+) -> Result<Vec<GameRow>, QueryError> {
+    let (db_path, filter) = (db_path.to_owned(), filter.clone());
+    run_blocking(move || crate::query::search_games(&db_path, &filter, page)).await
+}
+
+pub async fn count_games_async(db_path: &str, filter: &GameFilter) -> Result<u64, QueryError> {
+    let (db_path, filter) = (db_path.to_owned(), filter.clone());
+    run_blocking(move || crate::query::count_games(&db_path, &filter)).await
+}
+
+pub async fn replay_game_async(db_path: &str, game_id: i64) -> Result<ReplayTimeline, ReplayError> {
+    let db_path = db_path.to_owned();
+    run_blocking(move || crate::replay::replay_game(&db_path, game_id)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn search_games_async_runs_on_a_current_thread_runtime() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after UNIX_EPOCH")
+            .as_nanos();
+        let db_path = std::env::temp_dir().join(format!(
+            "chess_prep_async_{}_{nanos}.sqlite",
+            std::process::id()
+        ));
+        let pgn_path = db_path.with_extension("pgn");
+        let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+        let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+        fs::write(
+            &pgn_path,
+            "[Event \"Async Open\"]\n[White \"Alice\"]\n[Black \"Bob\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n",
+        )
+        .expect("should write temp PGN");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("should build runtime");
+        let games = runtime.block_on(async {
+            init_db_async(db_path_str).await.expect("init should work");
+            let summary = import_pgn_file_async(db_path_str, pgn_path_str)
+                .await
+                .expect("import should work");
+            assert_eq!(summary.inserted, 1);
+            assert_eq!(
+                count_games_async(db_path_str, &GameFilter::default())
+                    .await
+                    .expect("count should work"),
+                1
+            );
+            search_games_async(db_path_str, &GameFilter::default(), Pagination::default())
+                .await
+                .expect("search should work")
+        });
+
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].event.as_deref(), Some("Async Open"));
+
+        fs::remove_file(db_path).expect("should clean up temp db");
+        fs::remove_file(pgn_path).expect("should clean up temp PGN");
+    }
+}
@@ -1,4 +1,18 @@
-use rusqlite::{Connection, Result as SqlResult};
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags, Result as SqlResult};
+
+use crate::types::{QueryError, SchemaStatus};
+
+const READ_MMAP_SIZE_BYTES: i64 = 256 * 1024 * 1024;
+pub(crate) const NATURAL_COLLATION: &str = "natural_order";
+
+/// SQL for a new row's `imported_at`: Unix milliseconds, but never below the
+/// newest existing value, so the column stays monotonic even if the clock
+/// steps backwards.
+pub(crate) const IMPORTED_AT_SQL: &str = "MAX(CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER), COALESCE((SELECT MAX(imported_at) FROM main.games), 0))";
 
 pub fn init_db(path: &str) -> SqlResult<()> {
     let mut conn = Connection::open(path)?;
@@ -13,7 +27,14 @@ pub fn init_db(path: &str) -> SqlResult<()> {
                 black TEXT,
                 result TEXT,
                 eco TEXT,
-                pgn TEXT
+                pgn TEXT,
+                setup_fen TEXT,
+                replay_error TEXT,
+                ply_count INTEGER,
+                opening TEXT,
+                variation TEXT,
+                imported_at INTEGER,
+                variant TEXT
                 );
 
                 CREATE INDEX IF NOT EXISTS idx_games_white ON games(white);
@@ -25,6 +46,9 @@ pub fn init_db(path: &str) -> SqlResult<()> {
                 CREATE INDEX IF NOT EXISTS idx_games_site ON games(site);
         ",
     )?;
+    ensure_games_columns(&conn)?;
+    ensure_game_tags_table(&conn)?;
+    backfill_ply_counts(&conn)?;
 
     let tx = conn.transaction()?;
     tx.execute(
@@ -61,7 +85,245 @@ pub fn init_db(path: &str) -> SqlResult<()> {
         );
         ",
     )?;
+    cleanup_orphaned_game_tags(&tx)?;
     tx.commit()?;
 
     Ok(())
 }
+
+/// Opens a read-only connection tuned for queries and replay.
+pub(crate) fn open_read_connection(path: &str) -> SqlResult<Connection> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.execute_batch(&format!(
+        "PRAGMA query_only = ON; PRAGMA mmap_size = {READ_MMAP_SIZE_BYTES};"
+    ))?;
+    conn.create_collation(NATURAL_COLLATION, natural_cmp)?;
+    Ok(conn)
+}
+
+/// Inspects a database without modifying it and reports whether it has the
+/// `games` table and which expected columns are absent.
+pub fn check_schema(db_path: &str) -> Result<SchemaStatus, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let has_games_table: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'games')",
+        [],
+        |row| row.get(0),
+    )?;
+    let existing = if has_games_table {
+        games_columns(&conn, "main")?
+    } else {
+        Vec::new()
+    };
+    let missing = |names: &mut dyn Iterator<Item = &str>| -> Vec<String> {
+        names
+            .filter(|name| !existing.iter().any(|column| column == name))
+            .map(str::to_owned)
+            .collect()
+    };
+
+    Ok(SchemaStatus {
+        has_games_table,
+        missing_columns: missing(&mut GAMES_CORE_COLUMNS.iter().copied()),
+        missing_migrated_columns: missing(
+            &mut GAMES_COLUMN_MIGRATIONS.iter().map(|&(name, _)| name),
+        ),
+    })
+}
+
+/// Copies `src_path` to `dest_path` with SQLite's online backup API, so the
+/// source may stay in use by other connections. `dest_path` is overwritten.
+pub fn backup_db(src_path: &str, dest_path: &str) -> Result<(), QueryError> {
+    let src = open_read_connection(src_path)?;
+    let mut dest = Connection::open(dest_path)?;
+    let backup = Backup::new(&src, &mut dest)?;
+    backup.run_to_completion(256, Duration::from_millis(5), None)?;
+    Ok(())
+}
+
+/// Case-insensitive comparison that orders runs of digits by numeric value.
+fn natural_cmp(left: &str, right: &str) -> Ordering {
+    let mut left = left.chars().peekable();
+    let mut right = right.chars().peekable();
+
+    loop {
+        match (left.peek().copied(), right.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                let take_digits = |chars: &mut std::iter::Peekable<std::str::Chars<'_>>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+                        digits.push(c);
+                        chars.next();
+                    }
+                    digits.trim_start_matches('0').to_owned()
+                };
+                let l_digits = take_digits(&mut left);
+                let r_digits = take_digits(&mut right);
+                let ordering = l_digits
+                    .len()
+                    .cmp(&r_digits.len())
+                    .then_with(|| l_digits.cmp(&r_digits));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(l), Some(r)) => {
+                let ordering = l.to_lowercase().cmp(r.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                left.next();
+                right.next();
+            }
+        }
+    }
+}
+
+/// Columns of the original `games` table, present in every database.
+const GAMES_CORE_COLUMNS: &[&str] = &[
+    "event", "site", "date", "white", "black", "result", "eco", "pgn",
+];
+
+const GAMES_COLUMN_MIGRATIONS: &[(&str, &str)] = &[
+    ("setup_fen", "TEXT"),
+    ("replay_error", "TEXT"),
+    ("ply_count", "INTEGER"),
+    ("opening", "TEXT"),
+    ("variation", "TEXT"),
+    ("imported_at", "INTEGER"),
+    ("variant", "TEXT"),
+];
+
+fn games_columns(conn: &Connection, schema: &str) -> SqlResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('games', ?1)")?;
+    stmt.query_map([schema], |row| row.get::<_, String>(0))?
+        .collect()
+}
+
+pub(crate) fn games_column_exists(conn: &Connection, name: &str) -> SqlResult<bool> {
+    games_column_exists_in(conn, "main", name)
+}
+
+fn games_column_exists_in(
+    conn: &Connection,
+    schema: &str,
+    name: &str,
+) -> SqlResult<bool> {
+    Ok(games_columns(conn, schema)?
+        .iter()
+        .any(|column| column == name))
+}
+
+/// Column expression for `name` in `games`, or `NULL` when a database from
+/// before the column was added has not been migrated yet; read paths never
+/// migrate.
+pub(crate) fn optional_games_column(
+    conn: &Connection,
+    name: &'static str,
+) -> SqlResult<&'static str> {
+    optional_games_column_in(conn, "main", name)
+}
+
+/// Like [`optional_games_column`], for the `games` table of an attached
+/// database.
+pub(crate) fn optional_games_column_in(
+    conn: &Connection,
+    schema: &str,
+    name: &'static str,
+) -> SqlResult<&'static str> {
+    Ok(if games_column_exists_in(conn, schema, name)? {
+        name
+    } else {
+        "NULL"
+    })
+}
+
+pub(crate) fn ensure_games_columns(conn: &Connection) -> SqlResult<()> {
+    let existing = games_columns(conn, "main")?;
+
+    for (name, declaration) in GAMES_COLUMN_MIGRATIONS {
+        if !existing.iter().any(|column| column == name) {
+            conn.execute_batch(&format!(
+                "ALTER TABLE games ADD COLUMN {name} {declaration};"
+            ))?;
+        }
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_games_imported_at ON games(imported_at);")?;
+
+    Ok(())
+}
+
+/// Fills `ply_count` for rows imported before the column existed. Stored
+/// movetext is space-separated SAN, so the ply count is the token count.
+pub(crate) fn backfill_ply_counts(conn: &Connection) -> SqlResult<usize> {
+    conn.execute(
+        "
+        UPDATE games
+        SET ply_count = LENGTH(TRIM(pgn)) - LENGTH(REPLACE(TRIM(pgn), ' ', '')) + 1
+        WHERE ply_count IS NULL
+          AND replay_error IS NULL
+          AND COALESCE(TRIM(pgn), '') != ''
+        ",
+        [],
+    )
+}
+
+/// Attaches the database at `path` as `alias`, runs `f`, and detaches it again
+/// whether or not `f` succeeded. ATTACH silently creates missing files, so
+/// callers check that `path` exists first.
+pub(crate) fn with_attached_db<T, E>(
+    conn: &mut Connection,
+    path: &str,
+    alias: &str,
+    f: impl FnOnce(&mut Connection) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<rusqlite::Error>,
+{
+    conn.execute(&format!("ATTACH DATABASE ?1 AS {alias}"), [path])?;
+    let result = f(conn);
+    let detached = conn.execute_batch(&format!("DETACH DATABASE {alias};"));
+    let value = result?;
+    detached?;
+    Ok(value)
+}
+
+/// Whether the `game_tags` table exists; databases from before tags were
+/// stored lack it until the next write migrates them.
+pub(crate) fn game_tags_table_exists(conn: &Connection) -> SqlResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'game_tags')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+pub(crate) fn ensure_game_tags_table(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS game_tags (
+            game_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (game_id, name)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_game_tags_name_value ON game_tags(name, value, game_id);
+        ",
+    )
+}
+
+pub(crate) fn cleanup_orphaned_game_tags(conn: &Connection) -> SqlResult<usize> {
+    conn.execute(
+        "DELETE FROM game_tags WHERE game_id NOT IN (SELECT rowid FROM games)",
+        [],
+    )
+}
@@ -0,0 +1,362 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rusqlite::{Connection, params};
+
+use crate::db::{games_column_exists, open_read_connection};
+use crate::types::QueryError;
+
+/// Bundled opening table as `(eco, name, san line)`. Lines are matched as
+/// move prefixes, so the longest matching line wins.
+static ECO_TABLE: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A00", "Grob Opening", "g4"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird Opening", "f4"),
+    ("A03", "Bird Opening: Dutch Variation", "f4 d5"),
+    ("A04", "Zukertort Opening", "Nf3"),
+    ("A05", "Zukertort Opening", "Nf3 Nf6"),
+    ("A06", "Zukertort Opening", "Nf3 d5"),
+    ("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    ("A10", "English Opening", "c4"),
+    ("A13", "English Opening: Agincourt Defense", "c4 e6"),
+    ("A15", "English Opening: Anglo-Indian Defense", "c4 Nf6"),
+    ("A20", "English Opening: King's English Variation", "c4 e5"),
+    ("A30", "English Opening: Symmetrical Variation", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A41", "Queen's Pawn Game", "d4 d6"),
+    ("A43", "Benoni Defense: Old Benoni", "d4 c5"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+    ("A46", "Indian Defense: Knights Variation", "d4 Nf6 Nf3"),
+    ("A50", "Indian Defense: Normal Variation", "d4 Nf6 c4"),
+    ("A51", "Budapest Defense", "d4 Nf6 c4 e5"),
+    ("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    (
+        "A60",
+        "Benoni Defense: Modern Variation",
+        "d4 Nf6 c4 c5 d5 e6",
+    ),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("B00", "King's Pawn Game", "e4"),
+    ("B00", "Nimzowitsch Defense", "e4 Nc6"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    ("B02", "Alekhine Defense", "e4 Nf6"),
+    ("B06", "Modern Defense", "e4 g6"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    (
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e4 c6 d4 d5 e5",
+    ),
+    (
+        "B13",
+        "Caro-Kann Defense: Exchange Variation",
+        "e4 c6 d4 d5 exd5 cxd5",
+    ),
+    ("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3"),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    (
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e4 c5 d4 cxd4 c3",
+    ),
+    ("B22", "Sicilian Defense: Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defense: Closed", "e4 c5 Nc3"),
+    ("B27", "Sicilian Defense", "e4 c5 Nf3"),
+    ("B30", "Sicilian Defense: Old Sicilian", "e4 c5 Nf3 Nc6"),
+    (
+        "B32",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4",
+    ),
+    ("B40", "Sicilian Defense: French Variation", "e4 c5 Nf3 e6"),
+    ("B50", "Sicilian Defense", "e4 c5 Nf3 d6"),
+    (
+        "B51",
+        "Sicilian Defense: Moscow Variation",
+        "e4 c5 Nf3 d6 Bb5",
+    ),
+    ("B54", "Sicilian Defense: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    (
+        "B56",
+        "Sicilian Defense: Classical Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3",
+    ),
+    (
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    (
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    ("C00", "French Defense", "e4 e6"),
+    (
+        "C01",
+        "French Defense: Exchange Variation",
+        "e4 e6 d4 d5 exd5",
+    ),
+    ("C02", "French Defense: Advance Variation", "e4 e6 d4 d5 e5"),
+    (
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e4 e6 d4 d5 Nd2",
+    ),
+    (
+        "C10",
+        "French Defense: Paulsen Variation",
+        "e4 e6 d4 d5 Nc3",
+    ),
+    (
+        "C11",
+        "French Defense: Classical Variation",
+        "e4 e6 d4 d5 Nc3 Nf6",
+    ),
+    (
+        "C15",
+        "French Defense: Winawer Variation",
+        "e4 e6 d4 d5 Nc3 Bb4",
+    ),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C21", "Center Game", "e4 e5 d4 exd4"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    (
+        "C44",
+        "King's Knight Opening: Normal Variation",
+        "e4 e5 Nf3 Nc6",
+    ),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Italian Game: Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    (
+        "C51",
+        "Italian Game: Evans Gambit",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 b4",
+    ),
+    (
+        "C53",
+        "Italian Game: Classical Variation",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 c3",
+    ),
+    (
+        "C55",
+        "Italian Game: Two Knights Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6",
+    ),
+    (
+        "C57",
+        "Italian Game: Two Knights Defense, Knight Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5",
+    ),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C68", "Ruy Lopez: Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6"),
+    (
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    (
+        "C70",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4",
+    ),
+    (
+        "C78",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O",
+    ),
+    (
+        "C84",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D02", "Queen's Pawn Game: Zukertort Variation", "d4 d5 Nf3"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    (
+        "D07",
+        "Queen's Gambit Declined: Chigorin Defense",
+        "d4 d5 c4 Nc6",
+    ),
+    (
+        "D08",
+        "Queen's Gambit Declined: Albin Countergambit",
+        "d4 d5 c4 e5",
+    ),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D31", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3"),
+    ("D43", "Semi-Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6"),
+    ("D80", "Grünfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("E00", "Indian Defense", "d4 Nf6 c4 e6"),
+    ("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    (
+        "E10",
+        "Indian Defense: Anti-Nimzo-Indian",
+        "d4 Nf6 c4 e6 Nf3",
+    ),
+    ("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4"),
+    ("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    (
+        "E70",
+        "King's Indian Defense: Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6",
+    ),
+];
+
+fn normalized_san(token: &str) -> Option<&str> {
+    if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return None;
+    }
+
+    // Accept both "1." / "1..." tokens and glued forms such as "1.e4".
+    let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let token = token.trim_end_matches(['+', '#', '!', '?']);
+    if token.is_empty() { None } else { Some(token) }
+}
+
+/// The display name of an ECO code from the bundled table, using its most
+/// general (shortest) line when several entries share the code.
+pub fn eco_name(eco: &str) -> Option<&'static str> {
+    let eco = eco.trim();
+    ECO_TABLE
+        .iter()
+        .filter(|(code, _, _)| code.eq_ignore_ascii_case(eco))
+        .min_by_key(|(_, _, line)| line.split_whitespace().count())
+        .map(|(_, name, _)| *name)
+}
+
+/// Classifies the opening of SAN movetext against the bundled ECO table,
+/// returning `(eco, name)` for the longest matching line.
+pub fn classify_opening(movetext: &str) -> Option<(String, String)> {
+    let moves: Vec<&str> = movetext
+        .split_whitespace()
+        .filter_map(normalized_san)
+        .collect();
+
+    ECO_TABLE
+        .iter()
+        .filter_map(|(eco, name, line)| {
+            let line: Vec<&str> = line.split_whitespace().collect();
+            moves
+                .starts_with(&line)
+                .then_some((line.len(), *eco, *name))
+        })
+        .max_by_key(|(length, _, _)| *length)
+        .map(|(_, eco, name)| (eco.to_owned(), name.to_owned()))
+}
+
+/// Classifies stored games from their movetext and writes the ECO code.
+/// Only games without an ECO are touched unless `overwrite` is set. Games
+/// from a set-up position, in a variant, or flagged with a replay error are
+/// never classified. Returns the number of rows updated.
+pub fn backfill_eco(db_path: &str, overwrite: bool) -> Result<u64, QueryError> {
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+
+    let mut conditions = vec![
+        "COALESCE(TRIM(pgn), '') != ''",
+        "(?1 OR COALESCE(TRIM(eco), '') = '')",
+    ];
+    if games_column_exists(&tx, "setup_fen")? {
+        conditions.push("COALESCE(TRIM(setup_fen), '') = ''");
+    }
+    if games_column_exists(&tx, "variant")? {
+        conditions.push("LOWER(TRIM(COALESCE(variant, ''))) IN ('', 'standard', 'chess')");
+    }
+    if games_column_exists(&tx, "replay_error")? {
+        conditions.push("replay_error IS NULL");
+    }
+
+    let candidates: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT rowid, pgn FROM games WHERE {}",
+            conditions.join(" AND ")
+        ))?;
+        stmt.query_map(params![overwrite], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?
+    };
+
+    let mut updated = 0u64;
+    {
+        let mut update_stmt = tx.prepare(
+            "UPDATE OR IGNORE games SET eco = ?2 WHERE rowid = ?1 AND COALESCE(eco, '') != ?2",
+        )?;
+        for (game_id, movetext) in candidates {
+            if let Some((eco, _)) = classify_opening(&movetext) {
+                updated += update_stmt.execute(params![game_id, eco])? as u64;
+            }
+        }
+    }
+    tx.commit()?;
+
+    Ok(updated)
+}
+
+/// Game counts for every ECO code in the bundled table, in code order, with
+/// zero for codes that have no games. Stored codes are matched ignoring case
+/// and surrounding whitespace; codes outside the table are not reported.
+pub fn eco_coverage(db_path: &str) -> Result<Vec<(String, u64)>, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT UPPER(TRIM(eco)) AS code, COUNT(*)
+        FROM games
+        WHERE COALESCE(TRIM(eco), '') <> ''
+        GROUP BY code
+        ",
+    )?;
+    let mut counts = BTreeMap::new();
+    for row in stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })? {
+        let (code, count) = row?;
+        let count = u64::try_from(count).map_err(|_| QueryError::CountOverflow(count))?;
+        counts.insert(code, count);
+    }
+
+    let codes: BTreeSet<&str> = ECO_TABLE.iter().map(|(code, _, _)| *code).collect();
+    Ok(codes
+        .into_iter()
+        .map(|code| (code.to_owned(), counts.get(code).copied().unwrap_or(0)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_sicilian_from_numbered_movetext() {
+        let (eco, name) = classify_opening("1. e4 c5").expect("should classify");
+        assert_eq!(eco, "B20");
+        assert_eq!(name, "Sicilian Defense");
+    }
+
+    #[test]
+    fn prefers_longest_matching_line() {
+        let (eco, name) =
+            classify_opening("e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3").expect("should classify");
+        assert_eq!(eco, "B90");
+        assert_eq!(name, "Sicilian Defense: Najdorf Variation");
+        assert_eq!(classify_opening("a3"), None);
+    }
+}
@@ -1,11 +1,18 @@
 use std::collections::BTreeMap;
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
 use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use crate::types::{EngineAnalysis, EngineError, EngineLine};
+use crate::types::{
+    Direction, EngineAnalysis, EngineError, EngineLine, EngineOption, EngineSessionOptions,
+    EngineVariant,
+};
 use shakmaty::uci::UciMove;
-use shakmaty::{CastlingMode, Chess, Position, fen::Fen, san::San};
+use shakmaty::{Chess, Position, fen::Fen, san::San};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct ParsedInfoLine {
@@ -16,35 +23,315 @@ struct ParsedInfoLine {
     multipv: u32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchLimit {
+    Depth(u32),
+    Nodes(u64),
+}
+
+impl SearchLimit {
+    fn go_command(self) -> String {
+        match self {
+            Self::Depth(depth) => format!("go depth {depth}"),
+            Self::Nodes(nodes) => format!("go nodes {nodes}"),
+        }
+    }
+
+    fn fallback_depth(self) -> u32 {
+        match self {
+            Self::Depth(depth) => depth,
+            Self::Nodes(_) => 0,
+        }
+    }
+}
+
+const MAX_CAPTURED_STDERR_BYTES: usize = 16 * 1024;
+/// MultiPV ceiling for engines that do not advertise a `MultiPV` option.
+pub const DEFAULT_MAX_MULTIPV: u32 = 10;
+/// Centipawn value of a mate when comparing lines by `cp_loss_vs_best`.
+pub const MATE_SCORE_CP: i32 = 10_000;
+
+/// Observer for raw UCI traffic, installed with
+/// [`EngineSession::start_with_io_hook`]. Lines are passed without their
+/// trailing newline.
+pub type EngineIoHook = Box<dyn FnMut(Direction, &str) + Send>;
+
+type SharedIoHook = Option<Arc<Mutex<EngineIoHook>>>;
+
+fn notify_io_hook(hook: &SharedIoHook, direction: Direction, line: &str) {
+    if let Some(hook) = hook
+        && let Ok(mut hook) = hook.lock()
+    {
+        hook(direction, line);
+    }
+}
+
+/// The engine's stdin, reporting every command to the session's I/O hook.
+struct EngineStdin {
+    inner: ChildStdin,
+    on_io: SharedIoHook,
+}
+
+/// The engine's stdout, reporting every line read to the session's I/O hook.
+/// Lines are read on a background thread so that a wait can give up at a
+/// deadline instead of blocking on an engine that never answers.
+struct EngineReader {
+    lines: Receiver<std::io::Result<Vec<u8>>>,
+    pending: Vec<u8>,
+    consumed: usize,
+    on_io: SharedIoHook,
+    /// How long a wait for `uciok` or `readyok` may take.
+    ready_timeout: Duration,
+    /// Set once a wait gave up; a late answer would desync later reads.
+    timed_out: bool,
+}
+
+impl EngineReader {
+    fn spawn(stdout: ChildStdout, on_io: SharedIoHook, ready_timeout: Duration) -> Self {
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            loop {
+                let mut line = Vec::new();
+                match stdout.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        if sender.send(Ok(line)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        let _ = sender.send(Err(err));
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            lines,
+            pending: Vec::new(),
+            consumed: 0,
+            on_io,
+            ready_timeout,
+            timed_out: false,
+        }
+    }
+
+    /// Makes sure there are unread bytes, waiting for the next line until
+    /// `deadline` if there is one. Returns `false` if the deadline passed; an
+    /// empty buffer afterwards means the engine closed its output.
+    fn refill(&mut self, deadline: Option<Instant>) -> std::io::Result<bool> {
+        if self.consumed < self.pending.len() {
+            return Ok(true);
+        }
+
+        let received = match deadline {
+            Some(deadline) => self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self
+                .lines
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        self.pending.clear();
+        self.consumed = 0;
+        match received {
+            Ok(line) => {
+                self.pending = line?;
+                Ok(true)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(false),
+            Err(RecvTimeoutError::Disconnected) => Ok(true),
+        }
+    }
+
+    /// Like [`BufRead::read_line`], but returns `None` once `deadline` has
+    /// passed, even if the engine is still printing.
+    fn read_line_until(
+        &mut self,
+        buf: &mut String,
+        deadline: Option<Instant>,
+    ) -> std::io::Result<Option<usize>> {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) || !self.refill(deadline)? {
+            return Ok(None);
+        }
+
+        let bytes = &self.pending[self.consumed..];
+        let text = std::str::from_utf8(bytes)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        let start = buf.len();
+        buf.push_str(text);
+        let read = bytes.len();
+        self.consumed = self.pending.len();
+        if read > 0 {
+            let line = buf[start..].trim_end_matches(['\n', '\r']);
+            notify_io_hook(&self.on_io, Direction::Received, line);
+        }
+        Ok(Some(read))
+    }
+}
+
+impl Read for EngineReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let amount = available.len().min(buf.len());
+        buf[..amount].copy_from_slice(&available[..amount]);
+        self.consume(amount);
+        Ok(amount)
+    }
+}
+
+impl BufRead for EngineReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.refill(None)?;
+        Ok(&self.pending[self.consumed..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.consumed = (self.consumed + amount).min(self.pending.len());
+    }
+
+    fn read_line(&mut self, buf: &mut String) -> std::io::Result<usize> {
+        Ok(self.read_line_until(buf, None)?.unwrap_or(0))
+    }
+}
+
 pub struct EngineSession {
     child: Child,
-    stdin: ChildStdin,
-    reader: BufReader<ChildStdout>,
+    stdin: EngineStdin,
+    reader: EngineReader,
+    variant: EngineVariant,
+    stderr: Option<StderrCapture>,
+    max_multipv: u32,
+    options: Vec<EngineOption>,
+}
+
+struct StderrCapture {
+    buffer: Arc<Mutex<String>>,
+    handle: Option<JoinHandle<()>>,
 }
 
-fn send_uci_command(stdin: &mut ChildStdin, command: &str) -> Result<(), EngineError> {
-    writeln!(stdin, "{command}")?;
-    stdin.flush()?;
+impl StderrCapture {
+    fn spawn(stderr: ChildStderr) -> Self {
+        let buffer = Arc::new(Mutex::new(String::new()));
+        let thread_buffer = Arc::clone(&buffer);
+        let handle = std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let Ok(mut buffer) = thread_buffer.lock() else {
+                    break;
+                };
+                if buffer.len() + line.len() < MAX_CAPTURED_STDERR_BYTES {
+                    buffer.push_str(&line);
+                    buffer.push('\n');
+                }
+            }
+        });
+
+        Self {
+            buffer,
+            handle: Some(handle),
+        }
+    }
+
+    /// Waits for the reader thread when the engine has exited so that output
+    /// written just before exit is not lost, then returns what was captured.
+    fn contents(&mut self, engine_exited: bool) -> String {
+        if engine_exited && let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        self.buffer
+            .lock()
+            .map(|buffer| buffer.trim().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Protocol errors get the captured stderr appended; I/O errors (such as a
+    /// broken pipe after the engine exited) become protocol errors when there
+    /// is stderr output to explain them.
+    fn annotate(&mut self, err: EngineError, engine_exited: bool) -> EngineError {
+        let message = match &err {
+            EngineError::Protocol(message) => message.clone(),
+            EngineError::Io(io_err) => format!("engine I/O failed: {io_err}"),
+            EngineError::Spawn(_) => return err,
+        };
+
+        let stderr = self.contents(engine_exited);
+        if stderr.is_empty() {
+            err
+        } else {
+            EngineError::Protocol(format!("{message}; engine stderr: {stderr}"))
+        }
+    }
+}
+
+fn send_uci_command(stdin: &mut EngineStdin, command: &str) -> Result<(), EngineError> {
+    writeln!(stdin.inner, "{command}")?;
+    stdin.inner.flush()?;
+    notify_io_hook(&stdin.on_io, Direction::Sent, command);
     Ok(())
 }
 
+/// Lines an engine may legitimately print while we wait for a handshake
+/// token; these never count against the unexpected-line budget.
+fn is_known_chatter(line: &str) -> bool {
+    match line.split_whitespace().next() {
+        None => true,
+        Some(keyword) => matches!(
+            keyword,
+            "info" | "id" | "option" | "copyprotection" | "registration"
+        ),
+    }
+}
+
+/// Reads until `token` arrives. Only lines that are neither the token nor
+/// known chatter count toward `max_lines`; chatter is bounded by the reader's
+/// ready timeout instead, so an engine that never stops printing `info`
+/// lines still fails.
 fn wait_for_uci_token(
-    reader: &mut BufReader<ChildStdout>,
+    reader: &mut EngineReader,
+    token: &str,
+    max_lines: usize,
+) -> Result<(), EngineError> {
+    wait_for_uci_token_with(reader, token, max_lines, |_| {})
+}
+
+/// Like [`wait_for_uci_token`], but hands every other line to `on_line`.
+fn wait_for_uci_token_with(
+    reader: &mut EngineReader,
     token: &str,
     max_lines: usize,
+    mut on_line: impl FnMut(&str),
 ) -> Result<(), EngineError> {
+    let timeout = reader.ready_timeout;
+    let deadline = Instant::now() + timeout;
     let mut line = String::new();
-    for _ in 0..max_lines {
+    let mut unexpected = 0;
+    while unexpected < max_lines {
         line.clear();
-        let bytes = reader.read_line(&mut line)?;
+        let Some(bytes) = reader.read_line_until(&mut line, Some(deadline))? else {
+            reader.timed_out = true;
+            return Err(EngineError::Protocol(format!(
+                "engine did not send '{token}' within {timeout:?}"
+            )));
+        };
         if bytes == 0 {
             return Err(EngineError::Protocol(format!(
                 "engine closed output while waiting for '{token}'"
             )));
         }
-        if line.trim() == token {
+        let trimmed = line.trim();
+        if trimmed == token {
             return Ok(());
         }
+        on_line(trimmed);
+        if !is_known_chatter(trimmed) {
+            unexpected += 1;
+        }
     }
 
     Err(EngineError::Protocol(format!(
@@ -52,6 +339,23 @@ fn wait_for_uci_token(
     )))
 }
 
+/// Splits a `bestmove <move> [ponder <move>]` line into its best and ponder
+/// moves; `(none)` in either slot becomes `None`.
+fn parse_bestmove_line(line: &str) -> Option<(Option<String>, Option<String>)> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("bestmove") {
+        return None;
+    }
+
+    let known = |token: &str| (token != "(none)").then(|| token.to_owned());
+    let bestmove = tokens.next().and_then(known);
+    let ponder = match tokens.next() {
+        Some("ponder") => tokens.next().and_then(known),
+        _ => None,
+    };
+    Some((bestmove, ponder))
+}
+
 fn parse_info_line(line: &str) -> Option<ParsedInfoLine> {
     if !line.starts_with("info ") {
         return None;
@@ -128,25 +432,113 @@ fn better_info(candidate: &ParsedInfoLine, current: &ParsedInfoLine) -> bool {
         || (candidate_depth == current_depth && !candidate.pv.is_empty() && current.pv.is_empty())
 }
 
-fn normalized_depth(depth: u32) -> u32 {
+pub(crate) fn normalized_depth(depth: u32) -> u32 {
     if depth == 0 { 18 } else { depth }
 }
 
-fn normalized_multipv(multipv: u32) -> u32 {
-    multipv.clamp(1, 10)
+fn normalized_multipv(multipv: u32, max_multipv: u32) -> u32 {
+    multipv.clamp(1, max_multipv.max(1))
 }
 
-fn pv_uci_to_san(fen: &str, pv: &[String]) -> Vec<String> {
-    let parsed_fen = match Fen::from_str(fen) {
-        Ok(value) => value,
-        Err(_) => return Vec::new(),
-    };
+/// Parses an `option name <name> type <type> [default ..] [min ..] [max ..]
+/// [var ..]*` line. Names, defaults and vars may contain spaces, so each value
+/// runs until the next keyword.
+pub(crate) fn parse_engine_option(line: &str) -> Option<EngineOption> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("option") || tokens.next() != Some("name") {
+        return None;
+    }
 
-    let mut position: Chess = match parsed_fen.into_position(CastlingMode::Standard) {
-        Ok(value) => value,
-        Err(_) => return Vec::new(),
-    };
+    let mut name: Vec<&str> = Vec::new();
+    let mut option_type: Vec<&str> = Vec::new();
+    let mut default: Option<Vec<&str>> = None;
+    let mut min: Vec<&str> = Vec::new();
+    let mut max: Vec<&str> = Vec::new();
+    let mut vars: Vec<Vec<&str>> = Vec::new();
+    let mut field = "name";
 
+    for token in tokens {
+        match token {
+            "type" | "min" | "max" => field = token,
+            "default" => {
+                field = token;
+                default.get_or_insert_with(Vec::new);
+            }
+            "var" => {
+                field = token;
+                vars.push(Vec::new());
+            }
+            _ => match field {
+                "name" => name.push(token),
+                "type" => option_type.push(token),
+                "default" => default.get_or_insert_with(Vec::new).push(token),
+                "min" => min.push(token),
+                "max" => max.push(token),
+                _ => {
+                    if let Some(var) = vars.last_mut() {
+                        var.push(token);
+                    }
+                }
+            },
+        }
+    }
+
+    if name.is_empty() || option_type.is_empty() {
+        return None;
+    }
+
+    Some(EngineOption {
+        name: name.join(" "),
+        option_type: option_type.join(" "),
+        default: default.map(|value| value.join(" ")),
+        min: min.join(" ").parse().ok(),
+        max: max.join(" ").parse().ok(),
+        vars: vars.into_iter().map(|var| var.join(" ")).collect(),
+    })
+}
+
+/// The `max` of an `option name MultiPV type spin ... max N` line.
+fn advertised_multipv_max(option: &EngineOption) -> Option<u32> {
+    if !option.name.eq_ignore_ascii_case("MultiPV") {
+        return None;
+    }
+    option.max.and_then(|max| u32::try_from(max).ok())
+}
+
+/// Parses `fen` under `variant`'s castling rules, so Chess960 sessions accept
+/// Shredder and X-FEN castling rights.
+pub(crate) fn session_position(fen: &str, variant: EngineVariant) -> Option<Chess> {
+    Fen::from_str(fen)
+        .ok()?
+        .into_position(variant.castling_mode())
+        .ok()
+}
+
+/// The legal moves of `position` in UCI form, castling written the way its
+/// castling mode expects.
+pub(crate) fn legal_uci_moves(position: &Chess) -> Vec<String> {
+    let castling_mode = position.castles().mode();
+    position
+        .legal_moves()
+        .into_iter()
+        .map(|mv| UciMove::from_move(mv, castling_mode).to_string())
+        .collect()
+}
+
+/// The single legal move of `position` in UCI form when it is forced.
+pub(crate) fn only_uci_move(position: &Chess) -> Option<String> {
+    let mut moves = legal_uci_moves(position);
+    if moves.len() == 1 { moves.pop() } else { None }
+}
+
+pub(crate) fn pv_uci_to_san(fen: &str, pv: &[String], variant: EngineVariant) -> Vec<String> {
+    session_position(fen, variant)
+        .map(|position| pv_to_san(&position, pv))
+        .unwrap_or_default()
+}
+
+fn pv_to_san(start: &Chess, pv: &[String]) -> Vec<String> {
+    let mut position = start.clone();
     let mut san_tokens: Vec<String> = Vec::new();
 
     for uci in pv {
@@ -168,23 +560,68 @@ fn pv_uci_to_san(fen: &str, pv: &[String]) -> Vec<String> {
     san_tokens
 }
 
-fn spawn_engine(engine_path: &str) -> Result<Child, EngineError> {
+pub(crate) fn bestmove_uci_to_san(
+    fen: &str,
+    bestmove_uci: Option<&str>,
+    variant: EngineVariant,
+) -> Option<String> {
+    let uci = bestmove_uci?.to_owned();
+    pv_uci_to_san(fen, std::slice::from_ref(&uci), variant)
+        .into_iter()
+        .next()
+}
+
+fn spawn_engine(engine_path: &str, capture_stderr: bool) -> Result<Child, EngineError> {
+    let stderr = if capture_stderr {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+
     Command::new(engine_path)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::null())
+        .stderr(stderr)
         .spawn()
-        .map_err(|err| EngineError::Spawn(format!("failed to start engine '{engine_path}': {err}")))
+        .map_err(|err| EngineError::Spawn(describe_spawn_error(engine_path, &err)))
+}
+
+fn describe_spawn_error(engine_path: &str, err: &std::io::Error) -> String {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => {
+            format!("engine '{engine_path}' was not found; check that the path is correct")
+        }
+        std::io::ErrorKind::PermissionDenied => format!(
+            "engine '{engine_path}' is not executable; check its file permissions (e.g. chmod +x)"
+        ),
+        _ => format!("failed to start engine '{engine_path}': {err}"),
+    }
+}
+
+/// A side-to-move score on one centipawn scale: a mate for the side to move
+/// counts as `MATE_SCORE_CP` less the moves to mate, so a faster mate scores
+/// higher, and being mated as the negation.
+fn comparable_score_cp(score_cp: Option<i32>, score_mate: Option<i32>) -> Option<i32> {
+    match score_mate {
+        Some(mate) if mate > 0 => Some(MATE_SCORE_CP.saturating_sub(mate)),
+        Some(mate) => Some((-MATE_SCORE_CP).saturating_sub(mate)),
+        None => score_cp,
+    }
 }
 
-fn collect_analysis_result(
-    reader: &mut BufReader<ChildStdout>,
+/// Reads info lines until `bestmove`, handing every accepted info line to
+/// `on_info` as it arrives.
+fn collect_analysis_result<R: BufRead>(
+    reader: &mut R,
     fen: &str,
+    variant: EngineVariant,
     requested_depth: u32,
     requested_multipv: u32,
+    mut on_info: impl FnMut(&ParsedInfoLine),
 ) -> Result<EngineAnalysis, EngineError> {
     let mut best_by_rank: BTreeMap<u32, ParsedInfoLine> = BTreeMap::new();
     let mut bestmove: Option<String> = None;
+    let mut ponder: Option<String> = None;
     let mut line = String::new();
 
     for _ in 0..50_000 {
@@ -201,6 +638,7 @@ fn collect_analysis_result(
             if info.multipv == 0 || info.multipv > requested_multipv {
                 continue;
             }
+            on_info(&info);
 
             let should_update = match best_by_rank.get(&info.multipv) {
                 Some(current) => better_info(&info, current),
@@ -212,13 +650,9 @@ fn collect_analysis_result(
             continue;
         }
 
-        if trimmed.starts_with("bestmove") {
-            let tokens: Vec<&str> = trimmed.split_whitespace().collect();
-            if let Some(token) = tokens.get(1)
-                && *token != "(none)"
-            {
-                bestmove = Some((*token).to_owned());
-            }
+        if let Some((best, reply)) = parse_bestmove_line(trimmed) {
+            bestmove = best;
+            ponder = reply;
             break;
         }
     }
@@ -229,10 +663,14 @@ fn collect_analysis_result(
         ));
     }
 
+    let start = session_position(fen, variant);
     let mut lines: Vec<EngineLine> = best_by_rank
         .into_iter()
         .map(|(rank, info)| {
-            let san_pv = pv_uci_to_san(fen, &info.pv);
+            let san_pv = start
+                .as_ref()
+                .map(|position| pv_to_san(position, &info.pv))
+                .unwrap_or_default();
             EngineLine {
                 multipv_rank: rank,
                 depth: info.depth.unwrap_or(requested_depth),
@@ -240,11 +678,22 @@ fn collect_analysis_result(
                 score_mate: info.score_mate,
                 pv: info.pv,
                 san_pv,
+                cp_loss_vs_best: None,
             }
         })
         .collect();
     lines.sort_by_key(|line| line.multipv_rank);
 
+    let best_score = lines
+        .iter()
+        .find(|line| line.multipv_rank == 1)
+        .and_then(|line| comparable_score_cp(line.score_cp, line.score_mate));
+    for line in &mut lines {
+        line.cp_loss_vs_best = best_score
+            .zip(comparable_score_cp(line.score_cp, line.score_mate))
+            .map(|(best, score)| best.saturating_sub(score).max(0));
+    }
+
     let primary = lines
         .iter()
         .find(|line| line.multipv_rank == 1)
@@ -253,44 +702,151 @@ fn collect_analysis_result(
             EngineError::Protocol("engine returned no analysis info for this position".to_string())
         })?;
 
-    let bestmove = primary
-        .san_pv
-        .first()
-        .cloned()
-        .or(bestmove)
-        .or_else(|| primary.pv.first().cloned());
+    let bestmove_uci = primary.pv.first().cloned().or(bestmove);
+    let bestmove_san = start
+        .as_ref()
+        .zip(bestmove_uci.as_ref())
+        .and_then(|(position, uci)| pv_to_san(position, std::slice::from_ref(uci)).pop());
 
     Ok(EngineAnalysis {
         depth: primary.depth,
         score_cp: primary.score_cp,
         score_mate: primary.score_mate,
-        bestmove,
+        bestmove_uci,
+        bestmove_san,
         pv: primary.pv.clone(),
+        san_pv: primary.san_pv.clone(),
         lines,
+        only_move: start.as_ref().and_then(only_uci_move),
+        requested_lines: requested_multipv,
+        available_lines: start
+            .as_ref()
+            .and_then(|position| u32::try_from(legal_uci_moves(position).len()).ok()),
+        ponder,
     })
 }
 
+/// Counts consecutive depths at which the principal variation starts with
+/// the same move.
+#[derive(Debug, Default)]
+struct BestMoveStability {
+    depth: u32,
+    best_move: Option<String>,
+    streak: u32,
+}
+
+impl BestMoveStability {
+    /// Records a MultiPV 1 info line and returns the current streak.
+    fn observe(&mut self, info: &ParsedInfoLine) -> u32 {
+        let (Some(depth), Some(best_move)) = (info.depth, info.pv.first()) else {
+            return self.streak;
+        };
+        let same_move = self.best_move.as_ref() == Some(best_move);
+        if depth > self.depth {
+            self.streak = if same_move { self.streak + 1 } else { 1 };
+            self.depth = depth;
+        } else if !same_move {
+            self.streak = 1;
+        }
+        self.best_move = Some(best_move.clone());
+        self.streak
+    }
+}
+
 fn analyze_with_engine_io(
-    stdin: &mut ChildStdin,
-    reader: &mut BufReader<ChildStdout>,
+    stdin: &mut EngineStdin,
+    reader: &mut EngineReader,
+    variant: EngineVariant,
     fen: &str,
-    depth: u32,
+    limit: SearchLimit,
+    multipv: u32,
+) -> Result<EngineAnalysis, EngineError> {
+    analyze_with_engine_io_until(stdin, reader, variant, fen, limit, multipv, |_| false)
+}
+
+/// Like [`analyze_with_engine_io`], but sends `stop` once `should_stop`
+/// returns true for an info line, and reports the search up to that point.
+fn analyze_with_engine_io_until(
+    stdin: &mut EngineStdin,
+    reader: &mut EngineReader,
+    variant: EngineVariant,
+    fen: &str,
+    limit: SearchLimit,
     multipv: u32,
+    mut should_stop: impl FnMut(&ParsedInfoLine) -> bool,
 ) -> Result<EngineAnalysis, EngineError> {
-    let depth = normalized_depth(depth);
-    let multipv = normalized_multipv(multipv);
+    let limit = match limit {
+        SearchLimit::Depth(depth) => SearchLimit::Depth(normalized_depth(depth)),
+        SearchLimit::Nodes(nodes) => SearchLimit::Nodes(nodes.max(1)),
+    };
     send_uci_command(stdin, &format!("setoption name MultiPV value {multipv}"))?;
     send_uci_command(stdin, "isready")?;
     wait_for_uci_token(reader, "readyok", 20_000)?;
     send_uci_command(stdin, &format!("position fen {fen}"))?;
-    send_uci_command(stdin, &format!("go depth {depth}"))?;
-    collect_analysis_result(reader, fen, depth, multipv)
+    send_uci_command(stdin, &limit.go_command())?;
+
+    let mut stop_result = Ok(());
+    let mut stop_sent = false;
+    let analysis = collect_analysis_result(
+        reader,
+        fen,
+        variant,
+        limit.fallback_depth(),
+        multipv,
+        |info| {
+            if !stop_sent && should_stop(info) {
+                stop_sent = true;
+                stop_result = send_uci_command(stdin, "stop");
+            }
+        },
+    )?;
+    stop_result?;
+    Ok(analysis)
 }
 
 impl EngineSession {
     pub fn start(engine_path: &str) -> Result<Self, EngineError> {
-        let mut child = spawn_engine(engine_path)?;
-        let mut stdin = child
+        Self::start_with_variant(engine_path, EngineVariant::Standard)
+    }
+
+    pub fn start_with_variant(
+        engine_path: &str,
+        variant: EngineVariant,
+    ) -> Result<Self, EngineError> {
+        Self::start_with_options(
+            engine_path,
+            EngineSessionOptions {
+                variant,
+                ..EngineSessionOptions::default()
+            },
+        )
+    }
+
+    pub fn start_with_options(
+        engine_path: &str,
+        options: EngineSessionOptions,
+    ) -> Result<Self, EngineError> {
+        Self::start_inner(engine_path, options, None)
+    }
+
+    /// Like [`EngineSession::start_with_options`], but passes every command
+    /// sent and every line received, including the handshake, to `on_io` in
+    /// the order they happen.
+    pub fn start_with_io_hook(
+        engine_path: &str,
+        options: EngineSessionOptions,
+        on_io: EngineIoHook,
+    ) -> Result<Self, EngineError> {
+        Self::start_inner(engine_path, options, Some(Arc::new(Mutex::new(on_io))))
+    }
+
+    fn start_inner(
+        engine_path: &str,
+        options: EngineSessionOptions,
+        on_io: SharedIoHook,
+    ) -> Result<Self, EngineError> {
+        let mut child = spawn_engine(engine_path, options.capture_stderr)?;
+        let stdin = child
             .stdin
             .take()
             .ok_or_else(|| EngineError::Protocol("engine stdin is unavailable".to_string()))?;
@@ -298,22 +854,112 @@ impl EngineSession {
             .stdout
             .take()
             .ok_or_else(|| EngineError::Protocol("engine stdout is unavailable".to_string()))?;
-        let mut reader = BufReader::new(stdout);
+        let mut stdin = EngineStdin {
+            inner: stdin,
+            on_io: on_io.clone(),
+        };
+        let mut reader = EngineReader::spawn(stdout, on_io, options.ready_timeout);
+        let mut stderr = child.stderr.take().map(StderrCapture::spawn);
+
+        let mut max_multipv = None;
+        let mut engine_options = Vec::new();
+        let handshake = (|| {
+            send_uci_command(&mut stdin, "uci")?;
+            wait_for_uci_token_with(&mut reader, "uciok", 20_000, |line| {
+                if let Some(option) = parse_engine_option(line) {
+                    if let Some(max) = advertised_multipv_max(&option) {
+                        max_multipv = Some(max);
+                    }
+                    engine_options.push(option);
+                }
+            })?;
+            if options.variant == EngineVariant::Chess960 {
+                send_uci_command(&mut stdin, "setoption name UCI_Chess960 value true")?;
+            }
+            send_uci_command(&mut stdin, "isready")?;
+            wait_for_uci_token(&mut reader, "readyok", 20_000)
+        })();
 
-        send_uci_command(&mut stdin, "uci")?;
-        wait_for_uci_token(&mut reader, "uciok", 20_000)?;
-        send_uci_command(&mut stdin, "isready")?;
-        wait_for_uci_token(&mut reader, "readyok", 20_000)?;
+        if let Err(err) = handshake {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(match stderr.as_mut() {
+                Some(capture) => capture.annotate(err, true),
+                None => err,
+            });
+        }
 
         Ok(Self {
             child,
             stdin,
             reader,
+            variant: options.variant,
+            stderr,
+            max_multipv: max_multipv.unwrap_or(DEFAULT_MAX_MULTIPV),
+            options: engine_options,
         })
     }
 
+    /// Fails once a wait has timed out: the engine may still answer it late,
+    /// so nothing it sends afterwards can be trusted.
+    fn ensure_responsive(&self) -> Result<(), EngineError> {
+        if self.reader.timed_out {
+            return Err(EngineError::Protocol(
+                "engine session stopped after the engine failed to answer in time".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Annotates `err` with captured stderr, and stops the engine if the
+    /// error was a timeout.
+    fn annotate_error(&mut self, err: EngineError) -> EngineError {
+        if self.reader.timed_out {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+        let engine_exited = matches!(self.child.try_wait(), Ok(Some(_)));
+        match self.stderr.as_mut() {
+            Some(capture) => capture.annotate(err, engine_exited),
+            None => err,
+        }
+    }
+
+    pub fn variant(&self) -> EngineVariant {
+        self.variant
+    }
+
+    /// The largest MultiPV the engine advertised during the handshake, or
+    /// `DEFAULT_MAX_MULTIPV` if it did not advertise one.
+    pub fn max_multipv(&self) -> u32 {
+        self.max_multipv
+    }
+
+    /// The options the engine advertised during the handshake, in the order it
+    /// sent them.
+    pub fn options(&self) -> &[EngineOption] {
+        &self.options
+    }
+
+    /// Sends `isready` and waits up to the configured `ready_timeout` for
+    /// `readyok`; a liveness probe to run between analyses. A timeout stops
+    /// the engine, and every later call on the session fails.
+    pub fn ping(&mut self) -> Result<(), EngineError> {
+        self.ensure_responsive()?;
+        send_uci_command(&mut self.stdin, "isready")
+            .and_then(|()| wait_for_uci_token(&mut self.reader, "readyok", 20_000))
+            .map_err(|err| self.annotate_error(err))
+    }
+
+    /// Sends `quit` and waits for the engine to exit, returning its status.
+    /// Dropping a session does the same but discards any error.
+    pub fn shutdown(mut self) -> Result<ExitStatus, EngineError> {
+        send_uci_command(&mut self.stdin, "quit").map_err(|err| self.annotate_error(err))?;
+        Ok(self.child.wait()?)
+    }
+
     pub fn analyze(&mut self, fen: &str, depth: u32) -> Result<EngineAnalysis, EngineError> {
-        analyze_with_engine_io(&mut self.stdin, &mut self.reader, fen, depth, 1)
+        self.analyze_multipv(fen, depth, 1)
     }
 
     pub fn analyze_multipv(
@@ -322,7 +968,52 @@ impl EngineSession {
         depth: u32,
         multipv: u32,
     ) -> Result<EngineAnalysis, EngineError> {
-        analyze_with_engine_io(&mut self.stdin, &mut self.reader, fen, depth, multipv)
+        self.ensure_responsive()?;
+        analyze_with_engine_io(
+            &mut self.stdin,
+            &mut self.reader,
+            self.variant,
+            fen,
+            SearchLimit::Depth(depth),
+            normalized_multipv(multipv, self.max_multipv),
+        )
+        .map_err(|err| self.annotate_error(err))
+    }
+
+    /// Searches up to `max_depth` but stops early once the best move has
+    /// been the same for `stability` consecutive depths.
+    pub fn analyze_until_stable(
+        &mut self,
+        fen: &str,
+        max_depth: u32,
+        stability: u32,
+    ) -> Result<EngineAnalysis, EngineError> {
+        self.ensure_responsive()?;
+        let stability = stability.max(1);
+        let mut tracker = BestMoveStability::default();
+        analyze_with_engine_io_until(
+            &mut self.stdin,
+            &mut self.reader,
+            self.variant,
+            fen,
+            SearchLimit::Depth(max_depth),
+            1,
+            |info| tracker.observe(info) >= stability,
+        )
+        .map_err(|err| self.annotate_error(err))
+    }
+
+    pub fn analyze_nodes(&mut self, fen: &str, nodes: u64) -> Result<EngineAnalysis, EngineError> {
+        self.ensure_responsive()?;
+        analyze_with_engine_io(
+            &mut self.stdin,
+            &mut self.reader,
+            self.variant,
+            fen,
+            SearchLimit::Nodes(nodes),
+            1,
+        )
+        .map_err(|err| self.annotate_error(err))
     }
 }
 
@@ -351,9 +1042,53 @@ pub fn analyze_position_multipv(
     session.analyze_multipv(fen, depth, multipv)
 }
 
+fn copy_engine_error(err: &EngineError) -> EngineError {
+    match err {
+        EngineError::Io(io_err) => {
+            EngineError::Io(std::io::Error::new(io_err.kind(), io_err.to_string()))
+        }
+        EngineError::Spawn(message) => EngineError::Spawn(message.clone()),
+        EngineError::Protocol(message) => EngineError::Protocol(message.clone()),
+    }
+}
+
+/// Analyzes every FEN with a single engine session. If the engine cannot be
+/// started, each position reports the startup error.
+pub fn analyze_positions(
+    engine_path: &str,
+    fens: &[&str],
+    depth: u32,
+    multipv: u32,
+) -> Vec<Result<EngineAnalysis, EngineError>> {
+    let mut session = match EngineSession::start(engine_path) {
+        Ok(session) => session,
+        Err(err) => return fens.iter().map(|_| Err(copy_engine_error(&err))).collect(),
+    };
+
+    fens.iter()
+        .map(|fen| session.analyze_multipv(fen, depth, multipv))
+        .collect()
+}
+
+pub fn analyze_nodes(
+    engine_path: &str,
+    fen: &str,
+    nodes: u64,
+) -> Result<EngineAnalysis, EngineError> {
+    let mut session = EngineSession::start(engine_path)?;
+    session.analyze_nodes(fen, nodes)
+}
+
 #[cfg(test)]
 mod engine_tests {
-    use super::parse_info_line;
+    use super::{
+        DEFAULT_MAX_MULTIPV, MATE_SCORE_CP, SearchLimit, advertised_multipv_max,
+        collect_analysis_result, normalized_multipv, parse_bestmove_line, parse_engine_option,
+        parse_info_line, pv_uci_to_san,
+    };
+    use crate::types::{EngineVariant, Score};
+    use shakmaty::Color;
+    use std::io::Cursor;
 
     #[test]
     fn parse_info_line_cp_and_pv() {
@@ -366,6 +1101,20 @@ mod engine_tests {
         assert_eq!(parsed.multipv, 1);
     }
 
+    #[test]
+    fn parse_bestmove_line_with_and_without_ponder() {
+        assert_eq!(
+            parse_bestmove_line("bestmove e2e4 ponder e7e5"),
+            Some((Some("e2e4".to_string()), Some("e7e5".to_string())))
+        );
+        assert_eq!(
+            parse_bestmove_line("bestmove e2e4"),
+            Some((Some("e2e4".to_string()), None))
+        );
+        assert_eq!(parse_bestmove_line("bestmove (none)"), Some((None, None)));
+        assert_eq!(parse_bestmove_line("info depth 1"), None);
+    }
+
     #[test]
     fn parse_info_line_mate() {
         let line = "info depth 21 score mate -3 pv h7h8q";
@@ -375,4 +1124,236 @@ mod engine_tests {
         assert_eq!(parsed.score_mate, Some(-3));
         assert_eq!(parsed.pv, vec!["h7h8q"]);
     }
+
+    #[test]
+    fn search_limit_builds_go_commands() {
+        assert_eq!(SearchLimit::Depth(18).go_command(), "go depth 18");
+        assert_eq!(SearchLimit::Nodes(250_000).go_command(), "go nodes 250000");
+    }
+
+    #[test]
+    fn node_limit_has_no_fallback_depth() {
+        assert_eq!(SearchLimit::Depth(12).fallback_depth(), 12);
+        assert_eq!(SearchLimit::Nodes(1_000).fallback_depth(), 0);
+    }
+
+    #[test]
+    fn pv_to_san_uses_chess960_castling_for_chess960_sessions() {
+        let fen = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1";
+        let pv = vec!["f1g1".to_string(), "f8g8".to_string()];
+        assert_eq!(
+            pv_uci_to_san(fen, &pv, EngineVariant::Chess960),
+            vec!["O-O", "O-O"]
+        );
+        assert!(pv_uci_to_san(fen, &pv, EngineVariant::Standard).is_empty());
+    }
+
+    #[test]
+    fn analysis_exposes_primary_san_pv_at_top_level() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let output = "info depth 10 multipv 1 score cp 30 pv e2e4 e7e5 g1f3\n\
+                      info depth 10 multipv 2 score cp 20 pv d2d4 d7d5\n\
+                      bestmove e2e4\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(output),
+            fen,
+            EngineVariant::Standard,
+            10,
+            2,
+            |_| {},
+        )
+        .expect("analysis should parse");
+
+        let expected = pv_uci_to_san(fen, &analysis.pv, EngineVariant::Standard);
+        assert_eq!(analysis.san_pv, vec!["e4", "e5", "Nf3"]);
+        assert_eq!(analysis.san_pv, expected);
+        assert_eq!(analysis.san_pv, analysis.lines[0].san_pv);
+        assert_eq!(analysis.pv, vec!["e2e4", "e7e5", "g1f3"]);
+    }
+
+    #[test]
+    fn advertised_multipv_max_reads_the_option_line() {
+        let multipv_max =
+            |line: &str| parse_engine_option(line).and_then(|o| advertised_multipv_max(&o));
+        assert_eq!(
+            multipv_max("option name MultiPV type spin default 1 min 1 max 500"),
+            Some(500)
+        );
+        assert_eq!(
+            multipv_max("option name Threads type spin default 1 min 1 max 1024"),
+            None
+        );
+        assert_eq!(multipv_max("info string MultiPV max 3"), None);
+        assert_eq!(normalized_multipv(16, 500), 16);
+        assert_eq!(normalized_multipv(16, DEFAULT_MAX_MULTIPV), 10);
+        assert_eq!(normalized_multipv(0, 20), 1);
+    }
+
+    #[test]
+    fn analysis_reports_bestmove_in_uci_and_san() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let output = "info depth 12 multipv 1 score cp 25 pv g1f3 d7d5 d2d4\n\
+                      bestmove g1f3 ponder d7d5\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(output),
+            fen,
+            EngineVariant::Standard,
+            12,
+            1,
+            |_| {},
+        )
+        .expect("analysis should parse");
+
+        assert_eq!(analysis.bestmove_uci.as_deref(), Some("g1f3"));
+        assert_eq!(analysis.bestmove_san.as_deref(), Some("Nf3"));
+        assert_eq!(analysis.bestmove_uci.as_ref(), analysis.pv.first());
+        assert_eq!(analysis.bestmove_san.as_ref(), analysis.san_pv.first());
+        assert_eq!(analysis.ponder.as_deref(), Some("d7d5"));
+        assert_eq!(analysis.only_move, None);
+    }
+
+    #[test]
+    fn multipv_lines_report_loss_against_the_best_line() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let output = "info depth 12 multipv 1 score cp 40 pv e2e4\n\
+                      info depth 12 multipv 2 score cp 15 pv d2d4\n\
+                      info depth 12 multipv 3 score cp -30 pv g2g4\n\
+                      info depth 12 multipv 4 score mate -2 pv f2f3\n\
+                      info depth 12 multipv 5 pv a2a3\n\
+                      bestmove e2e4\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(output),
+            fen,
+            EngineVariant::Standard,
+            12,
+            5,
+            |_| {},
+        )
+        .expect("analysis should parse");
+
+        let losses: Vec<Option<i32>> = analysis
+            .lines
+            .iter()
+            .map(|line| line.cp_loss_vs_best)
+            .collect();
+        assert_eq!(
+            losses,
+            vec![
+                Some(0),
+                Some(25),
+                Some(70),
+                Some(40 + MATE_SCORE_CP - 2),
+                None
+            ]
+        );
+
+        let mating = "info depth 9 multipv 1 score mate 2 pv e2e4\n\
+                      info depth 9 multipv 2 score mate 3 pv d2d4\n\
+                      info depth 9 multipv 3 score cp 500 pv g1f3\n\
+                      bestmove e2e4\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(mating),
+            fen,
+            EngineVariant::Standard,
+            9,
+            3,
+            |_| {},
+        )
+        .expect("analysis should parse");
+        let losses: Vec<Option<i32>> = analysis
+            .lines
+            .iter()
+            .map(|line| line.cp_loss_vs_best)
+            .collect();
+        assert_eq!(
+            losses,
+            vec![Some(0), Some(1), Some(MATE_SCORE_CP - 2 - 500)]
+        );
+    }
+
+    #[test]
+    fn white_score_flips_black_to_move_scores() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let output = "info depth 10 multipv 1 score cp 35 pv e7e5\n\
+                      bestmove e7e5\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(output),
+            fen,
+            EngineVariant::Standard,
+            10,
+            1,
+            |_| {},
+        )
+        .expect("analysis should parse");
+
+        assert_eq!(analysis.score_cp, Some(35));
+        assert_eq!(analysis.white_score(Color::Black), Score::Cp(-35));
+        assert_eq!(analysis.white_score(Color::White), Score::Cp(35));
+
+        let mated = "info depth 10 multipv 1 score mate -2 pv e7e5\n\
+                     bestmove e7e5\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(mated),
+            fen,
+            EngineVariant::Standard,
+            10,
+            1,
+            |_| {},
+        )
+        .expect("analysis should parse");
+        assert_eq!(analysis.white_score(Color::Black), Score::Mate(2));
+
+        let unscored = "info depth 10 multipv 1 pv e7e5\n\
+                        bestmove e7e5\n";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(unscored),
+            fen,
+            EngineVariant::Standard,
+            10,
+            1,
+            |_| {},
+        )
+        .expect("analysis should parse");
+        assert_eq!(analysis.white_score(Color::Black), Score::Cp(0));
+    }
+
+    #[test]
+    fn analysis_reports_only_move_in_forced_positions() {
+        let fen = "k7/2K5/8/8/8/8/8/1R6 b - - 0 1";
+        let output = "info depth 20 score mate -3 pv a8a7 b1b8
+bestmove a8a7
+";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(output),
+            fen,
+            EngineVariant::Standard,
+            20,
+            1,
+            |_| {},
+        )
+        .expect("analysis should parse");
+
+        assert_eq!(analysis.only_move.as_deref(), Some("a8a7"));
+        assert_eq!(analysis.bestmove_san.as_deref(), Some("Ka7"));
+    }
+
+    #[test]
+    fn chess960_analysis_reports_only_move_with_shredder_castling_rights() {
+        let fen = "k7/8/8/8/8/8/8/1RK3R1 b BG - 0 1";
+        let output = "info depth 12 score cp -900 pv a8a7
+bestmove a8a7
+";
+        let analysis = collect_analysis_result(
+            &mut Cursor::new(output),
+            fen,
+            EngineVariant::Chess960,
+            12,
+            1,
+            |_| {},
+        )
+        .expect("analysis should parse");
+
+        assert_eq!(analysis.only_move.as_deref(), Some("a8a7"));
+        assert_eq!(analysis.available_lines, Some(1));
+    }
 }
@@ -0,0 +1,204 @@
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::engine::{
+    EngineSession, bestmove_uci_to_san, legal_uci_moves, normalized_depth, only_uci_move,
+    pv_uci_to_san, session_position,
+};
+use crate::types::{EngineAnalysis, EngineLine, EngineVariant, EvalCacheError};
+
+/// Columns added to `eval_cache` after it was first created, filled in on
+/// open for caches written by older versions.
+const EVAL_CACHE_COLUMN_MIGRATIONS: &[(&str, &str)] = &[
+    ("ponder", "TEXT"),
+    ("chess960", "INTEGER NOT NULL DEFAULT 0"),
+    ("requested_depth", "INTEGER"),
+];
+
+/// Every cached eval is a single-line (MultiPV 1) search, stored with its
+/// ponder move and whether it was run under Chess960 castling rules; a
+/// lookup under the other rules is a miss. `depth` is the depth the engine
+/// reported and `requested_depth` the depth it was asked for, which is higher
+/// when it stopped early on a forced mate or a single legal move.
+fn ensure_schema(conn: &Connection) -> Result<(), EvalCacheError> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS eval_cache (
+            fen TEXT PRIMARY KEY,
+            depth INTEGER NOT NULL,
+            score_cp INTEGER,
+            score_mate INTEGER,
+            bestmove TEXT,
+            pv TEXT NOT NULL DEFAULT ''
+        );
+        ",
+    )?;
+
+    let existing: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('eval_cache')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    for (name, declaration) in EVAL_CACHE_COLUMN_MIGRATIONS {
+        if !existing.iter().any(|column| column == name) {
+            conn.execute_batch(&format!(
+                "ALTER TABLE eval_cache ADD COLUMN {name} {declaration};"
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn lookup_cached_eval(
+    conn: &Connection,
+    fen: &str,
+    min_depth: u32,
+    variant: EngineVariant,
+) -> Result<Option<EngineAnalysis>, EvalCacheError> {
+    let cached = conn
+        .query_row(
+            "
+            SELECT depth, score_cp, score_mate, bestmove, pv, ponder
+            FROM eval_cache
+            WHERE fen = ?1
+              AND MAX(depth, COALESCE(requested_depth, 0)) >= ?2
+              AND chess960 = ?3
+            ",
+            params![fen, min_depth, variant == EngineVariant::Chess960],
+            |row| {
+                let pv_text: String = row.get(4)?;
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, Option<i32>>(1)?,
+                    row.get::<_, Option<i32>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    pv_text,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    let Some((depth, score_cp, score_mate, bestmove, pv_text, ponder)) = cached else {
+        return Ok(None);
+    };
+
+    let pv: Vec<String> = pv_text.split_whitespace().map(ToOwned::to_owned).collect();
+    let san_pv = pv_uci_to_san(fen, &pv, variant);
+    let bestmove_uci = pv.first().cloned().or(bestmove);
+    let bestmove_san = bestmove_uci_to_san(fen, bestmove_uci.as_deref(), variant);
+    let start = session_position(fen, variant);
+    Ok(Some(EngineAnalysis {
+        depth,
+        score_cp,
+        score_mate,
+        bestmove_uci,
+        bestmove_san,
+        pv: pv.clone(),
+        san_pv: san_pv.clone(),
+        lines: vec![EngineLine {
+            multipv_rank: 1,
+            depth,
+            score_cp,
+            score_mate,
+            pv,
+            san_pv,
+            cp_loss_vs_best: (score_cp.is_some() || score_mate.is_some()).then_some(0),
+        }],
+        only_move: start.as_ref().and_then(only_uci_move),
+        requested_lines: 1,
+        ponder,
+        available_lines: start
+            .as_ref()
+            .and_then(|position| u32::try_from(legal_uci_moves(position).len()).ok()),
+    }))
+}
+
+/// Stores `analysis` with the depth it reached and the depth it was run for.
+/// An engine that stops short of the request (a forced mate, a single legal
+/// move) has still answered it, so later requests up to `requested_depth`
+/// hit the cache.
+fn store_cached_eval(
+    conn: &Connection,
+    fen: &str,
+    analysis: &EngineAnalysis,
+    requested_depth: u32,
+    variant: EngineVariant,
+) -> Result<(), EvalCacheError> {
+    conn.execute(
+        "
+        INSERT INTO eval_cache (fen, depth, score_cp, score_mate, bestmove, pv, ponder, chess960, requested_depth)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        ON CONFLICT(fen) DO UPDATE SET
+            depth = excluded.depth,
+            requested_depth = excluded.requested_depth,
+            score_cp = excluded.score_cp,
+            score_mate = excluded.score_mate,
+            bestmove = excluded.bestmove,
+            pv = excluded.pv,
+            ponder = excluded.ponder,
+            chess960 = excluded.chess960
+        WHERE MAX(excluded.depth, excluded.requested_depth)
+                >= MAX(eval_cache.depth, COALESCE(eval_cache.requested_depth, 0))
+            OR excluded.chess960 != eval_cache.chess960
+        ",
+        params![
+            fen,
+            analysis.depth,
+            analysis.score_cp,
+            analysis.score_mate,
+            analysis.bestmove_uci.as_deref(),
+            analysis.pv.join(" "),
+            analysis.ponder.as_deref(),
+            variant == EngineVariant::Chess960,
+            requested_depth
+        ],
+    )?;
+    Ok(())
+}
+
+/// Returns the cached single-line eval of `fen` when one reaches `depth`;
+/// otherwise runs the engine to `depth` and stores the result, replacing a
+/// shallower entry.
+pub fn cached_analyze(
+    db_path: &str,
+    engine_path: &str,
+    fen: &str,
+    depth: u32,
+) -> Result<EngineAnalysis, EvalCacheError> {
+    cached_analyze_with_variant(db_path, engine_path, fen, depth, EngineVariant::Standard)
+}
+
+/// Like [`cached_analyze`], but runs the engine and reads cached moves under
+/// `variant`'s castling rules.
+pub fn cached_analyze_with_variant(
+    db_path: &str,
+    engine_path: &str,
+    fen: &str,
+    depth: u32,
+    variant: EngineVariant,
+) -> Result<EngineAnalysis, EvalCacheError> {
+    let fen = fen.trim();
+    let depth = normalized_depth(depth);
+
+    let conn = Connection::open(db_path)?;
+    ensure_schema(&conn)?;
+
+    if let Some(cached) = lookup_cached_eval(&conn, fen, depth, variant)? {
+        return Ok(cached);
+    }
+
+    let analysis = EngineSession::start_with_variant(engine_path, variant)?.analyze(fen, depth)?;
+    store_cached_eval(&conn, fen, &analysis, depth, variant)?;
+    Ok(analysis)
+}
+
+/// Deepens the cached eval of `fen` to `target_depth`: a shallower entry is
+/// re-run and replaced, one that already reaches it is returned as is. The
+/// cache lookup of [`cached_analyze`] under the name re-analysis code uses.
+pub fn deepen_analysis(
+    db_path: &str,
+    engine_path: &str,
+    fen: &str,
+    target_depth: u32,
+) -> Result<EngineAnalysis, EvalCacheError> {
+    cached_analyze(db_path, engine_path, fen, target_depth)
+}
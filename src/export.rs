@@ -0,0 +1,516 @@
+use std::io::Write;
+
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use shakmaty::{Color, Position};
+
+use crate::analysis_workspace::{latest_workspace_ids, load_analysis_workspace};
+use crate::db::{game_tags_table_exists, open_read_connection, optional_games_column};
+use crate::query::build_where_clause;
+use crate::replay::{ReplayPosition, start_position_from_setup};
+use crate::types::{
+    AnalysisWorkspaceError, AnalysisWorkspaceNode, GameFilter, LoadedAnalysisWorkspace, QueryError,
+};
+
+const PGN_LINE_WIDTH: usize = 80;
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const RESULT_TOKENS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+const STANDARD_START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+struct ExportRow {
+    id: i64,
+    event: Option<String>,
+    site: Option<String>,
+    date: Option<String>,
+    white: Option<String>,
+    black: Option<String>,
+    result: Option<String>,
+    eco: Option<String>,
+    movetext: Option<String>,
+    setup_fen: Option<String>,
+    replay_error: Option<String>,
+}
+
+fn export_columns(conn: &Connection) -> rusqlite::Result<String> {
+    Ok(format!(
+        "rowid, event, site, date, white, black, result, eco, pgn, {}, {}",
+        optional_games_column(conn, "setup_fen")?,
+        optional_games_column(conn, "replay_error")?
+    ))
+}
+
+fn export_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ExportRow> {
+    Ok(ExportRow {
+        id: row.get(0)?,
+        event: row.get(1)?,
+        site: row.get(2)?,
+        date: row.get(3)?,
+        white: row.get(4)?,
+        black: row.get(5)?,
+        result: row.get(6)?,
+        eco: row.get(7)?,
+        movetext: row.get(8)?,
+        setup_fen: row.get(9)?,
+        replay_error: row.get(10)?,
+    })
+}
+
+fn stored_tags(
+    conn: &Connection,
+    has_tags_table: bool,
+    game_id: i64,
+) -> rusqlite::Result<Vec<(String, String)>> {
+    if !has_tags_table {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT name, value FROM game_tags WHERE game_id = ?1 ORDER BY rowid ASC",
+    )?;
+    stmt.query_map(params![game_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn parse_start_position(fen: &str) -> Option<ReplayPosition> {
+    start_position_from_setup(None, Some(fen)).ok()
+}
+
+/// The fullmove number and side to move at `setup_fen`, or at the standard
+/// start when it is absent or unparseable.
+fn start_numbering(setup_fen: Option<&str>) -> (u32, Color) {
+    match setup_fen.and_then(parse_start_position) {
+        Some(position) => (position.fullmoves().get(), position.turn()),
+        None => (1, Color::White),
+    }
+}
+
+fn numbered_movetext(setup_fen: Option<&str>, movetext: &str) -> String {
+    let (mut fullmove, mut turn) = start_numbering(setup_fen);
+
+    let mut tokens = Vec::new();
+    for (index, san) in movetext.split_whitespace().enumerate() {
+        match turn {
+            Color::White => tokens.push(format!("{fullmove}.")),
+            Color::Black if index == 0 => tokens.push(format!("{fullmove}...")),
+            Color::Black => {}
+        }
+        tokens.push(san.to_owned());
+        if turn == Color::Black {
+            fullmove += 1;
+        }
+        turn = !turn;
+    }
+    tokens.join(" ")
+}
+
+fn wrap_movetext(movetext: &str) -> String {
+    let mut out = String::new();
+    let mut line_len = 0;
+    for token in movetext.split_whitespace() {
+        if line_len > 0 && line_len + 1 + token.len() > PGN_LINE_WIDTH {
+            out.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            out.push(' ');
+            line_len += 1;
+        }
+        out.push_str(token);
+        line_len += token.len();
+    }
+    out
+}
+
+/// `$n` form of a workspace NAG; the common move symbols are translated and
+/// anything else that is not already `$n` is dropped.
+fn nag_token(nag: &str) -> Option<String> {
+    let nag = nag.trim();
+    let code = match nag {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => {
+            let digits = nag.strip_prefix('$')?;
+            return (!digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()))
+                .then(|| nag.to_owned());
+        }
+    };
+    Some(format!("${code}"))
+}
+
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+    let comment = comment.trim();
+    if !comment.is_empty() {
+        tokens.push(format!("{{{}}}", comment.replace('}', "")));
+    }
+}
+
+fn push_workspace_move(
+    tokens: &mut Vec<String>,
+    node: &AnalysisWorkspaceNode,
+    san: &str,
+    (fullmove, turn): (u32, Color),
+    force_number: bool,
+) {
+    match turn {
+        Color::White => tokens.push(format!("{fullmove}.")),
+        Color::Black if force_number => tokens.push(format!("{fullmove}...")),
+        Color::Black => {}
+    }
+    tokens.push(san.to_owned());
+    tokens.extend(node.nags.iter().filter_map(|nag| nag_token(nag)));
+    push_comment(tokens, &node.comment);
+}
+
+/// Writes the line below `parent`: first children form the line and later
+/// siblings become parenthesized variations. `budget` bounds the number of
+/// moves written so a damaged tree cannot recurse forever.
+fn push_workspace_line<'a>(
+    workspace: &'a LoadedAnalysisWorkspace,
+    mut parent: &'a AnalysisWorkspaceNode,
+    (mut fullmove, mut turn): (u32, Color),
+    mut force_number: bool,
+    budget: &mut usize,
+    tokens: &mut Vec<String>,
+) {
+    loop {
+        let children: Vec<(&AnalysisWorkspaceNode, &str)> = workspace
+            .children_of(&parent.id)
+            .into_iter()
+            .filter_map(|node| Some((node, node.san.as_deref()?)))
+            .collect();
+        let Some((&(main, main_san), alternatives)) = children.split_first() else {
+            return;
+        };
+        if *budget == 0 {
+            return;
+        }
+        *budget -= 1;
+
+        push_workspace_move(tokens, main, main_san, (fullmove, turn), force_number);
+        force_number = false;
+        let next = match turn {
+            Color::White => (fullmove, Color::Black),
+            Color::Black => (fullmove + 1, Color::White),
+        };
+        for &(alternative, san) in alternatives {
+            if *budget == 0 {
+                return;
+            }
+            *budget -= 1;
+            let mut variation = Vec::new();
+            push_workspace_move(&mut variation, alternative, san, (fullmove, turn), true);
+            push_workspace_line(workspace, alternative, next, false, budget, &mut variation);
+            tokens.push(format!("({})", variation.join(" ")));
+            force_number = true;
+        }
+
+        parent = main;
+        (fullmove, turn) = next;
+    }
+}
+
+/// Numbered movetext for a workspace tree, with variations, comments and
+/// NAGs, but no result token.
+fn workspace_movetext(workspace: &LoadedAnalysisWorkspace) -> String {
+    let Some(root) = workspace
+        .nodes
+        .iter()
+        .find(|node| node.id == workspace.workspace.root_node_id)
+    else {
+        return String::new();
+    };
+
+    let mut tokens = Vec::new();
+    push_comment(&mut tokens, &root.comment);
+    let mut budget = workspace.nodes.len();
+    push_workspace_line(
+        workspace,
+        root,
+        start_numbering(Some(&root.fen)),
+        true,
+        &mut budget,
+        &mut tokens,
+    );
+    tokens.join(" ")
+}
+
+fn format_game_pgn(row: &ExportRow, tags: &[(String, String)]) -> String {
+    format_game_pgn_with_movetext(row, tags, None)
+}
+
+/// Like [`format_game_pgn`], but with `movetext` (already numbered) in place
+/// of the stored moves when it is given.
+fn format_game_pgn_with_movetext(
+    row: &ExportRow,
+    tags: &[(String, String)],
+    movetext: Option<&str>,
+) -> String {
+    let tag_value = |name: &str| {
+        tags.iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.clone())
+    };
+    let result = row
+        .result
+        .clone()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "*".to_string());
+
+    let mut headers: Vec<(String, String)> = SEVEN_TAG_ROSTER
+        .iter()
+        .map(|name| {
+            let column = match *name {
+                "Event" => row.event.clone(),
+                "Site" => row.site.clone(),
+                "Date" => row.date.clone(),
+                "White" => row.white.clone(),
+                "Black" => row.black.clone(),
+                "Result" => Some(result.clone()),
+                _ => tag_value(name),
+            };
+            (name.to_string(), column.unwrap_or_else(|| "?".to_string()))
+        })
+        .collect();
+    for (name, value) in tags {
+        if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+            headers.push((name.clone(), value.clone()));
+        }
+    }
+    if let Some(eco) = &row.eco
+        && !headers.iter().any(|(name, _)| name == "ECO")
+    {
+        headers.push(("ECO".to_string(), eco.clone()));
+    }
+    if let Some(setup_fen) = &row.setup_fen
+        && !headers.iter().any(|(name, _)| name == "FEN")
+    {
+        headers.push(("SetUp".to_string(), "1".to_string()));
+        headers.push(("FEN".to_string(), setup_fen.clone()));
+    }
+
+    let stored_movetext = row.movetext.as_deref().unwrap_or("").trim();
+    let mut body = if let Some(movetext) = movetext {
+        movetext.to_owned()
+    } else if row.replay_error.is_some() {
+        stored_movetext.to_owned()
+    } else {
+        numbered_movetext(row.setup_fen.as_deref(), stored_movetext)
+    };
+    let ends_with_result = body
+        .split_whitespace()
+        .last()
+        .is_some_and(|token| RESULT_TOKENS.contains(&token));
+    if !ends_with_result {
+        if !body.is_empty() {
+            body.push(' ');
+        }
+        body.push_str(&result);
+    }
+
+    let mut out = String::new();
+    for (name, value) in headers {
+        out.push_str(&format!("[{name} \"{}\"]\n", escape_tag_value(&value)));
+    }
+    out.push('\n');
+    out.push_str(&wrap_movetext(&body));
+    out.push('\n');
+    out
+}
+
+/// Streams every game matching `filter` to `out` as one JSON-encoded
+/// [`crate::NewGame`] per line, in insertion order, and returns the number of
+/// games written.
+#[cfg(feature = "serde")]
+pub fn export_games_ndjson(
+    db_path: &str,
+    filter: &GameFilter,
+    mut out: impl Write,
+) -> Result<u64, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, values) = build_where_clause(&conn, filter)?;
+    let has_tags_table = game_tags_table_exists(&conn)?;
+    let (setup_fen, opening, variation) = (
+        optional_games_column(&conn, "setup_fen")?,
+        optional_games_column(&conn, "opening")?,
+        optional_games_column(&conn, "variation")?,
+    );
+
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT rowid, event, site, date, white, black, result, eco, pgn, {setup_fen}, {opening}, {variation}
+        FROM games
+        {where_clause}
+        ORDER BY rowid ASC
+        "
+    ))?;
+    let mut rows = stmt.query(params_from_iter(values.iter()))?;
+
+    let mut written = 0u64;
+    while let Some(row) = rows.next()? {
+        let game = crate::types::NewGame {
+            event: row.get(1)?,
+            site: row.get(2)?,
+            date: row.get(3)?,
+            white: row.get(4)?,
+            black: row.get(5)?,
+            result: row.get(6)?,
+            eco: row.get(7)?,
+            pgn: row.get(8)?,
+            setup_fen: row.get(9)?,
+            opening: row.get(10)?,
+            variation: row.get(11)?,
+            tags: stored_tags(&conn, has_tags_table, row.get(0)?)?,
+        };
+        serde_json::to_writer(&mut out, &game).map_err(std::io::Error::from)?;
+        out.write_all(b"\n")?;
+        written += 1;
+    }
+    out.flush()?;
+
+    Ok(written)
+}
+
+/// Renders one stored game as a PGN block with headers and numbered movetext.
+pub fn export_game_pgn(db_path: &str, game_id: i64) -> Result<String, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let columns = export_columns(&conn)?;
+    let row = conn
+        .query_row(
+            &format!("SELECT {columns} FROM games WHERE rowid = ?1"),
+            params![game_id],
+            export_row,
+        )
+        .optional()?
+        .ok_or(QueryError::GameNotFound(game_id))?;
+
+    let tags = stored_tags(&conn, game_tags_table_exists(&conn)?, row.id)?;
+    Ok(format_game_pgn(&row, &tags))
+}
+
+/// Streams every game matching `filter` to `out` as PGN, in insertion order,
+/// and returns the number of games written.
+pub fn export_games_pgn(
+    db_path: &str,
+    filter: &GameFilter,
+    mut out: impl Write,
+) -> Result<u64, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, values) = build_where_clause(&conn, filter)?;
+    let columns = export_columns(&conn)?;
+    let has_tags_table = game_tags_table_exists(&conn)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT {columns}
+        FROM games
+        {where_clause}
+        ORDER BY rowid ASC
+        "
+    ))?;
+    let mut rows = stmt.query(params_from_iter(values.iter()))?;
+
+    let mut written = 0u64;
+    while let Some(row) = rows.next()? {
+        let row = export_row(row)?;
+        let tags = stored_tags(&conn, has_tags_table, row.id)?;
+        if written > 0 {
+            out.write_all(b"\n")?;
+        }
+        out.write_all(format_game_pgn(&row, &tags).as_bytes())?;
+        written += 1;
+    }
+    out.flush()?;
+
+    Ok(written)
+}
+
+/// Renders an analysis workspace as a PGN block whose movetext carries every
+/// variation, comment and NAG. The workspace name becomes the `Event` tag and
+/// the root position is written as a `FEN` tag unless it is the standard
+/// start.
+pub fn export_workspace_pgn(
+    analysis_db_path: &str,
+    workspace_id: i64,
+) -> Result<String, AnalysisWorkspaceError> {
+    let workspace = load_analysis_workspace(analysis_db_path, workspace_id)?;
+    let setup_fen = workspace
+        .nodes
+        .iter()
+        .find(|node| node.id == workspace.workspace.root_node_id)
+        .map(|root| root.fen.trim())
+        .filter(|fen| *fen != STANDARD_START_FEN && parse_start_position(fen).is_some())
+        .map(str::to_owned);
+    let row = ExportRow {
+        id: workspace.workspace.game_id,
+        event: Some(workspace.workspace.name.clone()),
+        site: None,
+        date: None,
+        white: None,
+        black: None,
+        result: None,
+        eco: None,
+        movetext: None,
+        setup_fen,
+        replay_error: None,
+    };
+    Ok(format_game_pgn_with_movetext(
+        &row,
+        &[],
+        Some(&workspace_movetext(&workspace)),
+    ))
+}
+
+/// Streams every game matching `filter` to `out` as PGN, like
+/// [`export_games_pgn`], but games studied in `analysis_db_path` carry the
+/// variations and comments of their most recently updated workspace in place
+/// of the stored moves. Workspaces are matched on `games_db_path` as it was
+/// given when they were saved. Returns the number of games written.
+pub fn export_studied_games_pgn(
+    games_db_path: &str,
+    analysis_db_path: &str,
+    filter: &GameFilter,
+    mut out: impl Write,
+) -> Result<u64, QueryError> {
+    let conn = open_read_connection(games_db_path)?;
+    let (where_clause, values) = build_where_clause(&conn, filter)?;
+    let columns = export_columns(&conn)?;
+    let has_tags_table = game_tags_table_exists(&conn)?;
+    let studied = latest_workspace_ids(analysis_db_path, games_db_path)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT {columns}
+        FROM games
+        {where_clause}
+        ORDER BY rowid ASC
+        "
+    ))?;
+    let mut rows = stmt.query(params_from_iter(values.iter()))?;
+
+    let mut written = 0u64;
+    while let Some(row) = rows.next()? {
+        let row = export_row(row)?;
+        let tags = stored_tags(&conn, has_tags_table, row.id)?;
+        let movetext = match studied.get(&row.id) {
+            Some(&workspace_id) => {
+                let workspace = load_analysis_workspace(analysis_db_path, workspace_id)?;
+                Some(workspace_movetext(&workspace)).filter(|movetext| !movetext.is_empty())
+            }
+            None => None,
+        };
+        if written > 0 {
+            out.write_all(b"\n")?;
+        }
+        out.write_all(format_game_pgn_with_movetext(&row, &tags, movetext.as_deref()).as_bytes())?;
+        written += 1;
+    }
+    out.flush()?;
+
+    Ok(written)
+}
@@ -1,15 +1,26 @@
 use std::io::{BufRead, BufReader, Cursor, Read};
 use std::ops::ControlFlow;
+use std::path::Path;
 use std::process::{Child, ChildStdout, Command, Stdio};
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use pgn_reader::{RawTag, Reader, SanPlus, Visitor};
-use rusqlite::{Connection, Result as SqlResult, params};
+use rusqlite::{Connection, Result as SqlResult, Statement, params};
+use shakmaty::Position;
 
-use crate::types::{ImportError, ImportSummary};
+use crate::analysis::position_result;
+use crate::db::{
+    IMPORTED_AT_SQL, backfill_ply_counts, cleanup_orphaned_game_tags, ensure_game_tags_table,
+    ensure_games_columns, optional_games_column_in, with_attached_db,
+};
+use crate::eco::classify_opening;
+use crate::replay::{final_position, is_plain_chess};
+use crate::types::{
+    ImportError, ImportOptions, ImportSummary, ImportedHeaders, Locale, PgnGameReport,
+    PgnGameStatus, PgnReport, QueryError, ReplayError,
+};
 
-const PROGRESS_EMIT_GAMES_INTERVAL: usize = 1_000;
-const PROGRESS_EMIT_TIME_INTERVAL: Duration = Duration::from_millis(300);
+const UTF8_BOM: char = '\u{feff}';
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 struct GameHeaders {
@@ -20,12 +31,47 @@ struct GameHeaders {
     black: Option<String>,
     result: Option<String>,
     eco: Option<String>,
+    setup_fen: Option<String>,
+    opening: Option<String>,
+    variation: Option<String>,
+    variant: Option<String>,
+    tags: Vec<(String, String)>,
     movetext: String,
 }
 
+/// Optional extras threaded through [`ingest_game_chunk`] by the import entry
+/// points that need them.
+#[derive(Default)]
+struct IngestHooks<'a> {
+    inserted_ids: Option<&'a mut Vec<i64>>,
+    header_filter: Option<&'a dyn Fn(&ImportedHeaders) -> bool>,
+}
+
 impl GameHeaders {
+    fn imported_headers(&self) -> ImportedHeaders {
+        ImportedHeaders {
+            event: self.event.clone(),
+            site: self.site.clone(),
+            date: self.date.clone(),
+            white: self.white.clone(),
+            black: self.black.clone(),
+            result: self.result.clone(),
+            eco: self.eco.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    fn tag_value(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.trim())
+    }
+
     fn set_tag(&mut self, name: &[u8], value: RawTag<'_>) {
         let value = value.decode_utf8_lossy().into_owned();
+        self.tags
+            .push((String::from_utf8_lossy(name).into_owned(), value.clone()));
         match name {
             b"Event" => self.event = Some(value),
             b"Site" => self.site = Some(value),
@@ -34,6 +80,10 @@ impl GameHeaders {
             b"Black" => self.black = Some(value),
             b"Result" => self.result = Some(value),
             b"ECO" => self.eco = Some(value),
+            b"FEN" => self.setup_fen = Some(value),
+            b"Opening" => self.opening = Some(value),
+            b"Variation" => self.variation = Some(value),
+            b"Variant" => self.variant = Some(value),
             _ => {}
         }
     }
@@ -145,13 +195,64 @@ fn ensure_exact_dedupe_index(tx: &rusqlite::Transaction<'_>) -> SqlResult<()> {
     )
 }
 
-struct ZstdProcessReader {
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PgnCompression {
+    Zstd,
+    Gzip,
+}
+
+impl PgnCompression {
+    fn program(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        }
+    }
+}
+
+/// Magic bytes win over the file extension, so compressed downloads without
+/// a `.zst`/`.gz` suffix are still decoded.
+fn detect_compression(pgn_path: &str) -> std::io::Result<Option<PgnCompression>> {
+    let mut magic = [0u8; 4];
+    let mut file = std::fs::File::open(pgn_path)?;
+    let mut filled = 0;
+    while filled < magic.len() {
+        let bytes_read = file.read(&mut magic[filled..])?;
+        if bytes_read == 0 {
+            break;
+        }
+        filled += bytes_read;
+    }
+
+    let magic = &magic[..filled];
+    if magic.starts_with(ZSTD_MAGIC) {
+        return Ok(Some(PgnCompression::Zstd));
+    }
+    if magic.starts_with(GZIP_MAGIC) {
+        return Ok(Some(PgnCompression::Gzip));
+    }
+
+    let lower_path = pgn_path.to_ascii_lowercase();
+    if lower_path.ends_with(".zst") {
+        Ok(Some(PgnCompression::Zstd))
+    } else if lower_path.ends_with(".gz") {
+        Ok(Some(PgnCompression::Gzip))
+    } else {
+        Ok(None)
+    }
+}
+
+struct DecompressProcessReader {
+    program: &'static str,
     child: Option<Child>,
     stdout: ChildStdout,
     eof_validated: bool,
 }
 
-impl Read for ZstdProcessReader {
+impl Read for DecompressProcessReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         if self.eof_validated {
             return Ok(0);
@@ -167,7 +268,7 @@ impl Read for ZstdProcessReader {
             if !status.success() {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
-                    format!("zstd failed with status {status}"),
+                    format!("{} failed with status {status}", self.program),
                 ));
             }
         }
@@ -177,7 +278,7 @@ impl Read for ZstdProcessReader {
     }
 }
 
-impl Drop for ZstdProcessReader {
+impl Drop for DecompressProcessReader {
     fn drop(&mut self) {
         if let Some(mut child) = self.child.take() {
             let _ = child.kill();
@@ -187,8 +288,9 @@ impl Drop for ZstdProcessReader {
 }
 
 fn open_pgn_reader(pgn_path: &str) -> std::result::Result<Box<dyn Read>, ImportError> {
-    if pgn_path.to_ascii_lowercase().ends_with(".zst") {
-        let mut child = Command::new("zstd")
+    if let Some(compression) = detect_compression(pgn_path)? {
+        let program = compression.program();
+        let mut child = Command::new(program)
             .arg("-d")
             .arg("-c")
             .arg(pgn_path)
@@ -196,12 +298,12 @@ fn open_pgn_reader(pgn_path: &str) -> std::result::Result<Box<dyn Read>, ImportE
             .stderr(Stdio::null())
             .spawn()?;
 
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| std::io::Error::other("failed to capture zstd stdout pipe"))?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            std::io::Error::other(format!("failed to capture {program} stdout pipe"))
+        })?;
 
-        return Ok(Box::new(ZstdProcessReader {
+        return Ok(Box::new(DecompressProcessReader {
+            program,
             child: Some(child),
             stdout,
             eof_validated: false,
@@ -226,15 +328,316 @@ fn parse_game_chunk(chunk: &str) -> std::io::Result<GameHeaders> {
     }
 }
 
+/// Reduces PGN movetext to the space-separated SAN the importer stores,
+/// dropping move numbers, comments, NAGs, variations and the result. The
+/// moves must replay legally, from the `FEN` tag if one is present.
+pub fn normalize_movetext(raw: &str) -> Result<String, ReplayError> {
+    let game =
+        parse_game_chunk(raw).map_err(|err| ReplayError::InvalidMovetext(err.to_string()))?;
+    final_position(
+        game.variant.as_deref(),
+        game.setup_fen.as_deref(),
+        &game.movetext,
+    )?;
+    Ok(game.movetext)
+}
+
+/// Splits and parses `pgn` the way import does, replaying each game, without
+/// touching a database.
+pub fn validate_pgn(pgn: &str) -> PgnReport {
+    let mut report = PgnReport::default();
+    let mut reader = Cursor::new(pgn.as_bytes());
+    // Reading from an in-memory `&str` cannot fail.
+    let _ = for_each_game_chunk(&mut reader, |chunk, line| {
+        let status = match parse_game_chunk(chunk) {
+            Err(err) => PgnGameStatus::ParseError(err.to_string()),
+            Ok(game) => {
+                let movetext = game.movetext.trim();
+                match final_position(game.variant.as_deref(), game.setup_fen.as_deref(), movetext) {
+                    Ok(_) => PgnGameStatus::Valid,
+                    Err(err) => PgnGameStatus::InvalidMoves(describe_replay_error(&err)),
+                }
+            }
+        };
+        match status {
+            PgnGameStatus::Valid => report.valid += 1,
+            PgnGameStatus::ParseError(_) => report.parse_errors += 1,
+            PgnGameStatus::InvalidMoves(_) => report.invalid += 1,
+        }
+        report.games.push(PgnGameReport { line, status });
+        Ok::<_, std::io::Error>(())
+    });
+    report
+}
+
+/// Splits `pgn` into the raw text of each game, headers and movetext
+/// included, using the same game boundaries as import. Line endings are
+/// normalized to `\n`; nothing is parsed or replayed.
+pub fn split_pgn(pgn: &str) -> Vec<String> {
+    let mut games = Vec::new();
+    let mut reader = Cursor::new(pgn.as_bytes());
+    // Reading from an in-memory `&str` cannot fail.
+    let _ = for_each_game_chunk(&mut reader, |chunk, _| {
+        games.push(chunk.to_string());
+        Ok::<_, std::io::Error>(())
+    });
+    games
+}
+
+/// Whether a line can open PGN text: a tag pair, a comment, a move number, a
+/// result or a SAN move. Anything else before the first game is preamble.
+fn starts_pgn_text(line: &str) -> bool {
+    let Some(token) = line.split_whitespace().next() else {
+        return false;
+    };
+    let is_move_number = token.starts_with(|c: char| c.is_ascii_digit())
+        && token
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .starts_with('.');
+    token.starts_with(['[', '{'])
+        || is_move_number
+        || is_result_token(token)
+        || SanPlus::from_ascii(token.as_bytes()).is_ok()
+}
+
+fn is_result_token(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+/// Whether a movetext line finishes its game, i.e. its last token is a result.
+fn ends_with_result_token(line: &str) -> bool {
+    !line.trim_start().starts_with('[')
+        && line
+            .split_whitespace()
+            .next_back()
+            .is_some_and(is_result_token)
+}
+
+/// Splits `1-0 [Event "..."]` so the tags start a line of their own, returning
+/// the detached tag section.
+fn split_inline_event_tag(line: &mut String) -> Option<String> {
+    if line.trim_start().starts_with('[') {
+        return None;
+    }
+    let index = line
+        .match_indices("[Event ")
+        .map(|(index, _)| index)
+        .find(|&index| {
+            line[..index]
+                .split_whitespace()
+                .next_back()
+                .is_some_and(is_result_token)
+        })?;
+    let tail = line.split_off(index);
+    line.truncate(line.trim_end().len());
+    line.push('\n');
+    Some(tail)
+}
+
+/// The movetext section of a raw chunk, i.e. everything after the tag pairs.
+fn raw_movetext(chunk: &str) -> String {
+    chunk
+        .lines()
+        .skip_while(|line| {
+            let line = line.trim();
+            line.is_empty() || line.starts_with('[')
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+/// The English SAN letter for a localized piece letter or figurine; pawn
+/// figurines map to nothing.
+fn english_piece(piece: char, locale: Locale) -> Option<&'static str> {
+    let figurine = match piece {
+        '♔' | '♚' => Some("K"),
+        '♕' | '♛' => Some("Q"),
+        '♖' | '♜' => Some("R"),
+        '♗' | '♝' => Some("B"),
+        '♘' | '♞' => Some("N"),
+        '♙' | '♟' => Some(""),
+        _ => None,
+    };
+    figurine.or(match (locale, piece) {
+        (Locale::English, _) => None,
+        (Locale::German, 'K') | (Locale::Dutch, 'K') => Some("K"),
+        (Locale::French | Locale::Spanish | Locale::Italian, 'R') => Some("K"),
+        (_, 'D') => Some("Q"),
+        (_, 'T') => Some("R"),
+        (Locale::German | Locale::Dutch, 'L') => Some("B"),
+        (Locale::French, 'F') => Some("B"),
+        (Locale::Spanish | Locale::Italian, 'A') => Some("B"),
+        (Locale::German, 'S') => Some("N"),
+        (Locale::Dutch, 'P') => Some("N"),
+        (Locale::French | Locale::Spanish | Locale::Italian, 'C') => Some("N"),
+        _ => None,
+    })
+}
+
+/// Rewrites localized piece letters in movetext to English: the letter
+/// starting a move and the one after `=` in a promotion. Tag lines and
+/// comments are left as they are.
+fn translate_san_pieces(chunk: &str, locale: Locale) -> String {
+    let mut out = String::with_capacity(chunk.len());
+    let mut in_comment = false;
+    for line in chunk.split_inclusive('\n') {
+        if !in_comment && line.trim_start().starts_with('[') {
+            out.push_str(line);
+            continue;
+        }
+
+        let mut move_start = true;
+        for (index, c) in line.char_indices() {
+            if in_comment {
+                in_comment = c != '}';
+                out.push(c);
+                continue;
+            }
+            match c {
+                '{' => in_comment = true,
+                ';' => {
+                    out.push_str(&line[index..]);
+                    break;
+                }
+                _ => {}
+            }
+            match english_piece(c, locale).filter(|_| move_start) {
+                Some(english) => out.push_str(english),
+                None => out.push(c),
+            }
+            move_start = c.is_whitespace() || matches!(c, '.' | '(' | ')' | '=');
+        }
+    }
+    out
+}
+
+fn describe_replay_error(err: &ReplayError) -> String {
+    match err {
+        ReplayError::InvalidSan { ply, san, .. } => format!("illegal SAN '{san}' at ply {ply}"),
+        ReplayError::InvalidSetupFen(fen) => format!("invalid setup FEN '{fen}'"),
+        ReplayError::UnsupportedVariant(variant) => format!("unsupported variant '{variant}'"),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Splits PGN text into one chunk per game and calls `on_chunk` with each
+/// chunk and the 1-based line it starts on. A leading BOM and any lines
+/// before the first one that can start PGN text (see [`starts_pgn_text`])
+/// are skipped, so a file without tags still yields its movetext.
+fn for_each_game_chunk<R, E, F>(reader: &mut R, mut on_chunk: F) -> std::result::Result<(), E>
+where
+    R: BufRead,
+    E: From<std::io::Error>,
+    F: FnMut(&str, usize) -> std::result::Result<(), E>,
+{
+    let mut chunk = String::new();
+    let mut chunk_line = 0;
+    let mut line = String::new();
+    let mut line_number = 0;
+    let mut seen_pgn_text = false;
+    let mut previous_ended_game = false;
+    loop {
+        line.clear();
+        let bytes_read = reader.read_line(&mut line)?;
+        if bytes_read == 0 {
+            if !chunk.trim().is_empty() {
+                on_chunk(&chunk, chunk_line)?;
+            }
+            return Ok(());
+        }
+        line_number += 1;
+
+        if line.ends_with("\r\n") {
+            line.truncate(line.len() - 2);
+            line.push('\n');
+        }
+
+        if !seen_pgn_text {
+            let preamble = line.trim_start_matches(UTF8_BOM).trim_start();
+            if !starts_pgn_text(preamble) {
+                continue;
+            }
+            let preamble_len = line.len() - preamble.len();
+            line.drain(..preamble_len);
+            seen_pgn_text = true;
+        }
+
+        let inline_tags = split_inline_event_tag(&mut line);
+        for segment in std::iter::once(line.as_str()).chain(inline_tags.as_deref()) {
+            // Some exporters omit the blank line between games, so a tag line
+            // right after a result token also starts a new game.
+            let starts_game =
+                segment.starts_with("[Event ") || (previous_ended_game && segment.starts_with('['));
+            if starts_game && !chunk.trim().is_empty() {
+                on_chunk(&chunk, chunk_line)?;
+                chunk.clear();
+            }
+
+            if !segment.trim().is_empty() {
+                previous_ended_game = ends_with_result_token(segment);
+            }
+            if chunk.trim().is_empty() {
+                chunk_line = line_number;
+            }
+            chunk.push_str(segment);
+        }
+    }
+}
+
+/// The insert every import path uses for a game. Binds `event`, `site`,
+/// `date`, `white`, `black`, `result`, `eco`, `pgn`, `setup_fen`,
+/// `replay_error`, `ply_count`, `opening`, `variation` and `variant` as `?1`
+/// to `?14`; `imported_at` is filled in here. Exact duplicates are ignored.
+fn prepare_game_insert(conn: &Connection) -> SqlResult<Statement<'_>> {
+    conn.prepare(&format!(
+        "
+        INSERT OR IGNORE INTO main.games (event, site, date, white, black, result, eco, pgn, setup_fen, replay_error, ply_count, opening, variation, imported_at, variant)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, {IMPORTED_AT_SQL}, ?14)
+        ",
+    ))
+}
+
+/// Stores one tag pair, bound as `(game_id, name, value)`.
+fn prepare_tag_insert(conn: &Connection) -> SqlResult<Statement<'_>> {
+    conn.prepare(
+        "
+        INSERT OR REPLACE INTO main.game_tags (game_id, name, value)
+        VALUES (?1, ?2, ?3)
+        ",
+    )
+}
+
 fn ingest_game_chunk(
-    insert_stmt: &mut rusqlite::Statement<'_>,
+    conn: &Connection,
+    insert_stmt: &mut Statement<'_>,
+    tag_stmt: &mut Statement<'_>,
     chunk: &str,
+    options: &ImportOptions,
     summary: &mut ImportSummary,
+    hooks: &mut IngestHooks<'_>,
 ) -> std::result::Result<(), ImportError> {
     summary.total += 1;
 
+    let localized;
+    let chunk = match options.san_locale {
+        Some(locale) => {
+            localized = translate_san_pieces(chunk, locale);
+            localized.as_str()
+        }
+        None => chunk,
+    };
+
     match parse_game_chunk(chunk) {
-        Ok(game) => {
+        Ok(mut game) => {
+            if let Some(keep) = hooks.header_filter
+                && !keep(&game.imported_headers())
+            {
+                summary.filtered += 1;
+                return Ok(());
+            }
+
             let movetext = game.movetext.trim();
             let movetext = if movetext.is_empty() {
                 None
@@ -242,6 +645,61 @@ fn ingest_game_chunk(
                 Some(movetext)
             };
 
+            // Un-replayable SAN is kept as the raw movetext and flagged, so it
+            // never looks like a replayable game. Games in variants replay does
+            // not support are flagged without being replayed.
+            let replay = movetext.map(|movetext| {
+                final_position(game.variant.as_deref(), game.setup_fen.as_deref(), movetext)
+            });
+            let (end_position, replay_error) = match replay {
+                Some(Ok(position)) => (Some(position), None),
+                Some(Err(err)) => (None, Some(describe_replay_error(&err))),
+                None => (None, None),
+            };
+            if replay_error.is_some() && options.skip_invalid_games {
+                summary.invalid += 1;
+                return Ok(());
+            }
+            let raw_movetext = replay_error.as_ref().map(|_| raw_movetext(chunk));
+            let movetext = raw_movetext.as_deref().or(movetext);
+
+            // Flagged games keep NULL like `backfill_ply_counts` leaves them:
+            // the raw movetext token count is not a ply count.
+            let ply_count = match game.movetext.split_whitespace().count() {
+                _ if replay_error.is_some() => None,
+                0 => game
+                    .tag_value("PlyCount")
+                    .and_then(|value| value.parse::<i64>().ok()),
+                count => i64::try_from(count).ok(),
+            };
+
+            let result_unknown = game
+                .result
+                .as_deref()
+                .is_none_or(|result| matches!(result.trim(), "" | "*"));
+            if options.infer_result_from_checkmate
+                && result_unknown
+                && let Some(end_position) = end_position.as_ref()
+                && end_position.is_checkmate()
+                && let Some(inferred) = position_result(end_position)
+            {
+                game.result = Some(inferred.to_owned());
+            }
+
+            let eco_missing = game.eco.as_deref().is_none_or(|eco| eco.trim().is_empty());
+            if options.classify_missing_eco
+                && eco_missing
+                && replay_error.is_none()
+                && game
+                    .setup_fen
+                    .as_deref()
+                    .is_none_or(|fen| fen.trim().is_empty())
+                && is_plain_chess(game.variant.as_deref())
+                && let Some((eco, _)) = movetext.and_then(classify_opening)
+            {
+                game.eco = Some(eco);
+            }
+
             let inserted_rows = insert_stmt.execute(params![
                 game.event.as_deref(),
                 game.site.as_deref(),
@@ -250,11 +708,28 @@ fn ingest_game_chunk(
                 game.black.as_deref(),
                 game.result.as_deref(),
                 game.eco.as_deref(),
-                movetext
+                movetext,
+                game.setup_fen.as_deref(),
+                replay_error.as_deref(),
+                ply_count,
+                game.opening.as_deref(),
+                game.variation.as_deref(),
+                game.variant.as_deref()
             ])?;
 
             if inserted_rows == 1 {
-                summary.inserted += 1;
+                let game_id = conn.last_insert_rowid();
+                for (name, value) in &game.tags {
+                    tag_stmt.execute(params![game_id, name, value])?;
+                }
+                if let Some(ids) = hooks.inserted_ids.as_deref_mut() {
+                    ids.push(game_id);
+                }
+                if replay_error.is_some() {
+                    summary.invalid += 1;
+                } else {
+                    summary.inserted += 1;
+                }
             } else {
                 summary.skipped += 1;
             }
@@ -267,17 +742,23 @@ fn ingest_game_chunk(
     Ok(())
 }
 
-fn maybe_emit_progress<F>(summary: ImportSummary, last_emit: &mut Instant, on_progress: &mut F)
-where
+fn maybe_emit_progress<F>(
+    summary: ImportSummary,
+    options: &ImportOptions,
+    last_emit: &mut Instant,
+    on_progress: &mut F,
+) where
     F: FnMut(ImportSummary),
 {
     if summary.total == 0 {
         return;
     }
 
-    if summary.total.is_multiple_of(PROGRESS_EMIT_GAMES_INTERVAL)
-        || last_emit.elapsed() >= PROGRESS_EMIT_TIME_INTERVAL
-    {
+    let games_due = options.progress_games_interval > 0
+        && summary
+            .total
+            .is_multiple_of(options.progress_games_interval);
+    if games_due || last_emit.elapsed() >= options.progress_time_interval {
         on_progress(summary);
         *last_emit = Instant::now();
     }
@@ -293,7 +774,101 @@ pub fn import_pgn_file(
 pub fn import_pgn_file_with_progress<F>(
     db_path: &str,
     pgn_path: &str,
+    on_progress: F,
+) -> std::result::Result<ImportSummary, ImportError>
+where
+    F: FnMut(ImportSummary),
+{
+    import_pgn_file_with_options(db_path, pgn_path, ImportOptions::default(), on_progress)
+}
+
+pub fn import_pgn_file_with_options<F>(
+    db_path: &str,
+    pgn_path: &str,
+    options: ImportOptions,
+    on_progress: F,
+) -> std::result::Result<ImportSummary, ImportError>
+where
+    F: FnMut(ImportSummary),
+{
+    import_pgn_file_inner(
+        db_path,
+        pgn_path,
+        options,
+        on_progress,
+        IngestHooks::default(),
+    )
+}
+
+/// Imports with default options, storing only the games whose headers satisfy
+/// `predicate`. Rejected games are counted in `ImportSummary::filtered`; games
+/// that fail to parse never reach the predicate and count as errors.
+pub fn import_pgn_file_filtered<P, F>(
+    db_path: &str,
+    pgn_path: &str,
+    predicate: P,
+    on_progress: F,
+) -> std::result::Result<ImportSummary, ImportError>
+where
+    P: Fn(&ImportedHeaders) -> bool,
+    F: FnMut(ImportSummary),
+{
+    import_pgn_file_inner(
+        db_path,
+        pgn_path,
+        ImportOptions::default(),
+        on_progress,
+        IngestHooks {
+            inserted_ids: None,
+            header_filter: Some(&predicate),
+        },
+    )
+}
+
+/// Like [`import_pgn_file_with_options`], but also returns the rowids of the
+/// games this import created, in file order. Duplicates that were skipped are
+/// not included; games stored with a `replay_error` are.
+pub fn import_pgn_file_returning_ids(
+    db_path: &str,
+    pgn_path: &str,
+    options: ImportOptions,
+) -> std::result::Result<(ImportSummary, Vec<i64>), ImportError> {
+    let mut inserted_ids = Vec::new();
+    let summary = import_pgn_file_inner(
+        db_path,
+        pgn_path,
+        options,
+        |_| {},
+        IngestHooks {
+            inserted_ids: Some(&mut inserted_ids),
+            header_filter: None,
+        },
+    )?;
+    Ok((summary, inserted_ids))
+}
+
+/// Commits the games ingested so far and opens the next batch's
+/// transaction on the same connection. A failed `COMMIT` leaves the batch
+/// for the caller to roll back, and the import fails rather than letting
+/// later inserts autocommit one by one outside any transaction.
+fn commit_batch(conn: &Connection) -> Result<(), ImportError> {
+    conn.execute_batch("COMMIT")?;
+    conn.execute_batch("BEGIN")?;
+    if conn.is_autocommit() {
+        return Err(ImportError::Sql(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+            Some("import batch transaction did not reopen".to_owned()),
+        )));
+    }
+    Ok(())
+}
+
+fn import_pgn_file_inner<F>(
+    db_path: &str,
+    pgn_path: &str,
+    options: ImportOptions,
     mut on_progress: F,
+    mut hooks: IngestHooks<'_>,
 ) -> std::result::Result<ImportSummary, ImportError>
 where
     F: FnMut(ImportSummary),
@@ -303,48 +878,327 @@ where
     let mut reader = BufReader::new(reader);
 
     let tx = conn.transaction()?;
+    ensure_games_columns(&tx)?;
+    ensure_game_tags_table(&tx)?;
+    let _ = backfill_ply_counts(&tx)?;
+    let _ = cleanup_orphaned_game_tags(&tx)?;
     let _ = cleanup_exact_duplicate_rows(&tx)?;
     ensure_exact_dedupe_index(&tx)?;
 
-    let mut insert_stmt = tx.prepare(
-        "
-        INSERT OR IGNORE INTO games (event, site, date, white, black, result, eco, pgn)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-        ",
-    )?;
+    let mut insert_stmt = prepare_game_insert(&tx)?;
+    let mut tag_stmt = prepare_tag_insert(&tx)?;
 
     let mut summary = ImportSummary::default();
     on_progress(summary);
     let mut last_emit = Instant::now();
 
-    let mut chunk = String::new();
-    let mut line = String::new();
-    loop {
-        line.clear();
-        let bytes_read = reader.read_line(&mut line)?;
-        if bytes_read == 0 {
-            if !chunk.trim().is_empty() {
-                ingest_game_chunk(&mut insert_stmt, &chunk, &mut summary)?;
-                maybe_emit_progress(summary, &mut last_emit, &mut on_progress);
-            }
-            break;
+    for_each_game_chunk(&mut reader, |chunk, _| {
+        ingest_game_chunk(
+            &tx,
+            &mut insert_stmt,
+            &mut tag_stmt,
+            chunk,
+            &options,
+            &mut summary,
+            &mut hooks,
+        )?;
+        if let Some(every) = options.commit_every.filter(|every| *every > 0)
+            && summary.total.is_multiple_of(every)
+        {
+            // The prepared statements stay valid across the commit, and the
+            // outer transaction still commits or rolls back the last batch.
+            commit_batch(&tx)?;
         }
+        maybe_emit_progress(summary, &options, &mut last_emit, &mut on_progress);
+        Ok::<_, ImportError>(())
+    })?;
 
-        if line.starts_with("[Event ") && !chunk.trim().is_empty() {
-            ingest_game_chunk(&mut insert_stmt, &chunk, &mut summary)?;
-            maybe_emit_progress(summary, &mut last_emit, &mut on_progress);
-            chunk.clear();
+    let removed = cleanup_stale_empty_movetext_rows(&tx)? + cleanup_exact_duplicate_rows(&tx)?;
+    ensure_exact_dedupe_index(&tx)?;
+    let _ = cleanup_orphaned_game_tags(&tx)?;
+    if removed > 0
+        && let Some(ids) = hooks.inserted_ids
+    {
+        let mut exists_stmt = tx.prepare("SELECT 1 FROM games WHERE rowid = ?1")?;
+        let mut kept = Vec::with_capacity(ids.len());
+        for &id in ids.iter() {
+            if exists_stmt.exists(params![id])? {
+                kept.push(id);
+            }
         }
+        *ids = kept;
+    }
+    drop(insert_stmt);
+    drop(tag_stmt);
+    tx.commit()?;
+
+    on_progress(summary);
+    Ok(summary)
+}
+
+/// Imports one JSON-encoded [`NewGame`] per line, skipping blank lines and
+/// exact duplicates. Lines that fail to parse count as errors; games that fail
+/// to replay are stored flagged and count as invalid.
+#[cfg(feature = "serde")]
+pub fn import_games_ndjson(
+    db_path: &str,
+    reader: impl BufRead,
+) -> std::result::Result<ImportSummary, ImportError> {
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+    ensure_games_columns(&tx)?;
+    ensure_game_tags_table(&tx)?;
+    let _ = cleanup_orphaned_game_tags(&tx)?;
+    let _ = cleanup_exact_duplicate_rows(&tx)?;
+    ensure_exact_dedupe_index(&tx)?;
 
-        chunk.push_str(&line);
+    let mut summary = ImportSummary::default();
+    {
+        let mut insert_stmt = prepare_game_insert(&tx)?;
+        let mut tag_stmt = prepare_tag_insert(&tx)?;
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            summary.total += 1;
+
+            let Ok(game) = serde_json::from_str::<crate::types::NewGame>(&line) else {
+                summary.errors += 1;
+                continue;
+            };
+            let movetext = game
+                .pgn
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty());
+            let variant = game
+                .tags
+                .iter()
+                .find(|(name, _)| name == "Variant")
+                .map(|(_, value)| value.as_str());
+            let replay_error = movetext
+                .and_then(|movetext| {
+                    final_position(variant, game.setup_fen.as_deref(), movetext).err()
+                })
+                .map(|err| describe_replay_error(&err));
+            let ply_count = movetext
+                .filter(|_| replay_error.is_none())
+                .map(|movetext| movetext.split_whitespace().count() as i64);
+
+            let inserted_rows = insert_stmt.execute(params![
+                game.event.as_deref(),
+                game.site.as_deref(),
+                game.date.as_deref(),
+                game.white.as_deref(),
+                game.black.as_deref(),
+                game.result.as_deref(),
+                game.eco.as_deref(),
+                movetext,
+                game.setup_fen.as_deref(),
+                replay_error.as_deref(),
+                ply_count,
+                game.opening.as_deref(),
+                game.variation.as_deref(),
+                variant
+            ])?;
+
+            if inserted_rows == 1 {
+                let game_id = tx.last_insert_rowid();
+                for (name, value) in &game.tags {
+                    tag_stmt.execute(params![game_id, name, value])?;
+                }
+                if replay_error.is_some() {
+                    summary.invalid += 1;
+                } else {
+                    summary.inserted += 1;
+                }
+            } else {
+                summary.skipped += 1;
+            }
+        }
     }
+    tx.commit()?;
+
+    Ok(summary)
+}
+
+/// Renames a player in both the `white` and `black` columns. Names match
+/// `from` ignoring surrounding whitespace and ASCII case. Returns the number
+/// of games changed; games that become exact duplicates of an older game are
+/// then removed, as on import.
+pub fn rename_player(db_path: &str, from: &str, to: &str) -> Result<u64, QueryError> {
+    rewrite_headers(db_path, |tx| {
+        tx.execute(
+            "
+            UPDATE games
+            SET white = CASE WHEN LOWER(TRIM(white)) = LOWER(?1) THEN ?2 ELSE white END,
+                black = CASE WHEN LOWER(TRIM(black)) = LOWER(?1) THEN ?2 ELSE black END
+            WHERE LOWER(TRIM(white)) = LOWER(?1) OR LOWER(TRIM(black)) = LOWER(?1)
+            ",
+            params![from.trim(), to],
+        )
+    })
+}
+
+/// Trims and collapses runs of whitespace in the event, site, white, black,
+/// result and ECO headers. Returns the number of games changed; games that
+/// become exact duplicates of an older game are then removed.
+pub fn normalize_headers(db_path: &str) -> Result<u64, QueryError> {
+    rewrite_headers(db_path, |tx| {
+        let rows: Vec<(i64, [Option<String>; 6])> = {
+            let mut stmt =
+                tx.prepare("SELECT rowid, event, site, white, black, result, eco FROM games")?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    [
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ],
+                ))
+            })?
+            .collect::<SqlResult<_>>()?
+        };
+
+        let mut update_stmt = tx.prepare(
+            "
+            UPDATE games
+            SET event = ?2, site = ?3, white = ?4, black = ?5, result = ?6, eco = ?7
+            WHERE rowid = ?1
+            ",
+        )?;
+        let mut changed = 0;
+        for (game_id, headers) in rows {
+            let normalized = headers.clone().map(|value| {
+                value.map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+            });
+            if normalized != headers {
+                let [event, site, white, black, result, eco] = normalized;
+                changed += update_stmt
+                    .execute(params![game_id, event, site, white, black, result, eco])?;
+            }
+        }
+        Ok(changed)
+    })
+}
+
+/// Runs a header rewrite with the exact-duplicate index dropped, since the
+/// rewrite may create duplicates the index would reject, then dedupes the way
+/// import does and rebuilds the index.
+fn rewrite_headers<F>(db_path: &str, rewrite: F) -> Result<u64, QueryError>
+where
+    F: FnOnce(&rusqlite::Transaction<'_>) -> SqlResult<usize>,
+{
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
 
-    let _ = cleanup_stale_empty_movetext_rows(&tx)?;
+    tx.execute_batch("DROP INDEX IF EXISTS idx_games_exact_unique;")?;
+    let changed = rewrite(&tx)?;
     let _ = cleanup_exact_duplicate_rows(&tx)?;
     ensure_exact_dedupe_index(&tx)?;
-    drop(insert_stmt);
+    let _ = cleanup_orphaned_game_tags(&tx)?;
     tx.commit()?;
 
-    on_progress(summary);
+    Ok(changed as u64)
+}
+
+pub fn merge_databases(
+    dest_db: &str,
+    src_db: &str,
+) -> std::result::Result<ImportSummary, ImportError> {
+    if !Path::new(src_db).is_file() {
+        return Err(ImportError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("source database '{src_db}' does not exist"),
+        )));
+    }
+
+    let mut conn = Connection::open(dest_db)?;
+    with_attached_db(&mut conn, src_db, "merge_src", merge_attached_games)
+}
+
+/// Copies every game of the attached `merge_src` database into `main` in one
+/// transaction; see [`merge_databases`].
+fn merge_attached_games(conn: &mut Connection) -> std::result::Result<ImportSummary, ImportError> {
+    let tx = conn.transaction()?;
+    ensure_games_columns(&tx)?;
+    ensure_game_tags_table(&tx)?;
+    let _ = cleanup_orphaned_game_tags(&tx)?;
+    let _ = cleanup_exact_duplicate_rows(&tx)?;
+    ensure_exact_dedupe_index(&tx)?;
+
+    let source_column = |name| optional_games_column_in(&tx, "merge_src", name);
+    let source_setup_fen = source_column("setup_fen")?;
+    let source_replay_error = source_column("replay_error")?;
+    let source_ply_count = source_column("ply_count")?;
+    let source_opening = source_column("opening")?;
+    let source_variation = source_column("variation")?;
+    let source_variant = source_column("variant")?;
+    let source_has_tags: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM merge_src.sqlite_master WHERE type = 'table' AND name = 'game_tags')",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let mut summary = ImportSummary::default();
+    {
+        let mut select_stmt = tx.prepare(&format!(
+            "
+            SELECT rowid, event, site, date, white, black, result, eco, pgn, {source_setup_fen}, {source_replay_error}, {source_ply_count}, {source_opening}, {source_variation}, {source_variant}
+            FROM merge_src.games
+            ORDER BY rowid ASC
+            "
+        ))?;
+        let mut insert_stmt = prepare_game_insert(&tx)?;
+        let mut copy_tags_stmt = if source_has_tags {
+            Some(tx.prepare(
+                "
+                INSERT OR REPLACE INTO main.game_tags (game_id, name, value)
+                SELECT ?1, name, value FROM merge_src.game_tags WHERE game_id = ?2
+                ",
+            )?)
+        } else {
+            None
+        };
+
+        let mut rows = select_stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            summary.total += 1;
+            let source_id: i64 = row.get(0)?;
+            let inserted_rows = insert_stmt.execute(params![
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, Option<String>>(8)?,
+                row.get::<_, Option<String>>(9)?,
+                row.get::<_, Option<String>>(10)?,
+                row.get::<_, Option<i64>>(11)?,
+                row.get::<_, Option<String>>(12)?,
+                row.get::<_, Option<String>>(13)?,
+                row.get::<_, Option<String>>(14)?
+            ])?;
+
+            if inserted_rows == 1 {
+                let game_id = tx.last_insert_rowid();
+                if let Some(copy_tags_stmt) = copy_tags_stmt.as_mut() {
+                    copy_tags_stmt.execute(params![game_id, source_id])?;
+                }
+                summary.inserted += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+    }
+    tx.commit()?;
     Ok(summary)
 }
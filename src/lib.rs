@@ -1,25 +1,70 @@
 mod analysis;
 mod analysis_workspace;
+#[cfg(feature = "async")]
+mod async_api;
 mod db;
+mod eco;
 mod engine;
+mod eval_cache;
+mod export;
 mod import;
 mod query;
 mod replay;
 mod types;
 
-pub use analysis::{apply_uci_to_fen, legal_uci_moves_for_fen};
+pub use analysis::{
+    apply_san_to_fen, apply_uci_to_fen, fen_after_sans, game_accuracy, is_legal_san, is_legal_uci,
+    legal_uci_moves_for_fen, only_legal_move, position_info, position_key, result_from_position,
+    square_from_uci,
+};
 pub use analysis_workspace::{
-    delete_analysis_workspace, init_analysis_workspace_db, list_analysis_workspaces,
-    load_analysis_workspace, rename_analysis_workspace, save_analysis_workspace,
-};
-pub use db::init_db;
-pub use engine::{EngineSession, analyze_position, analyze_position_multipv};
-pub use import::{import_pgn_file, import_pgn_file_with_progress};
-pub use query::{count_games, search_games};
-pub use replay::{replay_game, replay_game_fens};
+    delete_analysis_workspace, delete_workspaces_for_game, find_transpositions,
+    init_analysis_workspace_db, list_analysis_workspaces, list_dangling_workspaces,
+    list_recent_workspaces, load_analysis_workspace, rename_analysis_workspace, repair_analysis_db,
+    save_analysis_workspace, save_analysis_workspace_verified,
+};
+#[cfg(feature = "async")]
+pub use async_api::{
+    count_games_async, import_pgn_file_async, import_pgn_file_with_options_async, init_db_async,
+    replay_game_async, search_games_async,
+};
+pub use db::{backup_db, check_schema, init_db};
+pub use eco::{backfill_eco, classify_opening, eco_coverage, eco_name};
+pub use engine::{
+    DEFAULT_MAX_MULTIPV, EngineIoHook, EngineSession, MATE_SCORE_CP, analyze_nodes,
+    analyze_position, analyze_position_multipv, analyze_positions,
+};
+pub use eval_cache::{cached_analyze, cached_analyze_with_variant, deepen_analysis};
+#[cfg(feature = "serde")]
+pub use export::export_games_ndjson;
+pub use export::{
+    export_game_pgn, export_games_pgn, export_studied_games_pgn, export_workspace_pgn,
+};
+#[cfg(feature = "serde")]
+pub use import::import_games_ndjson;
+pub use import::{
+    import_pgn_file, import_pgn_file_filtered, import_pgn_file_returning_ids,
+    import_pgn_file_with_options, import_pgn_file_with_progress, merge_databases,
+    normalize_headers, normalize_movetext, rename_player, split_pgn, validate_pgn,
+};
+pub use query::{
+    count_games, database_stats, games_per_day, games_since, length_histogram, list_ecos,
+    player_color_split, result_consistency_report, search_games, search_games_page,
+    search_games_sorted, search_games_with_total, unique_positions,
+};
+pub use replay::{
+    DEFAULT_MAX_REPLAY_PLIES, REPLAY_PROGRESS_PLY_INTERVAL, ReplayIterator, board_timeline,
+    replay_frames, replay_game, replay_game_fens, replay_game_with_max_plies,
+    replay_game_with_progress, replay_previews, replay_sans_canonical, side_to_move_at_ply,
+};
+pub use shakmaty::{Color, Role, Square};
 pub use types::{
     AnalysisError, AnalysisWorkspaceError, AnalysisWorkspaceNode, AnalysisWorkspaceSummary,
-    AppliedMove, EngineAnalysis, EngineError, EngineLine, GameFilter, GameResultFilter, GameRow,
-    ImportError, ImportSummary, LoadedAnalysisWorkspace, Pagination, QueryError, ReplayError,
-    ReplayTimeline,
+    AppliedMove, DatabaseStats, DateFormatIssue, Direction, EngineAnalysis, EngineError,
+    EngineLine, EngineOption, EngineSessionOptions, EngineVariant, EvalCacheError, GameAccuracy,
+    GameFilter, GameResultFilter, GameRow, GamesSince, ImportError, ImportOptions, ImportSummary,
+    ImportedHeaders, LoadedAnalysisWorkspace, Locale, MoveEval, MoveKind, NewGame, Page,
+    Pagination, PgnGameReport, PgnGameStatus, PgnReport, PlayerAccuracy, PositionInfo, QueryError,
+    ReplayError, ReplayFrame, ReplayTimeline, ResultMismatch, SchemaStatus, Score, SortColumn,
+    SortOptions,
 };
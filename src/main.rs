@@ -1,9 +1,10 @@
 use chess_prep::{
     AnalysisWorkspaceNode, EngineSession, GameFilter, GameResultFilter, Pagination,
-    analyze_position, analyze_position_multipv, apply_uci_to_fen, count_games, import_pgn_file,
-    delete_analysis_workspace, import_pgn_file_with_progress, init_analysis_workspace_db, init_db,
-    legal_uci_moves_for_fen, list_analysis_workspaces, load_analysis_workspace,
-    rename_analysis_workspace, replay_game, replay_game_fens, save_analysis_workspace, search_games,
+    analyze_position, apply_san_to_fen, apply_uci_to_fen, count_games, delete_analysis_workspace,
+    import_pgn_file, import_pgn_file_with_progress, init_analysis_workspace_db, init_db,
+    legal_uci_moves_for_fen, list_analysis_workspaces, load_analysis_workspace, merge_databases,
+    rename_analysis_workspace, replay_game, replay_game_fens, save_analysis_workspace,
+    search_games,
 };
 
 use std::env;
@@ -13,11 +14,14 @@ fn print_usage(program: &str) {
     eprintln!("Usage: {program} init <db_path>");
     eprintln!("       {program} import <db_path> <pgn_path>");
     eprintln!("       {program} import <db_path> <pgn_path> --tsv");
+    eprintln!("       {program} merge <dest_db_path> <src_db_path>");
     eprintln!(
-        "       {program} search <db_path> [--search-text <text>] [--result <any|1-0|0-1|1/2-1/2>] [--eco <text>] [--event-or-site <text>] [--date-from <YYYY.MM.DD>] [--date-to <YYYY.MM.DD>] [--limit <n>] [--offset <n>]"
+        "       {program} search <db_path> [--search-text <text>] [--white <name>] [--black <name>] [--exact-player] [--result <any|1-0|0-1|1/2-1/2|*|missing>] [--eco <text>] [--eco-in <code,...>] [--eco-missing] [--opening <text>] [--contains-move <san>] [--event-or-site <text>] [--date-from <YYYY.MM.DD>] [--date-to <YYYY.MM.DD>] [--include-partial-dates] [--tag <name>=<value>]... [--limit <n, 0 = {default_limit}, max {max_limit}>] [--offset <n>]",
+        default_limit = Pagination::DEFAULT_LIMIT,
+        max_limit = Pagination::MAX_LIMIT
     );
     eprintln!(
-        "       {program} count <db_path> [--search-text <text>] [--result <any|1-0|0-1|1/2-1/2>] [--eco <text>] [--event-or-site <text>] [--date-from <YYYY.MM.DD>] [--date-to <YYYY.MM.DD>]"
+        "       {program} count <db_path> [--search-text <text>] [--white <name>] [--black <name>] [--exact-player] [--result <any|1-0|0-1|1/2-1/2|*|missing>] [--eco <text>] [--eco-in <code,...>] [--eco-missing] [--opening <text>] [--contains-move <san>] [--event-or-site <text>] [--date-from <YYYY.MM.DD>] [--date-to <YYYY.MM.DD>] [--include-partial-dates] [--tag <name>=<value>]..."
     );
     eprintln!("       {program} replay <db_path> <game_id>");
     eprintln!("       {program} replay-meta <db_path> <game_id>");
@@ -25,6 +29,7 @@ fn print_usage(program: &str) {
     eprintln!("       {program} analyze-multipv <engine_path> <fen> [--depth <n>] [--multipv <n>]");
     eprintln!("       {program} engine-session <engine_path>");
     eprintln!("       {program} apply-uci <fen> <uci>");
+    eprintln!("       {program} apply-san <fen> <san>");
     eprintln!("       {program} legal-uci <fen>");
     eprintln!("       {program} analysis-init <analysis_db_path>");
     eprintln!(
@@ -32,7 +37,9 @@ fn print_usage(program: &str) {
     );
     eprintln!("       {program} analysis-list <analysis_db_path> <source_db_path> <game_id>");
     eprintln!("       {program} analysis-load <analysis_db_path> <workspace_id>");
-    eprintln!("       {program} analysis-rename <analysis_db_path> <workspace_id> <workspace_name>");
+    eprintln!(
+        "       {program} analysis-rename <analysis_db_path> <workspace_id> <workspace_name>"
+    );
     eprintln!("       {program} analysis-delete <analysis_db_path> <workspace_id>");
 }
 
@@ -42,12 +49,23 @@ fn parse_result(value: &str) -> Result<GameResultFilter, String> {
         "1-0" => Ok(GameResultFilter::WhiteWin),
         "0-1" => Ok(GameResultFilter::BlackWin),
         "1/2-1/2" => Ok(GameResultFilter::Draw),
+        "*" => Ok(GameResultFilter::Unknown),
+        "missing" => Ok(GameResultFilter::Missing),
         _ => Err(format!(
-            "invalid result '{value}', expected one of: any, 1-0, 0-1, 1/2-1/2"
+            "invalid result '{value}', expected one of: any, 1-0, 0-1, 1/2-1/2, *, missing"
         )),
     }
 }
 
+fn parse_tag_filter(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, tag_value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_owned(), tag_value.to_owned()))
+        }
+        _ => Err(format!("invalid tag '{value}', expected <name>=<value>")),
+    }
+}
+
 fn parse_u32(name: &str, value: &str) -> Result<u32, String> {
     value
         .parse::<u32>()
@@ -81,9 +99,9 @@ fn parse_analysis_nodes_tsv(path: &str) -> Result<Vec<AnalysisWorkspaceNode>, St
         }
 
         let columns: Vec<&str> = line.split('\t').collect();
-        if columns.len() != 8 {
+        if columns.len() != 8 && columns.len() != 9 {
             return Err(format!(
-                "invalid nodes TSV line {}: expected 8 columns, got {}",
+                "invalid nodes TSV line {}: expected 8 or 9 columns, got {}",
                 line_index + 1,
                 columns.len()
             ));
@@ -113,6 +131,10 @@ fn parse_analysis_nodes_tsv(path: &str) -> Result<Vec<AnalysisWorkspaceNode>, St
             comment: columns[5].to_owned(),
             nags,
             sort_index,
+            metadata: columns
+                .get(8)
+                .filter(|value| !value.is_empty())
+                .map(|value| tsv_decode(value)),
         });
     }
 
@@ -137,6 +159,24 @@ fn parse_search_options(args: &[String]) -> Result<(GameFilter, Pagination), Str
                 filter.search_text = Some(value.clone());
                 i += 2;
             }
+            "--white" => {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| "missing value for --white".to_string())?;
+                filter.white = Some(value.clone());
+                i += 2;
+            }
+            "--black" => {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| "missing value for --black".to_string())?;
+                filter.black = Some(value.clone());
+                i += 2;
+            }
+            "--exact-player" => {
+                filter.player_exact = true;
+                i += 1;
+            }
             "--result" => {
                 let value = args
                     .get(i + 1)
@@ -151,6 +191,37 @@ fn parse_search_options(args: &[String]) -> Result<(GameFilter, Pagination), Str
                 filter.eco = Some(value.clone());
                 i += 2;
             }
+            "--eco-missing" => {
+                filter.eco_missing = true;
+                i += 1;
+            }
+            "--include-partial-dates" => {
+                filter.include_partial_dates = true;
+                i += 1;
+            }
+            "--eco-in" => {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| "missing value for --eco-in".to_string())?;
+                filter
+                    .eco_in
+                    .extend(value.split(',').map(|code| code.to_string()));
+                i += 2;
+            }
+            "--opening" => {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| "missing value for --opening".to_string())?;
+                filter.opening = Some(value.clone());
+                i += 2;
+            }
+            "--contains-move" => {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| "missing value for --contains-move".to_string())?;
+                filter.contains_move = Some(value.clone());
+                i += 2;
+            }
             "--event-or-site" => {
                 let value = args
                     .get(i + 1)
@@ -172,6 +243,13 @@ fn parse_search_options(args: &[String]) -> Result<(GameFilter, Pagination), Str
                 filter.date_to = Some(value.clone());
                 i += 2;
             }
+            "--tag" => {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| "missing value for --tag".to_string())?;
+                filter.tag_filters.push(parse_tag_filter(value)?);
+                i += 2;
+            }
             "--limit" => {
                 let value = args
                     .get(i + 1)
@@ -203,8 +281,8 @@ struct AnalyzeOptions {
 
 fn parse_multipv(value: &str) -> Result<u32, String> {
     let parsed = parse_u32("multipv", value)?;
-    if parsed == 0 || parsed > 10 {
-        return Err("invalid multipv, expected an integer in range 1..=10".to_string());
+    if parsed == 0 {
+        return Err("invalid multipv, expected a positive integer".to_string());
     }
     Ok(parsed)
 }
@@ -245,6 +323,43 @@ fn tsv_escape(value: Option<&str>) -> String {
     value.unwrap_or("").replace(['\t', '\n', '\r'], " ")
 }
 
+/// Like [`tsv_escape`], but reversible with [`tsv_decode`]: backslashes,
+/// tabs and line breaks become `\\`, `\t`, `\n` and `\r`. Used for opaque
+/// values such as node metadata that must round-trip byte for byte.
+fn tsv_encode(value: Option<&str>) -> String {
+    let value = value.unwrap_or("");
+    let mut encoded = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => encoded.push_str("\\\\"),
+            '\t' => encoded.push_str("\\t"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            other => encoded.push(other),
+        }
+    }
+    encoded
+}
+
+fn tsv_decode(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            decoded.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => decoded.push('\t'),
+            Some('n') => decoded.push('\n'),
+            Some('r') => decoded.push('\r'),
+            Some(other) => decoded.push(other),
+            None => decoded.push('\\'),
+        }
+    }
+    decoded
+}
+
 fn write_session_line(line: &str) -> Result<(), String> {
     let mut stdout = std::io::stdout().lock();
     writeln!(stdout, "{line}").map_err(|err| format!("failed to write session output: {err}"))?;
@@ -317,7 +432,7 @@ fn run_engine_session(engine_path: &str) -> Result<(), String> {
             match session.analyze_multipv(fen, depth, multipv) {
                 Ok(analysis) => {
                     let summary = format!(
-                        "ok-multipv\t{}\t{}\t{}\t{}\t{}",
+                        "ok-multipv\t{}\t{}\t{}\t{}\t{}\t{}",
                         analysis.depth,
                         analysis
                             .score_cp
@@ -327,14 +442,15 @@ fn run_engine_session(engine_path: &str) -> Result<(), String> {
                             .score_mate
                             .map(|value| value.to_string())
                             .unwrap_or_default(),
-                        tsv_escape(analysis.bestmove.as_deref()),
-                        tsv_escape(Some(&analysis.pv.join(" ")))
+                        tsv_escape(analysis.bestmove_uci.as_deref()),
+                        tsv_escape(Some(&analysis.pv.join(" "))),
+                        tsv_escape(analysis.bestmove_san.as_deref())
                     );
                     write_session_line(&summary)?;
 
                     for line in analysis.lines {
                         let row = format!(
-                            "line\t{}\t{}\t{}\t{}\t{}\t{}",
+                            "line\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
                             line.multipv_rank,
                             line.depth,
                             line.score_cp
@@ -344,7 +460,10 @@ fn run_engine_session(engine_path: &str) -> Result<(), String> {
                                 .map(|value| value.to_string())
                                 .unwrap_or_default(),
                             tsv_escape(Some(&line.pv.join(" "))),
-                            tsv_escape(Some(&line.san_pv.join(" ")))
+                            tsv_escape(Some(&line.san_pv.join(" "))),
+                            line.cp_loss_vs_best
+                                .map(|value| value.to_string())
+                                .unwrap_or_default()
                         );
                         write_session_line(&row)?;
                     }
@@ -379,7 +498,7 @@ fn run_engine_session(engine_path: &str) -> Result<(), String> {
             match session.analyze(fen, depth) {
                 Ok(analysis) => {
                     let line = format!(
-                        "ok\t{}\t{}\t{}\t{}\t{}",
+                        "ok\t{}\t{}\t{}\t{}\t{}\t{}",
                         analysis.depth,
                         analysis
                             .score_cp
@@ -389,8 +508,9 @@ fn run_engine_session(engine_path: &str) -> Result<(), String> {
                             .score_mate
                             .map(|value| value.to_string())
                             .unwrap_or_default(),
-                        tsv_escape(analysis.bestmove.as_deref()),
-                        tsv_escape(Some(&analysis.pv.join(" ")))
+                        tsv_escape(analysis.bestmove_uci.as_deref()),
+                        tsv_escape(Some(&analysis.pv.join(" "))),
+                        tsv_escape(analysis.bestmove_san.as_deref())
                     );
                     write_session_line(&line)?;
                 }
@@ -418,24 +538,44 @@ fn run() -> Result<(), String> {
                 format!("failed to import PGN file '{pgn_path}' into '{db_path}': {err:?}")
             })?;
             println!(
-                "Imported {} game(s) from '{}' into '{}' (inserted: {}, skipped: {}, errors: {})",
-                summary.total, pgn_path, db_path, summary.inserted, summary.skipped, summary.errors
+                "Imported {} game(s) from '{}' into '{}' (inserted: {}, skipped: {}, errors: {}, invalid: {})",
+                summary.total,
+                pgn_path,
+                db_path,
+                summary.inserted,
+                summary.skipped,
+                summary.errors,
+                summary.invalid
             );
             Ok(())
         }
         [_, command, db_path, pgn_path, tsv] if command == "import" && tsv == "--tsv" => {
             let summary = import_pgn_file_with_progress(db_path, pgn_path, |progress| {
                 println!(
-                    "progress\t{}\t{}\t{}\t{}",
-                    progress.total, progress.inserted, progress.skipped, progress.errors
+                    "progress\t{}\t{}\t{}\t{}\t{}",
+                    progress.total,
+                    progress.inserted,
+                    progress.skipped,
+                    progress.errors,
+                    progress.invalid
                 );
             })
             .map_err(|err| {
                 format!("failed to import PGN file '{pgn_path}' into '{db_path}': {err:?}")
             })?;
             println!(
-                "summary\t{}\t{}\t{}\t{}",
-                summary.total, summary.inserted, summary.skipped, summary.errors
+                "summary\t{}\t{}\t{}\t{}\t{}",
+                summary.total, summary.inserted, summary.skipped, summary.errors, summary.invalid
+            );
+            Ok(())
+        }
+        [_, command, dest_db_path, src_db_path] if command == "merge" => {
+            let summary = merge_databases(dest_db_path, src_db_path).map_err(|err| {
+                format!("failed to merge '{src_db_path}' into '{dest_db_path}': {err:?}")
+            })?;
+            println!(
+                "Merged {} game(s) from '{}' into '{}' (inserted: {}, skipped: {})",
+                summary.total, src_db_path, dest_db_path, summary.inserted, summary.skipped
             );
             Ok(())
         }
@@ -518,6 +658,17 @@ fn run() -> Result<(), String> {
             );
             Ok(())
         }
+        [_, command, fen, san] if command == "apply-san" => {
+            let applied = apply_san_to_fen(fen, san)
+                .map_err(|err| format!("failed to apply san '{san}' on fen '{fen}': {err:?}"))?;
+            println!(
+                "{}\t{}\t{}",
+                tsv_escape(Some(&applied.san)),
+                tsv_escape(Some(&applied.uci)),
+                tsv_escape(Some(&applied.fen))
+            );
+            Ok(())
+        }
         [_, command, fen] if command == "legal-uci" => {
             let legal_moves = legal_uci_moves_for_fen(fen)
                 .map_err(|err| format!("failed to list legal moves for fen '{fen}': {err:?}"))?;
@@ -534,7 +685,7 @@ fn run() -> Result<(), String> {
             })?;
 
             println!(
-                "{}\t{}\t{}\t{}\t{}",
+                "{}\t{}\t{}\t{}\t{}\t{}",
                 analysis.depth,
                 analysis
                     .score_cp
@@ -544,21 +695,31 @@ fn run() -> Result<(), String> {
                     .score_mate
                     .map(|value| value.to_string())
                     .unwrap_or_default(),
-                tsv_escape(analysis.bestmove.as_deref()),
-                tsv_escape(Some(&analysis.pv.join(" ")))
+                tsv_escape(analysis.bestmove_uci.as_deref()),
+                tsv_escape(Some(&analysis.pv.join(" "))),
+                tsv_escape(analysis.bestmove_san.as_deref())
             );
             Ok(())
         }
         [_, command, engine_path, fen, rest @ ..] if command == "analyze-multipv" => {
             let options = parse_analyze_multipv_options(rest)?;
-            let analysis =
-                analyze_position_multipv(engine_path, fen, options.depth, options.multipv)
-                    .map_err(|err| {
-                        format!("failed to analyze position with engine '{engine_path}': {err:?}")
-                    })?;
+            let mut session = EngineSession::start(engine_path)
+                .map_err(|err| format!("failed to start engine '{engine_path}': {err:?}"))?;
+            if options.multipv > session.max_multipv() {
+                return Err(format!(
+                    "invalid multipv {}, engine '{engine_path}' supports at most {}",
+                    options.multipv,
+                    session.max_multipv()
+                ));
+            }
+            let analysis = session
+                .analyze_multipv(fen, options.depth, options.multipv)
+                .map_err(|err| {
+                    format!("failed to analyze position with engine '{engine_path}': {err:?}")
+                })?;
 
             println!(
-                "summary\t{}\t{}\t{}\t{}\t{}",
+                "summary\t{}\t{}\t{}\t{}\t{}\t{}",
                 analysis.depth,
                 analysis
                     .score_cp
@@ -568,13 +729,14 @@ fn run() -> Result<(), String> {
                     .score_mate
                     .map(|value| value.to_string())
                     .unwrap_or_default(),
-                tsv_escape(analysis.bestmove.as_deref()),
-                tsv_escape(Some(&analysis.pv.join(" ")))
+                tsv_escape(analysis.bestmove_uci.as_deref()),
+                tsv_escape(Some(&analysis.pv.join(" "))),
+                tsv_escape(analysis.bestmove_san.as_deref())
             );
 
             for line in analysis.lines {
                 println!(
-                    "line\t{}\t{}\t{}\t{}\t{}\t{}",
+                    "line\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
                     line.multipv_rank,
                     line.depth,
                     line.score_cp
@@ -584,7 +746,10 @@ fn run() -> Result<(), String> {
                         .map(|value| value.to_string())
                         .unwrap_or_default(),
                     tsv_escape(Some(&line.pv.join(" "))),
-                    tsv_escape(Some(&line.san_pv.join(" ")))
+                    tsv_escape(Some(&line.san_pv.join(" "))),
+                    line.cp_loss_vs_best
+                        .map(|value| value.to_string())
+                        .unwrap_or_default()
                 );
             }
             Ok(())
@@ -679,7 +844,7 @@ fn run() -> Result<(), String> {
                 };
 
                 println!(
-                    "node\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    "node\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
                     tsv_escape(Some(&node.id)),
                     tsv_escape(node.parent_id.as_deref()),
                     tsv_escape(node.san.as_deref()),
@@ -687,13 +852,16 @@ fn run() -> Result<(), String> {
                     tsv_escape(Some(&node.fen)),
                     tsv_escape(Some(&node.comment)),
                     tsv_escape(Some(&nags)),
-                    node.sort_index
+                    node.sort_index,
+                    tsv_encode(node.metadata.as_deref())
                 );
             }
 
             Ok(())
         }
-        [_, command, analysis_db_path, workspace_id, workspace_name] if command == "analysis-rename" => {
+        [_, command, analysis_db_path, workspace_id, workspace_name]
+            if command == "analysis-rename" =>
+        {
             let workspace_id = parse_i64("workspace_id", workspace_id)?;
             rename_analysis_workspace(analysis_db_path, workspace_id, workspace_name)
                 .map_err(|err| format!("failed to rename analysis workspace: {err:?}"))?;
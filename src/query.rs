@@ -1,6 +1,26 @@
+use std::collections::HashSet;
+
 use rusqlite::{Connection, params_from_iter, types::Value};
+use shakmaty::san::SanPlus;
+
+use crate::analysis::{position_result, zobrist_key};
+use crate::db::{
+    NATURAL_COLLATION, game_tags_table_exists, games_column_exists, open_read_connection,
+    optional_games_column,
+};
+use crate::replay::{final_position, for_each_position};
+use crate::types::{
+    DatabaseStats, DateFormatIssue, GameFilter, GameResultFilter, GameRow, GamesSince, Page,
+    Pagination, QueryError, ResultMismatch, SortColumn, SortOptions,
+};
 
-use crate::types::{GameFilter, GameResultFilter, GameRow, Pagination, QueryError};
+const FULL_DATE_CLAUSE: &str = "date GLOB '[0-9][0-9][0-9][0-9].[0-9][0-9].[0-9][0-9]'";
+/// Full dates plus those with an unknown day or an unknown month and day.
+const PARTIAL_DATE_CLAUSE: &str = "(date GLOB '[0-9][0-9][0-9][0-9].[0-9][0-9].[0-9][0-9]' OR date GLOB '[0-9][0-9][0-9][0-9].[0-9][0-9].[?][?]' OR date GLOB '[0-9][0-9][0-9][0-9].[?][?].[?][?]')";
+/// The first and last day a partial date can stand for. A `.31` day is past
+/// every real day of its month, which is all string comparison needs.
+const PARTIAL_DATE_EARLIEST: &str = "REPLACE(date, '??', '01')";
+const PARTIAL_DATE_LATEST: &str = "REPLACE(REPLACE(date, '.??.??', '.12.31'), '.??', '.31')";
 
 fn normalized_filter_text(input: &Option<String>) -> Option<String> {
     let raw = input.as_ref()?;
@@ -12,29 +32,53 @@ fn normalized_filter_text(input: &Option<String>) -> Option<String> {
     }
 }
 
-fn validate_date_input(field: &'static str, value: &str) -> Result<(), QueryError> {
+fn date_format_issue(value: &str) -> Option<DateFormatIssue> {
     let bytes = value.as_bytes();
-    let valid = bytes.len() == 10
-        && bytes[4] == b'.'
-        && bytes[7] == b'.'
-        && bytes
-            .iter()
-            .enumerate()
-            .all(|(index, ch)| index == 4 || index == 7 || ch.is_ascii_digit());
-
-    if valid {
-        Ok(())
-    } else {
-        Err(QueryError::InvalidDateFormat {
+    if bytes.len() != 10 {
+        return Some(DateFormatIssue::Length {
+            actual: bytes.len(),
+        });
+    }
+
+    bytes.iter().enumerate().find_map(|(position, ch)| {
+        if position == 4 || position == 7 {
+            (*ch != b'.').then_some(DateFormatIssue::Separator { position })
+        } else {
+            (!ch.is_ascii_digit()).then_some(DateFormatIssue::NonDigit { position })
+        }
+    })
+}
+
+/// GLOB pattern matching `san` as a whole movetext token, with or without a
+/// check or mate suffix. GLOB rather than LIKE because SAN is case-sensitive:
+/// `Bxc6` and `bxc6` are different moves.
+fn contains_move_pattern(san: &str) -> Result<String, QueryError> {
+    let parsed =
+        SanPlus::from_ascii(san.as_bytes()).map_err(|_| QueryError::InvalidSan(san.to_owned()))?;
+    Ok(format!("* {}[+# ]*", parsed.san))
+}
+
+fn validate_date_input(field: &'static str, value: &str) -> Result<(), QueryError> {
+    match date_format_issue(value) {
+        None => Ok(()),
+        Some(reason) => Err(QueryError::InvalidDateFormat {
             field,
             value: value.to_owned(),
-        })
+            reason,
+        }),
     }
 }
 
-fn build_where_clause(filter: &GameFilter) -> Result<(String, Vec<Value>), QueryError> {
+/// Builds the `WHERE` clause for `filter`. Filters on columns or tables an
+/// unmigrated database lacks see them as empty rather than failing.
+pub(crate) fn build_where_clause(
+    conn: &Connection,
+    filter: &GameFilter,
+) -> Result<(String, Vec<Value>), QueryError> {
     let mut clauses = Vec::new();
     let mut values = Vec::new();
+    let eco_in_clause;
+    let opening_clause;
 
     if let Some(search_text) = normalized_filter_text(&filter.search_text) {
         clauses.push(
@@ -43,6 +87,31 @@ fn build_where_clause(filter: &GameFilter) -> Result<(String, Vec<Value>), Query
         values.push(Value::Text(format!("%{search_text}%")));
     }
 
+    let players = [
+        (
+            &filter.white,
+            "LOWER(TRIM(white)) = LOWER(?)",
+            "LOWER(COALESCE(white, '')) LIKE LOWER(?)",
+        ),
+        (
+            &filter.black,
+            "LOWER(TRIM(black)) = LOWER(?)",
+            "LOWER(COALESCE(black, '')) LIKE LOWER(?)",
+        ),
+    ];
+    for (name, exact_clause, substring_clause) in players {
+        let Some(name) = normalized_filter_text(name) else {
+            continue;
+        };
+        if filter.player_exact {
+            clauses.push(exact_clause);
+            values.push(Value::Text(name));
+        } else {
+            clauses.push(substring_clause);
+            values.push(Value::Text(format!("%{name}%")));
+        }
+    }
+
     match filter.result {
         GameResultFilter::Any => {}
         GameResultFilter::WhiteWin => {
@@ -57,6 +126,13 @@ fn build_where_clause(filter: &GameFilter) -> Result<(String, Vec<Value>), Query
             clauses.push("result = ?");
             values.push(Value::Text("1/2-1/2".to_string()));
         }
+        GameResultFilter::Unknown => {
+            clauses.push("result = ?");
+            values.push(Value::Text("*".to_string()));
+        }
+        GameResultFilter::Missing => {
+            clauses.push("COALESCE(TRIM(result), '') = ''");
+        }
     }
 
     if let Some(eco) = normalized_filter_text(&filter.eco) {
@@ -64,6 +140,38 @@ fn build_where_clause(filter: &GameFilter) -> Result<(String, Vec<Value>), Query
         values.push(Value::Text(format!("%{eco}%")));
     }
 
+    if filter.eco_missing {
+        clauses.push("COALESCE(TRIM(eco), '') = ''");
+    }
+
+    let eco_codes: Vec<String> = filter
+        .eco_in
+        .iter()
+        .map(|code| code.trim().to_ascii_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect();
+    if !eco_codes.is_empty() {
+        let placeholders = vec!["?"; eco_codes.len()].join(", ");
+        eco_in_clause = format!("UPPER(TRIM(eco)) IN ({placeholders})");
+        clauses.push(&eco_in_clause);
+        values.extend(eco_codes.into_iter().map(Value::Text));
+    }
+
+    if let Some(opening) = normalized_filter_text(&filter.opening) {
+        let opening_column = optional_games_column(conn, "opening")?;
+        let variation_column = optional_games_column(conn, "variation")?;
+        opening_clause = format!(
+            "LOWER(COALESCE({opening_column}, '') || ' ' || COALESCE({variation_column}, '')) LIKE LOWER(?)"
+        );
+        clauses.push(&opening_clause);
+        values.push(Value::Text(format!("%{opening}%")));
+    }
+
+    if let Some(san) = normalized_filter_text(&filter.contains_move) {
+        clauses.push("(' ' || COALESCE(pgn, '') || ' ') GLOB ?");
+        values.push(Value::Text(contains_move_pattern(&san)?));
+    }
+
     if let Some(event_or_site) = normalized_filter_text(&filter.event_or_site) {
         clauses.push("LOWER(COALESCE(event, '') || ' ' || COALESCE(site, '')) LIKE LOWER(?)");
         values.push(Value::Text(format!("%{event_or_site}%")));
@@ -73,22 +181,54 @@ fn build_where_clause(filter: &GameFilter) -> Result<(String, Vec<Value>), Query
     let date_to = normalized_filter_text(&filter.date_to);
     let has_date_filter = date_from.is_some() || date_to.is_some();
 
+    let date_from_clause;
+    let date_to_clause;
+    if filter.include_partial_dates {
+        date_from_clause = format!("{PARTIAL_DATE_EARLIEST} >= ?");
+        date_to_clause = format!("{PARTIAL_DATE_LATEST} <= ?");
+    } else {
+        date_from_clause = "date >= ?".to_string();
+        date_to_clause = "date <= ?".to_string();
+    }
+
     if has_date_filter {
-        clauses.push("date GLOB '[0-9][0-9][0-9][0-9].[0-9][0-9].[0-9][0-9]'");
+        clauses.push(if filter.include_partial_dates {
+            PARTIAL_DATE_CLAUSE
+        } else {
+            FULL_DATE_CLAUSE
+        });
     }
 
     if let Some(date_from) = date_from {
         validate_date_input("date_from", &date_from)?;
-        clauses.push("date >= ?");
+        clauses.push(&date_from_clause);
         values.push(Value::Text(date_from));
     }
 
     if let Some(date_to) = date_to {
         validate_date_input("date_to", &date_to)?;
-        clauses.push("date <= ?");
+        clauses.push(&date_to_clause);
         values.push(Value::Text(date_to));
     }
 
+    let has_tags_table = filter.tag_filters.is_empty() || game_tags_table_exists(conn)?;
+    for (name, value) in &filter.tag_filters {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        if !has_tags_table {
+            // No tags are stored, so no game can match.
+            clauses.push("0");
+            continue;
+        }
+        clauses.push(
+            "EXISTS (SELECT 1 FROM game_tags WHERE game_tags.game_id = games.rowid AND game_tags.name = ? AND game_tags.value = ?)",
+        );
+        values.push(Value::Text(name.to_owned()));
+        values.push(Value::Text(value.clone()));
+    }
+
     let where_clause = if clauses.is_empty() {
         String::new()
     } else {
@@ -98,13 +238,47 @@ fn build_where_clause(filter: &GameFilter) -> Result<(String, Vec<Value>), Query
     Ok((where_clause, values))
 }
 
+fn with_extra_clause(where_clause: &str, clause: &str) -> String {
+    if where_clause.is_empty() {
+        format!(" WHERE {clause}")
+    } else {
+        format!("{where_clause} AND {clause}")
+    }
+}
+
+fn order_by_clause(sort: SortOptions, has_tags_table: bool) -> String {
+    let direction = if sort.descending { "DESC" } else { "ASC" };
+    let expression = match sort.column {
+        SortColumn::Date => "date".to_string(),
+        SortColumn::Event => format!("event COLLATE {NATURAL_COLLATION}"),
+        SortColumn::Round if !has_tags_table => "NULL".to_string(),
+        SortColumn::Round => format!(
+            "(SELECT value FROM game_tags WHERE game_tags.game_id = games.rowid AND game_tags.name = 'Round') COLLATE {NATURAL_COLLATION}"
+        ),
+        SortColumn::White => "white COLLATE NOCASE".to_string(),
+        SortColumn::Black => "black COLLATE NOCASE".to_string(),
+        SortColumn::Result => "CASE result WHEN '1-0' THEN 0 WHEN '1/2-1/2' THEN 1 WHEN '0-1' THEN 2 WHEN '*' THEN 3 ELSE 4 END"
+            .to_string(),
+    };
+    format!("ORDER BY {expression} {direction}, rowid {direction}")
+}
+
 pub fn search_games(
     db_path: &str,
     filter: &GameFilter,
     page: Pagination,
 ) -> Result<Vec<GameRow>, QueryError> {
-    let conn = Connection::open(db_path)?;
-    let (where_clause, mut values) = build_where_clause(filter)?;
+    search_games_sorted(db_path, filter, page, SortOptions::default())
+}
+
+pub fn search_games_sorted(
+    db_path: &str,
+    filter: &GameFilter,
+    page: Pagination,
+    sort: SortOptions,
+) -> Result<Vec<GameRow>, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, mut values) = build_where_clause(&conn, filter)?;
     let page = page.normalized();
 
     let sql = format!(
@@ -112,9 +286,10 @@ pub fn search_games(
         SELECT rowid, event, site, date, white, black, result, eco
         FROM games
         {where_clause}
-        ORDER BY date DESC, rowid DESC
+        {order_by}
         LIMIT ? OFFSET ?
-        "
+        ",
+        order_by = order_by_clause(sort, game_tags_table_exists(&conn)?)
     );
 
     values.push(Value::Integer(i64::from(page.limit)));
@@ -141,9 +316,81 @@ pub fn search_games(
     Ok(games)
 }
 
+/// Games added after `since`, oldest first. Feeding the largest returned id
+/// back as `GamesSince::AfterRowid` gives a change feed of later imports.
+pub fn games_since(db_path: &str, since: GamesSince) -> Result<Vec<GameRow>, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, order_by, boundary) = match since {
+        GamesSince::AfterRowid(rowid) => ("rowid > ?1", "rowid", rowid),
+        GamesSince::ImportedAfter(timestamp) => {
+            if !games_column_exists(&conn, "imported_at")? {
+                return Ok(Vec::new());
+            }
+            ("imported_at > ?1", "imported_at, rowid", timestamp)
+        }
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT rowid, event, site, date, white, black, result, eco
+        FROM games
+        WHERE {where_clause}
+        ORDER BY {order_by}
+        "
+    ))?;
+    let rows = stmt.query_map([boundary], |row| {
+        Ok(GameRow {
+            id: row.get(0)?,
+            event: row.get(1)?,
+            site: row.get(2)?,
+            date: row.get(3)?,
+            white: row.get(4)?,
+            black: row.get(5)?,
+            result: row.get(6)?,
+            eco: row.get(7)?,
+        })
+    })?;
+
+    let mut games = Vec::new();
+    for row in rows {
+        games.push(row?);
+    }
+    Ok(games)
+}
+
+/// A page of matching games together with the total number of matches.
+pub fn search_games_with_total(
+    db_path: &str,
+    filter: &GameFilter,
+    page: Pagination,
+) -> Result<(Vec<GameRow>, u64), QueryError> {
+    let games = search_games(db_path, filter, page)?;
+    let total = count_games(db_path, filter)?;
+    Ok((games, total))
+}
+
+pub fn search_games_page(
+    db_path: &str,
+    filter: &GameFilter,
+    page: Pagination,
+) -> Result<Page<GameRow>, QueryError> {
+    let page = page.normalized();
+    let (items, total) = search_games_with_total(db_path, filter, page)?;
+    let end = u64::from(page.offset) + items.len() as u64;
+
+    Ok(Page {
+        has_prev: page.offset > 0,
+        has_next: end < total,
+        items,
+        total,
+        limit: page.limit,
+        offset: page.offset,
+    })
+}
+
 pub fn count_games(db_path: &str, filter: &GameFilter) -> Result<u64, QueryError> {
-    let conn = Connection::open(db_path)?;
-    let (where_clause, values) = build_where_clause(filter)?;
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, values) = build_where_clause(&conn, filter)?;
 
     let sql = format!(
         "
@@ -156,3 +403,246 @@ pub fn count_games(db_path: &str, filter: &GameFilter) -> Result<u64, QueryError
     let count: i64 = conn.query_row(&sql, params_from_iter(values.iter()), |row| row.get(0))?;
     u64::try_from(count).map_err(|_| QueryError::CountOverflow(count))
 }
+
+/// How many games `name` played as White and as Black, matched exactly but
+/// case-insensitively as with `GameFilter::player_exact`.
+pub fn player_color_split(db_path: &str, name: &str) -> Result<(u64, u64), QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (as_white, as_black) = conn.query_row(
+        "
+        SELECT
+            COALESCE(SUM(CASE WHEN LOWER(TRIM(white)) = LOWER(?1) THEN 1 ELSE 0 END), 0),
+            COALESCE(SUM(CASE WHEN LOWER(TRIM(black)) = LOWER(?1) THEN 1 ELSE 0 END), 0)
+        FROM games
+        ",
+        [name.trim()],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+    )?;
+
+    Ok((
+        u64::try_from(as_white).map_err(|_| QueryError::CountOverflow(as_white))?,
+        u64::try_from(as_black).map_err(|_| QueryError::CountOverflow(as_black))?,
+    ))
+}
+
+/// Buckets matching games by ply count into `[start, start + bucket_size)`
+/// ranges, returned as `(start, count)` in ascending order. Games without a
+/// known ply count are left out.
+pub fn length_histogram(
+    db_path: &str,
+    filter: &GameFilter,
+    bucket_size: u32,
+) -> Result<Vec<(u32, u64)>, QueryError> {
+    if bucket_size == 0 {
+        return Err(QueryError::InvalidBucketSize(bucket_size));
+    }
+
+    let conn = open_read_connection(db_path)?;
+    if !games_column_exists(&conn, "ply_count")? {
+        return Ok(Vec::new());
+    }
+    let (where_clause, mut values) = build_where_clause(&conn, filter)?;
+    let where_clause = with_extra_clause(&where_clause, "ply_count IS NOT NULL");
+
+    let sql = format!(
+        "
+        SELECT (ply_count / ?) * ? AS bucket, COUNT(*)
+        FROM games
+        {where_clause}
+        GROUP BY bucket
+        ORDER BY bucket ASC
+        "
+    );
+
+    let bucket_size = Value::Integer(i64::from(bucket_size));
+    values.insert(0, bucket_size.clone());
+    values.insert(0, bucket_size);
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut buckets = Vec::new();
+    for row in rows {
+        let (bucket, count) = row?;
+        let count = u64::try_from(count).map_err(|_| QueryError::CountOverflow(count))?;
+        buckets.push((u32::try_from(bucket).unwrap_or(u32::MAX), count));
+    }
+    Ok(buckets)
+}
+
+/// Counts matching games per full `YYYY.MM.DD` date, ascending. Partial or
+/// unknown dates such as `2024.??.??` are skipped.
+pub fn games_per_day(db_path: &str, filter: &GameFilter) -> Result<Vec<(String, u64)>, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, values) = build_where_clause(&conn, filter)?;
+    let where_clause = with_extra_clause(&where_clause, FULL_DATE_CLAUSE);
+
+    let sql = format!(
+        "
+        SELECT date, COUNT(*)
+        FROM games
+        {where_clause}
+        GROUP BY date
+        ORDER BY date ASC
+        "
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut days = Vec::new();
+    for row in rows {
+        let (date, count) = row?;
+        let count = u64::try_from(count).map_err(|_| QueryError::CountOverflow(count))?;
+        days.push((date, count));
+    }
+    Ok(days)
+}
+
+/// Game count, total plies and average game length over the whole database.
+/// Games without a known ply count are left out of the ply figures.
+pub fn database_stats(db_path: &str) -> Result<DatabaseStats, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let ply_count = optional_games_column(&conn, "ply_count")?;
+    let (total_games, total_plies, avg_plies) = conn.query_row(
+        &format!("SELECT COUNT(*), COALESCE(SUM({ply_count}), 0), AVG({ply_count}) FROM games"),
+        [],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<f64>>(2)?,
+            ))
+        },
+    )?;
+
+    Ok(DatabaseStats {
+        total_games: u64::try_from(total_games)
+            .map_err(|_| QueryError::CountOverflow(total_games))?,
+        total_plies: u64::try_from(total_plies)
+            .map_err(|_| QueryError::CountOverflow(total_plies))?,
+        avg_plies,
+    })
+}
+
+/// Replays every game with a declared `1-0`, `0-1` or `1/2-1/2` result and
+/// reports those whose final position is checkmate, stalemate or insufficient
+/// material with a different outcome. Games that do not replay are skipped.
+pub fn result_consistency_report(db_path: &str) -> Result<Vec<ResultMismatch>, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let setup_fen_column = optional_games_column(&conn, "setup_fen")?;
+    let variant_column = optional_games_column(&conn, "variant")?;
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT rowid, TRIM(result), pgn, {setup_fen_column}, {variant_column}
+        FROM games
+        WHERE TRIM(result) IN ('1-0', '0-1', '1/2-1/2')
+          AND COALESCE(TRIM(pgn), '') != ''
+        ORDER BY rowid
+        "
+    ))?;
+    let mut rows = stmt.query([])?;
+
+    let mut mismatches = Vec::new();
+    while let Some(row) = rows.next()? {
+        let declared_result: String = row.get(1)?;
+        let movetext: String = row.get(2)?;
+        let setup_fen: Option<String> = row.get(3)?;
+        let variant: Option<String> = row.get(4)?;
+        let Ok(position) = final_position(variant.as_deref(), setup_fen.as_deref(), &movetext)
+        else {
+            continue;
+        };
+
+        if let Some(expected) = position_result(&position)
+            && expected != declared_result
+        {
+            mismatches.push(ResultMismatch {
+                game_id: row.get(0)?,
+                declared_result,
+                expected_result: expected.to_owned(),
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Counts the distinct positions, by [`position_key`](crate::position_key),
+/// among the start positions and first `max_ply` plies of the games matching
+/// `filter`. Games flagged with a replay error are left out; an unflagged
+/// game that stops replaying contributes the positions before the bad move.
+pub fn unique_positions(
+    db_path: &str,
+    max_ply: usize,
+    filter: &GameFilter,
+) -> Result<u64, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let (where_clause, values) = build_where_clause(&conn, filter)?;
+    let setup_fen_column = optional_games_column(&conn, "setup_fen")?;
+    let variant_column = optional_games_column(&conn, "variant")?;
+    let where_clause = if games_column_exists(&conn, "replay_error")? {
+        with_extra_clause(&where_clause, "replay_error IS NULL")
+    } else {
+        where_clause
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT COALESCE(pgn, ''), {setup_fen_column}, {variant_column}
+        FROM games
+        {where_clause}
+        "
+    ))?;
+    let mut rows = stmt.query(params_from_iter(values.iter()))?;
+
+    let mut keys = HashSet::new();
+    while let Some(row) = rows.next()? {
+        let movetext: String = row.get(0)?;
+        let setup_fen: Option<String> = row.get(1)?;
+        let variant: Option<String> = row.get(2)?;
+        let setup_fen = setup_fen
+            .as_deref()
+            .map(str::trim)
+            .filter(|fen| !fen.is_empty());
+        let _ = for_each_position(
+            variant.as_deref(),
+            setup_fen,
+            &movetext,
+            max_ply,
+            |position| {
+                keys.insert(zobrist_key(position));
+            },
+        );
+    }
+    Ok(keys.len() as u64)
+}
+
+/// Distinct ECO codes present in the database with their game counts, ordered
+/// by code. Pair with [`crate::eco_name`] for display names.
+pub fn list_ecos(db_path: &str) -> Result<Vec<(String, u64)>, QueryError> {
+    let conn = open_read_connection(db_path)?;
+    let mut stmt = conn.prepare(
+        "
+        SELECT TRIM(eco) AS code, COUNT(*)
+        FROM games
+        WHERE COALESCE(TRIM(eco), '') <> ''
+        GROUP BY code
+        ORDER BY code ASC
+        ",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut ecos = Vec::new();
+    for row in rows {
+        let (code, count) = row?;
+        let count = u64::try_from(count).map_err(|_| QueryError::CountOverflow(count))?;
+        ecos.push((code, count));
+    }
+    Ok(ecos)
+}
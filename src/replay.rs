@@ -1,58 +1,512 @@
+use std::str::FromStr;
+
 use pgn_reader::SanPlus;
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OptionalExtension, Statement, params};
 use shakmaty::uci::UciMove;
-use shakmaty::{Chess, EnPassantMode, Position, fen::Fen};
+#[cfg(feature = "variant")]
+use shakmaty::variant::{Variant, VariantPosition};
+use shakmaty::{
+    CastlingMode, Chess, Color, EnPassantMode, File, Move, Position, Rank, Setup, Square, fen::Fen,
+};
 
-use crate::types::{ReplayError, ReplayTimeline};
+use crate::db::{open_read_connection, optional_games_column};
+use crate::types::{ReplayError, ReplayFrame, ReplayTimeline};
 
-pub fn replay_game(db_path: &str, game_id: i64) -> Result<ReplayTimeline, ReplayError> {
-    let conn = Connection::open(db_path)?;
-    let movetext: Option<String> = match conn.query_row(
-        "SELECT pgn FROM games WHERE rowid = ?1",
-        params![game_id],
-        |row| row.get(0),
-    ) {
-        Ok(value) => value,
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            return Err(ReplayError::GameNotFound(game_id));
-        }
-        Err(err) => return Err(ReplayError::Sql(err)),
+pub const REPLAY_PROGRESS_PLY_INTERVAL: usize = 50;
+/// Ply cap used by `replay_game`; well above any legal game, but it stops a
+/// corrupt row from allocating millions of FENs.
+pub const DEFAULT_MAX_REPLAY_PLIES: usize = 20_000;
+
+/// The position type replay runs on: any variant shakmaty knows with the
+/// `variant` feature, standard rules otherwise.
+#[cfg(feature = "variant")]
+pub(crate) type ReplayPosition = VariantPosition;
+#[cfg(not(feature = "variant"))]
+pub(crate) type ReplayPosition = Chess;
+
+#[cfg(feature = "variant")]
+type ReplayRules = Variant;
+/// Without the `variant` feature only standard rules replay.
+#[cfg(not(feature = "variant"))]
+#[derive(Debug, Clone, Copy)]
+enum ReplayRules {
+    Chess,
+}
+
+struct GameSource {
+    movetext: String,
+    setup_fen: Option<String>,
+    variant: Option<String>,
+}
+
+impl GameSource {
+    /// Prepares the query every replay entry point reads games with; the
+    /// setup FEN and variant read as `NULL` on databases from before those
+    /// columns.
+    fn prepare_query(conn: &Connection) -> Result<Statement<'_>, ReplayError> {
+        let setup_fen_column = optional_games_column(conn, "setup_fen")?;
+        let variant_column = optional_games_column(conn, "variant")?;
+        Ok(conn.prepare(&format!(
+            "SELECT pgn, {setup_fen_column}, {variant_column} FROM games WHERE rowid = ?1"
+        ))?)
+    }
+
+    /// Reads `game_id` with a statement from [`GameSource::prepare_query`],
+    /// or `None` when there is no such game. Missing movetext reads as empty
+    /// and a blank setup FEN as none.
+    fn read(stmt: &mut Statement<'_>, game_id: i64) -> Result<Option<Self>, ReplayError> {
+        let row = stmt
+            .query_row(params![game_id], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })
+            .optional()?;
+        Ok(row.map(|(movetext, setup_fen, variant)| Self {
+            movetext: movetext.unwrap_or_default(),
+            setup_fen: setup_fen
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty()),
+            variant,
+        }))
+    }
+
+    fn start_position(&self) -> Result<ReplayPosition, ReplayError> {
+        start_position_from_setup(self.variant.as_deref(), self.setup_fen.as_deref())
+    }
+}
+
+/// The start position of a game in `variant` (its PGN `Variant` tag), parsed
+/// from the setup FEN when there is one. Castling switches to Chess960 rules
+/// when the FEN's castling rights do not fit the standard king and rook
+/// squares.
+pub(crate) fn start_position_from_setup(
+    variant: Option<&str>,
+    setup_fen: Option<&str>,
+) -> Result<ReplayPosition, ReplayError> {
+    let rules = replay_rules(variant)?;
+    let Some(setup_fen) = setup_fen else {
+        return Ok(default_position(rules));
+    };
+
+    Fen::from_str(setup_fen)
+        .ok()
+        .and_then(|fen| {
+            let setup = fen.into_setup();
+            let mode = CastlingMode::detect(&setup);
+            position_from_setup(rules, setup, mode)
+        })
+        .ok_or_else(|| ReplayError::InvalidSetupFen(setup_fen.to_owned()))
+}
+
+/// Lowercases a `Variant` tag and drops spaces and dashes, so `King of the
+/// Hill` and `Three-check` compare as `kingofthehill` and `threecheck`.
+/// Returns `None` when the tag is absent or blank.
+fn normalized_variant(variant: Option<&str>) -> Option<(&str, String)> {
+    let variant = variant.map(str::trim).filter(|value| !value.is_empty())?;
+    Some((
+        variant,
+        variant.to_ascii_lowercase().replace([' ', '-'], ""),
+    ))
+}
+
+/// Whether a `Variant` tag, if any, names plain chess rather than a variant
+/// or a Chess960 / "From Position" game.
+pub(crate) fn is_plain_chess(variant: Option<&str>) -> bool {
+    normalized_variant(variant)
+        .is_none_or(|(_, normalized)| matches!(normalized.as_str(), "standard" | "chess"))
+}
+
+fn is_standard_variant(normalized: &str) -> bool {
+    matches!(
+        normalized,
+        "standard" | "chess" | "fromposition" | "chess960" | "fischerandom"
+    )
+}
+
+/// The rules named by a `Variant` tag, or `ReplayError::UnsupportedVariant`
+/// when replay cannot follow them. Chess960 and "From Position" games use
+/// standard rules from the `FEN` tag's position, with Chess960 castling
+/// detected from its castling rights.
+#[cfg(feature = "variant")]
+fn replay_rules(variant: Option<&str>) -> Result<ReplayRules, ReplayError> {
+    let Some((variant, normalized)) = normalized_variant(variant) else {
+        return Ok(Variant::Chess);
     };
+    Ok(match normalized.as_str() {
+        name if is_standard_variant(name) => Variant::Chess,
+        "atomic" => Variant::Atomic,
+        "antichess" => Variant::Antichess,
+        "kingofthehill" => Variant::KingOfTheHill,
+        "threecheck" | "3check" => Variant::ThreeCheck,
+        "crazyhouse" => Variant::Crazyhouse,
+        "racingkings" => Variant::RacingKings,
+        "horde" => Variant::Horde,
+        _ => return Err(ReplayError::UnsupportedVariant(variant.to_owned())),
+    })
+}
 
-    let movetext = movetext.ok_or(ReplayError::MissingMovetext(game_id))?;
-    if movetext.trim().is_empty() {
+#[cfg(not(feature = "variant"))]
+fn replay_rules(variant: Option<&str>) -> Result<ReplayRules, ReplayError> {
+    match normalized_variant(variant) {
+        Some((variant, normalized)) if !is_standard_variant(&normalized) => {
+            Err(ReplayError::UnsupportedVariant(variant.to_owned()))
+        }
+        _ => Ok(ReplayRules::Chess),
+    }
+}
+
+#[cfg(feature = "variant")]
+fn default_position(rules: ReplayRules) -> ReplayPosition {
+    VariantPosition::new(rules)
+}
+
+#[cfg(not(feature = "variant"))]
+fn default_position(_: ReplayRules) -> ReplayPosition {
+    Chess::default()
+}
+
+#[cfg(feature = "variant")]
+fn position_from_setup(
+    rules: ReplayRules,
+    setup: Setup,
+    mode: CastlingMode,
+) -> Option<ReplayPosition> {
+    VariantPosition::from_setup(rules, setup, mode).ok()
+}
+
+#[cfg(not(feature = "variant"))]
+fn position_from_setup(_: ReplayRules, setup: Setup, mode: CastlingMode) -> Option<ReplayPosition> {
+    use shakmaty::FromSetup;
+    Chess::from_setup(setup, mode).ok()
+}
+
+/// Replays stored movetext under the rules of `variant` from the optional
+/// setup FEN and returns the final position.
+pub(crate) fn final_position(
+    variant: Option<&str>,
+    setup_fen: Option<&str>,
+    movetext: &str,
+) -> Result<ReplayPosition, ReplayError> {
+    let mut position = start_position_from_setup(variant, setup_fen)?;
+    for (index, token) in movetext.split_whitespace().enumerate() {
+        play_san_token(&mut position, index + 1, token)?;
+    }
+    Ok(position)
+}
+
+/// Calls `on_position` with the start position and then each position
+/// reached by the first `max_plies` moves of stored movetext.
+pub(crate) fn for_each_position<F>(
+    variant: Option<&str>,
+    setup_fen: Option<&str>,
+    movetext: &str,
+    max_plies: usize,
+    mut on_position: F,
+) -> Result<(), ReplayError>
+where
+    F: FnMut(&ReplayPosition),
+{
+    let mut position = start_position_from_setup(variant, setup_fen)?;
+    on_position(&position);
+    for (index, token) in movetext.split_whitespace().take(max_plies).enumerate() {
+        play_san_token(&mut position, index + 1, token)?;
+        on_position(&position);
+    }
+    Ok(())
+}
+
+/// Loads a game's replay inputs, failing with `ReplayError::TooLong` when the
+/// movetext has more than `max_plies` tokens so that no entry point replays
+/// an oversized row.
+fn load_game_source(
+    db_path: &str,
+    game_id: i64,
+    max_plies: usize,
+) -> Result<GameSource, ReplayError> {
+    let conn = open_read_connection(db_path)?;
+    let mut stmt = GameSource::prepare_query(&conn)?;
+    let source = GameSource::read(&mut stmt, game_id)?.ok_or(ReplayError::GameNotFound(game_id))?;
+
+    replay_rules(source.variant.as_deref())?;
+    if source.movetext.trim().is_empty() {
         return Err(ReplayError::MissingMovetext(game_id));
     }
+    check_ply_cap(&source.movetext, max_plies)?;
+    Ok(source)
+}
+
+fn check_ply_cap(movetext: &str, max_plies: usize) -> Result<(), ReplayError> {
+    let plies = movetext.split_whitespace().count();
+    if plies > max_plies {
+        return Err(ReplayError::TooLong { plies });
+    }
+    Ok(())
+}
+
+fn san_token_move(position: &ReplayPosition, ply: usize, san: &str) -> Result<Move, ReplayError> {
+    let invalid_san = || ReplayError::InvalidSan {
+        ply,
+        san: san.to_owned(),
+        fen: Fen::from_position(position, EnPassantMode::Legal).to_string(),
+    };
+    let san_plus = SanPlus::from_ascii(san.as_bytes()).map_err(|_| invalid_san())?;
+    san_plus.san.to_move(position).map_err(|_| invalid_san())
+}
+
+fn play_san_token(
+    position: &mut ReplayPosition,
+    ply: usize,
+    san: &str,
+) -> Result<String, ReplayError> {
+    let mv = san_token_move(position, ply, san)?;
+    let uci = UciMove::from_move(mv, position.castles().mode()).to_string();
+    position.play_unchecked(mv);
+    Ok(uci)
+}
 
-    let mut position = Chess::default();
-    let mut fens = vec![Fen::from_position(&position, EnPassantMode::Legal).to_string()];
+fn replay_movetext<F>(
+    mut position: ReplayPosition,
+    movetext: &str,
+    mut on_move: F,
+) -> Result<(), ReplayError>
+where
+    F: FnMut(&ReplayPosition, String, String),
+{
+    for (index, token) in movetext.split_whitespace().enumerate() {
+        let uci = play_san_token(&mut position, index + 1, token)?;
+        on_move(&position, token.to_owned(), uci);
+    }
+
+    Ok(())
+}
+
+fn board_squares(position: &ReplayPosition) -> [[Option<char>; 8]; 8] {
+    let mut squares = [[None; 8]; 8];
+    for (row, rank) in Rank::ALL.into_iter().rev().enumerate() {
+        for (column, file) in File::ALL.into_iter().enumerate() {
+            squares[row][column] = position
+                .board()
+                .piece_at(Square::from_coords(file, rank))
+                .map(|piece| piece.char());
+        }
+    }
+    squares
+}
+
+pub fn replay_game(db_path: &str, game_id: i64) -> Result<ReplayTimeline, ReplayError> {
+    replay_game_with_progress(db_path, game_id, |_| {})
+}
+
+/// Like [`replay_game`], but calls `on_ply` with the number of plies replayed
+/// so far after every `REPLAY_PROGRESS_PLY_INTERVAL` plies.
+pub fn replay_game_with_progress<F>(
+    db_path: &str,
+    game_id: i64,
+    on_ply: F,
+) -> Result<ReplayTimeline, ReplayError>
+where
+    F: FnMut(usize),
+{
+    replay_timeline(db_path, game_id, DEFAULT_MAX_REPLAY_PLIES, on_ply)
+}
+
+/// Like [`replay_game`], but fails with `ReplayError::TooLong` when the game
+/// has more than `max_plies` plies instead of replaying it.
+pub fn replay_game_with_max_plies(
+    db_path: &str,
+    game_id: i64,
+    max_plies: usize,
+) -> Result<ReplayTimeline, ReplayError> {
+    replay_timeline(db_path, game_id, max_plies, |_| {})
+}
+
+fn replay_timeline<F>(
+    db_path: &str,
+    game_id: i64,
+    max_plies: usize,
+    mut on_ply: F,
+) -> Result<ReplayTimeline, ReplayError>
+where
+    F: FnMut(usize),
+{
+    let source = load_game_source(db_path, game_id, max_plies)?;
+    let start = source.start_position()?;
+
+    let start_fen = Fen::from_position(&start, EnPassantMode::Legal).to_string();
+    let start_is_standard =
+        start_fen == Fen::from_position(&Chess::default(), EnPassantMode::Legal).to_string();
+    let mut fens = vec![start_fen];
     let mut sans = Vec::new();
     let mut ucis = Vec::new();
 
-    for (index, token) in movetext.split_whitespace().enumerate() {
-        let san = token.to_owned();
-        let san_plus =
-            SanPlus::from_ascii(san.as_bytes()).map_err(|_| ReplayError::InvalidSan {
-                ply: index + 1,
-                san: san.clone(),
-            })?;
-        let mv = san_plus
-            .san
-            .to_move(&position)
-            .map_err(|_| ReplayError::InvalidSan {
-                ply: index + 1,
-                san: san.clone(),
-            })?;
-        let uci = UciMove::from_move(mv, position.castles().mode()).to_string();
-        position.play_unchecked(mv);
-        fens.push(Fen::from_position(&position, EnPassantMode::Legal).to_string());
+    replay_movetext(start, &source.movetext, |position, san, uci| {
+        fens.push(Fen::from_position(position, EnPassantMode::Legal).to_string());
         sans.push(san);
         ucis.push(uci);
+        if sans.len().is_multiple_of(REPLAY_PROGRESS_PLY_INTERVAL) {
+            on_ply(sans.len());
+        }
+    })?;
+
+    Ok(ReplayTimeline {
+        fens,
+        sans,
+        ucis,
+        start_is_standard,
+    })
+}
+
+/// Lazily replays a game one frame at a time. Frame 0 is the start position;
+/// after the first error the iterator yields `None`.
+pub struct ReplayIterator {
+    position: ReplayPosition,
+    tokens: std::vec::IntoIter<String>,
+    next_ply: usize,
+    finished: bool,
+}
+
+impl Iterator for ReplayIterator {
+    type Item = Result<ReplayFrame, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let ply = self.next_ply;
+        self.next_ply += 1;
+
+        if ply == 0 {
+            return Some(Ok(ReplayFrame {
+                ply,
+                fen: Fen::from_position(&self.position, EnPassantMode::Legal).to_string(),
+                san: None,
+                uci: None,
+            }));
+        }
+
+        let Some(san) = self.tokens.next() else {
+            self.finished = true;
+            return None;
+        };
+
+        match play_san_token(&mut self.position, ply, &san) {
+            Ok(uci) => Some(Ok(ReplayFrame {
+                ply,
+                fen: Fen::from_position(&self.position, EnPassantMode::Legal).to_string(),
+                san: Some(san),
+                uci: Some(uci),
+            })),
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
     }
+}
+
+pub fn replay_frames(db_path: &str, game_id: i64) -> Result<ReplayIterator, ReplayError> {
+    let source = load_game_source(db_path, game_id, DEFAULT_MAX_REPLAY_PLIES)?;
+    let position = source.start_position()?;
+    let tokens: Vec<String> = source
+        .movetext
+        .split_whitespace()
+        .map(ToOwned::to_owned)
+        .collect();
 
-    Ok(ReplayTimeline { fens, sans, ucis })
+    Ok(ReplayIterator {
+        position,
+        tokens: tokens.into_iter(),
+        next_ply: 0,
+        finished: false,
+    })
+}
+
+/// Re-derives every move's SAN from the position instead of trusting the
+/// stored tokens, so disambiguation and `+`/`#` suffixes are canonical.
+pub fn replay_sans_canonical(db_path: &str, game_id: i64) -> Result<Vec<String>, ReplayError> {
+    let source = load_game_source(db_path, game_id, DEFAULT_MAX_REPLAY_PLIES)?;
+    let mut position = source.start_position()?;
+
+    let mut sans = Vec::new();
+    for (index, token) in source.movetext.split_whitespace().enumerate() {
+        let mv = san_token_move(&position, index + 1, token)?;
+        sans.push(SanPlus::from_move_and_play_unchecked(&mut position, mv).to_string());
+    }
+    Ok(sans)
+}
+
+/// The first `plies` SAN moves of each game in `ids`, in the order given,
+/// read over one connection. Games that are missing or have no movetext are
+/// left out; a preview stops early at the first move that does not replay.
+pub fn replay_previews(
+    db_path: &str,
+    ids: &[i64],
+    plies: usize,
+) -> Result<Vec<(i64, Vec<String>)>, ReplayError> {
+    let conn = open_read_connection(db_path)?;
+    let mut stmt = GameSource::prepare_query(&conn)?;
+
+    let mut previews = Vec::with_capacity(ids.len());
+    for &game_id in ids {
+        let Some(source) = GameSource::read(&mut stmt, game_id)? else {
+            continue;
+        };
+        if source.movetext.trim().is_empty() {
+            continue;
+        }
+
+        let mut sans = Vec::new();
+        if let Ok(mut position) = source.start_position() {
+            for (index, token) in source.movetext.split_whitespace().take(plies).enumerate() {
+                if play_san_token(&mut position, index + 1, token).is_err() {
+                    break;
+                }
+                sans.push(token.to_owned());
+            }
+        }
+        previews.push((game_id, sans));
+    }
+    Ok(previews)
+}
+
+/// Whose move it is after `ply` half-moves, derived from the start position's
+/// side to move and the ply parity rather than by replaying the game.
+pub fn side_to_move_at_ply(db_path: &str, game_id: i64, ply: usize) -> Result<Color, ReplayError> {
+    let source = load_game_source(db_path, game_id, DEFAULT_MAX_REPLAY_PLIES)?;
+    let plies = source.movetext.split_whitespace().count();
+    if ply > plies {
+        return Err(ReplayError::PlyOutOfRange { ply, plies });
+    }
+
+    let start_turn = source.start_position()?.turn();
+    Ok(if ply.is_multiple_of(2) {
+        start_turn
+    } else {
+        !start_turn
+    })
 }
 
 pub fn replay_game_fens(db_path: &str, game_id: i64) -> Result<Vec<String>, ReplayError> {
     replay_game(db_path, game_id).map(|timeline| timeline.fens)
 }
+
+/// Returns one 8x8 grid per ply, indexed as `[row][column]` with row 0 being
+/// rank 8 and column 0 being the a-file. White pieces are uppercase.
+pub fn board_timeline(
+    db_path: &str,
+    game_id: i64,
+) -> Result<Vec<[[Option<char>; 8]; 8]>, ReplayError> {
+    let source = load_game_source(db_path, game_id, DEFAULT_MAX_REPLAY_PLIES)?;
+    let start = source.start_position()?;
+
+    let mut boards = vec![board_squares(&start)];
+    replay_movetext(start, &source.movetext, |position, _, _| {
+        boards.push(board_squares(position));
+    })?;
+
+    Ok(boards)
+}
@@ -1,8 +1,59 @@
+use std::time::Duration;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AppliedMove {
     pub san: String,
     pub uci: String,
     pub fen: String,
+    pub kind: MoveKind,
+}
+
+/// What a move does on the board, for choosing UI animations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    Normal,
+    Capture,
+    EnPassant,
+    CastleKingside,
+    CastleQueenside,
+    /// Promotion (capturing or not) to the given lowercase piece letter.
+    Promotion(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionInfo {
+    pub side_to_move: shakmaty::Color,
+    pub in_check: bool,
+    pub legal_move_count: usize,
+    pub fullmove: u32,
+    pub halfmove: u32,
+}
+
+/// One analysed move, with the engine evaluation of the position before and
+/// after it in centipawns from White's point of view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveEval {
+    pub ply: usize,
+    pub mover: shakmaty::Color,
+    pub san: String,
+    pub eval_before_cp: i32,
+    pub eval_after_cp: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayerAccuracy {
+    pub moves: usize,
+    /// Mean centipawn loss per move, or 0 when the player made no moves.
+    pub average_cp_loss: f64,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GameAccuracy {
+    pub white: PlayerAccuracy,
+    pub black: PlayerAccuracy,
 }
 
 #[derive(Debug)]
@@ -10,6 +61,12 @@ pub enum AnalysisError {
     InvalidFen(String),
     InvalidUci(String),
     IllegalMove(String),
+    InvalidSquare(String),
+    /// `index` is the zero-based position of the rejected move in the input.
+    InvalidSan {
+        index: usize,
+        san: String,
+    },
 }
 
 #[derive(Debug)]
@@ -23,7 +80,91 @@ pub struct ImportSummary {
     pub total: usize,
     pub inserted: usize,
     pub skipped: usize,
+    /// Games that could not be read at all and were not stored.
     pub errors: usize,
+    /// Games whose movetext parsed but failed a full replay. They are stored
+    /// flagged with `replay_error` and a NULL `ply_count` unless
+    /// `ImportOptions::skip_invalid_games`, and are counted here rather than
+    /// in `errors` so callers can tell stored games from dropped ones.
+    pub invalid: usize,
+    /// Games left out because the header predicate of
+    /// [`import_pgn_file_filtered`](crate::import_pgn_file_filtered) rejected them.
+    pub filtered: usize,
+}
+
+/// The tag pairs of a game being imported, as passed to the predicate of
+/// [`import_pgn_file_filtered`](crate::import_pgn_file_filtered).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportedHeaders {
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub date: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<String>,
+    pub eco: Option<String>,
+    /// Every tag in file order, including the ones above.
+    pub tags: Vec<(String, String)>,
+}
+
+impl ImportedHeaders {
+    /// The value of the first tag called `name`, matched case-sensitively.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// When the `Result` tag is missing or `*`, replay the movetext and record
+    /// the winner if the final position is checkmate.
+    pub infer_result_from_checkmate: bool,
+    /// When the `ECO` tag is missing, classify the opening from the movetext
+    /// using the bundled ECO table.
+    pub classify_missing_eco: bool,
+    /// Leave games that fail replay out of the database instead of storing
+    /// them flagged; they are still counted as `invalid`.
+    pub skip_invalid_games: bool,
+    /// Report progress every this many games; `0` disables count-based reports.
+    pub progress_games_interval: usize,
+    /// Report progress when this much time has passed since the last report.
+    pub progress_time_interval: Duration,
+    /// Commit every this many games instead of once at the end, so a crash
+    /// keeps the batches already committed. Duplicate cleanup still runs once
+    /// after the last game. `None` or `Some(0)` imports in one transaction.
+    pub commit_every: Option<usize>,
+    /// Translate SAN piece letters from this language to English before
+    /// parsing movetext. Figurine symbols are translated under every locale.
+    pub san_locale: Option<Locale>,
+}
+
+/// Languages whose SAN piece letters import can translate, e.g. German
+/// `Sf3` (Springer) to `Nf3`. `English` only translates figurines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Dutch,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            infer_result_from_checkmate: false,
+            classify_missing_eco: false,
+            skip_invalid_games: false,
+            progress_games_interval: 1_000,
+            progress_time_interval: Duration::from_millis(300),
+            commit_every: None,
+            san_locale: None,
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -33,18 +174,72 @@ pub enum GameResultFilter {
     WhiteWin,
     BlackWin,
     Draw,
+    Unknown,
+    Missing,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct GameFilter {
     pub search_text: Option<String>,
+    /// Case-insensitive substring match on the White player's name, or an
+    /// exact (still case-insensitive) match when `player_exact` is set.
+    pub white: Option<String>,
+    /// As `white`, for the Black player.
+    pub black: Option<String>,
+    pub player_exact: bool,
     pub result: GameResultFilter,
     pub eco: Option<String>,
+    /// Exact, case-insensitive ECO codes; a game matches any of them.
+    pub eco_in: Vec<String>,
+    /// Only games with no ECO code (NULL or blank), e.g. to find candidates
+    /// for `backfill_eco`.
+    pub eco_missing: bool,
+    /// Substring match against the `Opening` and `Variation` tags.
+    pub opening: Option<String>,
+    /// A SAN move such as `Qxf7` that must appear in the game's movetext.
+    /// Check and mate suffixes are ignored.
+    pub contains_move: Option<String>,
     pub event_or_site: Option<String>,
     pub date_from: Option<String>,
     pub date_to: Option<String>,
+    /// Let `date_from`/`date_to` match `YYYY.MM.??` and `YYYY.??.??` dates by
+    /// treating them as the whole month or year they stand for; such a game
+    /// matches only when that whole span lies inside the range.
+    pub include_partial_dates: bool,
+    pub tag_filters: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    #[default]
+    Date,
+    /// Natural (numeric-aware) order, so "Round 2" sorts before "Round 10".
+    Event,
+    /// The `Round` tag, in natural order.
+    Round,
+    White,
+    Black,
+    /// Ordered 1-0, 1/2-1/2, 0-1, then unfinished or missing results.
+    Result,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOptions {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            column: SortColumn::Date,
+            descending: true,
+        }
+    }
+}
+
+/// A window of search results. There is no "unlimited": a `limit` of 0 means
+/// `DEFAULT_LIMIT`, and larger values are capped at `MAX_LIMIT`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Pagination {
     pub limit: u32,
@@ -54,14 +249,15 @@ pub struct Pagination {
 impl Default for Pagination {
     fn default() -> Self {
         Self {
-            limit: 50,
+            limit: Self::DEFAULT_LIMIT,
             offset: 0,
         }
     }
 }
 
 impl Pagination {
-    const MAX_LIMIT: u32 = 500;
+    pub const DEFAULT_LIMIT: u32 = 50;
+    pub const MAX_LIMIT: u32 = 500;
 
     pub(crate) fn normalized(self) -> Self {
         let limit = if self.limit == 0 {
@@ -76,6 +272,87 @@ impl Pagination {
     }
 }
 
+/// One page of results plus what a UI needs to render pager controls.
+/// `limit` and `offset` are the values actually used, after normalization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u32,
+    pub has_prev: bool,
+    pub has_next: bool,
+}
+
+impl<T> Page<T> {
+    /// Zero-based index of this page.
+    pub fn page_index(&self) -> u32 {
+        self.offset / self.limit.max(1)
+    }
+}
+
+/// What [`check_schema`](crate::check_schema) found in a database file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaStatus {
+    pub has_games_table: bool,
+    /// Columns every chess-prep database has; without them queries fail.
+    pub missing_columns: Vec<String>,
+    /// Newer columns that the next import or `init_db` would add.
+    pub missing_migrated_columns: Vec<String>,
+}
+
+impl SchemaStatus {
+    /// Whether the file can be queried as a chess-prep database.
+    pub fn is_usable(&self) -> bool {
+        self.has_games_table && self.missing_columns.is_empty()
+    }
+}
+
+/// A game whose declared result contradicts its final position, e.g. tagged
+/// `1-0` but ending with White checkmated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultMismatch {
+    pub game_id: i64,
+    pub declared_result: String,
+    /// The only result the final position allows.
+    pub expected_result: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnGameStatus {
+    Valid,
+    /// The game could not be parsed as PGN.
+    ParseError(String),
+    /// The game parsed but its moves do not replay legally.
+    InvalidMoves(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGameReport {
+    /// 1-based line of the input on which the game starts.
+    pub line: usize,
+    pub status: PgnGameStatus,
+}
+
+/// Result of [`validate_pgn`](crate::validate_pgn), one entry per game found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnReport {
+    pub games: Vec<PgnGameReport>,
+    pub valid: usize,
+    pub parse_errors: usize,
+    pub invalid: usize,
+}
+
+/// Aggregate figures for a database dashboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseStats {
+    pub total_games: u64,
+    /// Sum of `ply_count` over the games that have one.
+    pub total_plies: u64,
+    /// Mean `ply_count`, or `None` when no game has a ply count.
+    pub avg_plies: Option<f64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GameRow {
     pub id: i64,
@@ -88,11 +365,59 @@ pub struct GameRow {
     pub eco: Option<String>,
 }
 
+/// Boundary for [`games_since`](crate::games_since).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamesSince {
+    /// Games whose rowid is greater than this one.
+    AfterRowid(i64),
+    /// Games whose `imported_at` (Unix milliseconds) is later than this.
+    /// Rows imported before the column existed have none and never match.
+    ImportedAfter(i64),
+}
+
+/// One game as exchanged by the NDJSON export/import, independent of PGN.
+/// `pgn` holds the stored SAN movetext without move numbers.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct NewGame {
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub date: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<String>,
+    pub eco: Option<String>,
+    pub pgn: Option<String>,
+    pub setup_fen: Option<String>,
+    pub opening: Option<String>,
+    pub variation: Option<String>,
+    pub tags: Vec<(String, String)>,
+}
+
+/// Why a date filter was rejected; positions are byte offsets into the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFormatIssue {
+    Length { actual: usize },
+    Separator { position: usize },
+    NonDigit { position: usize },
+}
+
 #[derive(Debug)]
 pub enum QueryError {
     Sql(rusqlite::Error),
-    InvalidDateFormat { field: &'static str, value: String },
+    InvalidDateFormat {
+        field: &'static str,
+        value: String,
+        reason: DateFormatIssue,
+    },
     CountOverflow(i64),
+    InvalidBucketSize(u32),
+    InvalidSan(String),
+    GameNotFound(i64),
+    Io(std::io::Error),
+    /// Reading an analysis workspace failed while composing an export.
+    Workspace(AnalysisWorkspaceError),
 }
 
 #[derive(Debug)]
@@ -100,7 +425,26 @@ pub enum ReplayError {
     Sql(rusqlite::Error),
     GameNotFound(i64),
     MissingMovetext(i64),
-    InvalidSan { ply: usize, san: String },
+    /// Movetext that `pgn-reader` could not parse as a game.
+    InvalidMovetext(String),
+    InvalidSetupFen(String),
+    InvalidSan {
+        ply: usize,
+        san: String,
+        fen: String,
+    },
+    PlyOutOfRange {
+        ply: usize,
+        plies: usize,
+    },
+    /// The stored movetext has more plies than the replay cap allows.
+    TooLong {
+        plies: usize,
+    },
+    /// The game's `Variant` tag names rules replay cannot follow: anything
+    /// but standard chess without the `variant` feature, and variants
+    /// shakmaty does not implement with it.
+    UnsupportedVariant(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -108,6 +452,17 @@ pub struct ReplayTimeline {
     pub fens: Vec<String>,
     pub sans: Vec<String>,
     pub ucis: Vec<String>,
+    /// Whether ply 0 is the standard starting position rather than a custom
+    /// SetUp FEN.
+    pub start_is_standard: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayFrame {
+    pub ply: usize,
+    pub fen: String,
+    pub san: Option<String>,
+    pub uci: Option<String>,
 }
 
 #[derive(Debug)]
@@ -117,6 +472,66 @@ pub enum EngineError {
     Protocol(String),
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EngineVariant {
+    #[default]
+    Standard,
+    Chess960,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineSessionOptions {
+    pub variant: EngineVariant,
+    /// Collect the engine's stderr and append it to protocol error messages.
+    pub capture_stderr: bool,
+    /// How long the handshake and every `isready`, including
+    /// [`EngineSession::ping`](crate::EngineSession::ping), wait for `uciok`
+    /// or `readyok` before reporting the engine as unresponsive.
+    pub ready_timeout: Duration,
+}
+
+impl Default for EngineSessionOptions {
+    fn default() -> Self {
+        Self {
+            variant: EngineVariant::default(),
+            capture_stderr: false,
+            ready_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Which way a line of UCI traffic travelled, as reported to an
+/// [`EngineSession`](crate::EngineSession) I/O hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// A command written to the engine's stdin.
+    Sent,
+    /// A line read from the engine's stdout.
+    Received,
+}
+
+impl EngineVariant {
+    pub(crate) fn castling_mode(self) -> shakmaty::CastlingMode {
+        match self {
+            Self::Standard => shakmaty::CastlingMode::Standard,
+            Self::Chess960 => shakmaty::CastlingMode::Chess960,
+        }
+    }
+}
+
+/// One `option name ... type ...` line an engine advertised during the UCI
+/// handshake. `min`/`max` are only set for spin options and `vars` only for
+/// combo options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+    pub option_type: String,
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub vars: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EngineLine {
     pub multipv_rank: u32,
@@ -125,6 +540,10 @@ pub struct EngineLine {
     pub score_mate: Option<i32>,
     pub pv: Vec<String>,
     pub san_pv: Vec<String>,
+    /// How many centipawns worse this line scores than the rank-1 line, never
+    /// negative; `None` when either line has no score. Mates count as
+    /// [`MATE_SCORE_CP`](crate::MATE_SCORE_CP) less the moves to mate.
+    pub cp_loss_vs_best: Option<i32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -132,9 +551,54 @@ pub struct EngineAnalysis {
     pub depth: u32,
     pub score_cp: Option<i32>,
     pub score_mate: Option<i32>,
-    pub bestmove: Option<String>,
+    pub bestmove_uci: Option<String>,
+    pub bestmove_san: Option<String>,
     pub pv: Vec<String>,
+    pub san_pv: Vec<String>,
     pub lines: Vec<EngineLine>,
+    /// The only legal move in the analysed position, if it is forced.
+    pub only_move: Option<String>,
+    /// The MultiPV the search ran with, after clamping to the engine's
+    /// advertised maximum.
+    pub requested_lines: u32,
+    /// Legal moves in the analysed position, so the most lines any search can
+    /// return; `None` when the FEN does not parse. `lines.len()` below both
+    /// limits means the engine itself fell short.
+    pub available_lines: Option<u32>,
+    /// The reply the engine expects, from `bestmove ... ponder <move>`.
+    pub ponder: Option<String>,
+}
+
+/// An engine score from one side's point of view: centipawns, or moves to
+/// mate with positive meaning that side mates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Cp(i32),
+    Mate(i32),
+}
+
+impl EngineAnalysis {
+    /// The score from White's point of view, given the side to move in the
+    /// analysed position; `score_cp`/`score_mate` stay side-to-move relative.
+    /// A mate takes precedence over a centipawn score, and an analysis without
+    /// any score reads as `Cp(0)`.
+    pub fn white_score(&self, side_to_move: shakmaty::Color) -> Score {
+        let sign = match side_to_move {
+            shakmaty::Color::White => 1,
+            shakmaty::Color::Black => -1,
+        };
+        match (self.score_mate, self.score_cp) {
+            (Some(mate), _) => Score::Mate(sign * mate),
+            (None, Some(cp)) => Score::Cp(sign * cp),
+            (None, None) => Score::Cp(0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EvalCacheError {
+    Sql(rusqlite::Error),
+    Engine(EngineError),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -147,6 +611,8 @@ pub struct AnalysisWorkspaceNode {
     pub comment: String,
     pub nags: Vec<String>,
     pub sort_index: i32,
+    /// Opaque frontend data (e.g. JSON arrows/highlights), stored verbatim.
+    pub metadata: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -167,12 +633,51 @@ pub struct LoadedAnalysisWorkspace {
     pub nodes: Vec<AnalysisWorkspaceNode>,
 }
 
+impl LoadedAnalysisWorkspace {
+    /// Direct children of `node_id`, ordered by `sort_index`.
+    pub fn children_of(&self, node_id: &str) -> Vec<&AnalysisWorkspaceNode> {
+        let mut children: Vec<&AnalysisWorkspaceNode> = self
+            .nodes
+            .iter()
+            .filter(|node| node.parent_id.as_deref() == Some(node_id))
+            .collect();
+        children.sort_by_key(|node| node.sort_index);
+        children
+    }
+
+    /// The root node followed by the first child of each node in turn.
+    pub fn mainline(&self) -> Vec<&AnalysisWorkspaceNode> {
+        let Some(mut current) = self
+            .nodes
+            .iter()
+            .find(|node| node.id == self.workspace.root_node_id)
+        else {
+            return Vec::new();
+        };
+
+        let mut line = vec![current];
+        while let Some(next) = self.children_of(&current.id).into_iter().next() {
+            if line.len() > self.nodes.len() {
+                break;
+            }
+            line.push(next);
+            current = next;
+        }
+        line
+    }
+}
+
 #[derive(Debug)]
 pub enum AnalysisWorkspaceError {
     Sql(rusqlite::Error),
     Io(std::io::Error),
     NotFound(i64),
     InvalidInput(String),
+    /// The workspace row exists but has no nodes; saving always stores at
+    /// least the root, so the database has been damaged.
+    Corrupt {
+        workspace_id: i64,
+    },
 }
 
 impl From<std::io::Error> for ImportError {
@@ -193,6 +698,18 @@ impl From<rusqlite::Error> for ImportError {
     }
 }
 
+impl From<std::io::Error> for QueryError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<AnalysisWorkspaceError> for QueryError {
+    fn from(value: AnalysisWorkspaceError) -> Self {
+        Self::Workspace(value)
+    }
+}
+
 impl From<rusqlite::Error> for QueryError {
     fn from(value: rusqlite::Error) -> Self {
         Self::Sql(value)
@@ -216,3 +733,15 @@ impl From<std::io::Error> for AnalysisWorkspaceError {
         Self::Io(value)
     }
 }
+
+impl From<rusqlite::Error> for EvalCacheError {
+    fn from(value: rusqlite::Error) -> Self {
+        Self::Sql(value)
+    }
+}
+
+impl From<EngineError> for EvalCacheError {
+    fn from(value: EngineError) -> Self {
+        Self::Engine(value)
+    }
+}
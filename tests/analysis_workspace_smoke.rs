@@ -1,7 +1,9 @@
 use chess_prep::{
-    AnalysisWorkspaceError, AnalysisWorkspaceNode, init_analysis_workspace_db,
-    list_analysis_workspaces, load_analysis_workspace, save_analysis_workspace,
+    AnalysisWorkspaceError, AnalysisWorkspaceNode, init_analysis_workspace_db, init_db,
+    list_analysis_workspaces, list_dangling_workspaces, load_analysis_workspace,
+    repair_analysis_db, save_analysis_workspace, save_analysis_workspace_verified,
 };
+use rusqlite::Connection;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -39,6 +41,7 @@ fn analysis_workspace_roundtrip_save_list_load() {
             comment: "".to_string(),
             nags: vec![],
             sort_index: 0,
+            metadata: None,
         },
         AnalysisWorkspaceNode {
             id: "n1".to_string(),
@@ -49,6 +52,7 @@ fn analysis_workspace_roundtrip_save_list_load() {
             comment: "prep note".to_string(),
             nags: vec!["!".to_string()],
             sort_index: 0,
+            metadata: None,
         },
     ];
 
@@ -78,6 +82,130 @@ fn analysis_workspace_roundtrip_save_list_load() {
     fs::remove_file(db_path).expect("cleanup should work");
 }
 
+#[test]
+fn load_reports_corrupt_workspace_without_nodes() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("path should be valid utf-8");
+
+    let nodes = vec![AnalysisWorkspaceNode {
+        id: "root".to_string(),
+        parent_id: None,
+        san: None,
+        uci: None,
+        fen: "startfen".to_string(),
+        comment: "".to_string(),
+        nags: vec![],
+        sort_index: 0,
+        metadata: None,
+    }];
+    let workspace_id = save_analysis_workspace(
+        db_path_str,
+        "/tmp/source.sqlite",
+        11,
+        "Damaged",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect("save should succeed");
+
+    let conn = Connection::open(&db_path).expect("analysis db should open");
+    conn.execute(
+        "DELETE FROM analysis_nodes WHERE workspace_id = ?1",
+        [workspace_id],
+    )
+    .expect("node delete should succeed");
+    drop(conn);
+
+    let err = load_analysis_workspace(db_path_str, workspace_id)
+        .expect_err("load should fail for a workspace without nodes");
+    assert!(matches!(
+        err,
+        AnalysisWorkspaceError::Corrupt { workspace_id: id } if id == workspace_id
+    ));
+
+    let missing = load_analysis_workspace(db_path_str, workspace_id + 1)
+        .expect_err("load should fail for an unknown workspace");
+    assert!(matches!(missing, AnalysisWorkspaceError::NotFound(_)));
+
+    fs::remove_file(db_path).expect("cleanup should work");
+}
+
+#[test]
+fn repair_removes_orphaned_and_detached_nodes() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("path should be valid utf-8");
+
+    let nodes = vec![
+        AnalysisWorkspaceNode {
+            id: "root".to_string(),
+            parent_id: None,
+            san: None,
+            uci: None,
+            fen: "startfen".to_string(),
+            comment: "".to_string(),
+            nags: vec![],
+            sort_index: 0,
+            metadata: None,
+        },
+        AnalysisWorkspaceNode {
+            id: "n1".to_string(),
+            parent_id: Some("root".to_string()),
+            san: Some("e4".to_string()),
+            uci: Some("e2e4".to_string()),
+            fen: "fen_after_e4".to_string(),
+            comment: "".to_string(),
+            nags: vec![],
+            sort_index: 0,
+            metadata: None,
+        },
+    ];
+    let workspace_id = save_analysis_workspace(
+        db_path_str,
+        "/tmp/source.sqlite",
+        12,
+        "Intact",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect("save should succeed");
+
+    let conn = Connection::open(&db_path).expect("analysis db should open");
+    conn.execute_batch("PRAGMA foreign_keys = OFF;")
+        .expect("should disable foreign keys");
+    conn.execute(
+        "INSERT INTO analysis_nodes (workspace_id, node_id, parent_node_id, fen) VALUES (?1, 'orphan', NULL, 'x')",
+        [workspace_id + 100],
+    )
+    .expect("orphan insert should succeed");
+    conn.execute(
+        "INSERT INTO analysis_nodes (workspace_id, node_id, parent_node_id, fen) VALUES (?1, 'lost', 'gone', 'x')",
+        [workspace_id],
+    )
+    .expect("detached insert should succeed");
+    conn.execute(
+        "INSERT INTO analysis_nodes (workspace_id, node_id, parent_node_id, fen) VALUES (?1, 'lost_child', 'lost', 'x')",
+        [workspace_id],
+    )
+    .expect("detached child insert should succeed");
+    drop(conn);
+
+    assert_eq!(
+        repair_analysis_db(db_path_str).expect("repair should work"),
+        3
+    );
+    assert_eq!(
+        repair_analysis_db(db_path_str).expect("repair should work"),
+        0
+    );
+
+    let loaded = load_analysis_workspace(db_path_str, workspace_id).expect("load should work");
+    assert_eq!(loaded.nodes.len(), 2);
+
+    fs::remove_file(db_path).expect("cleanup should work");
+}
+
 #[test]
 fn analysis_workspace_rejects_empty_nodes() {
     let db_path = unique_temp_db_path();
@@ -96,3 +224,164 @@ fn analysis_workspace_rejects_empty_nodes() {
 
     assert!(matches!(err, AnalysisWorkspaceError::InvalidInput(_)));
 }
+
+#[test]
+fn verified_save_checks_game_exists_in_source_db() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("path should be valid utf-8");
+    let source_path = unique_temp_db_path();
+    let source_path_str = source_path.to_str().expect("path should be valid utf-8");
+
+    init_db(source_path_str).expect("source db init should succeed");
+    let conn = Connection::open(source_path_str).expect("should open source db");
+    conn.execute(
+        "INSERT INTO games (white, black, result) VALUES ('Alice', 'Bob', '1-0')",
+        [],
+    )
+    .expect("should insert source game");
+    let game_id = conn.last_insert_rowid();
+    drop(conn);
+
+    let nodes = vec![AnalysisWorkspaceNode {
+        id: "root".to_string(),
+        parent_id: None,
+        san: None,
+        uci: None,
+        fen: "startfen".to_string(),
+        comment: String::new(),
+        nags: vec![],
+        sort_index: 0,
+        metadata: None,
+    }];
+
+    let workspace_id = save_analysis_workspace_verified(
+        db_path_str,
+        source_path_str,
+        game_id,
+        "Verified",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect("save should succeed for an existing game");
+    let loaded = load_analysis_workspace(db_path_str, workspace_id).expect("load should work");
+    assert_eq!(loaded.workspace.game_id, game_id);
+
+    let err = save_analysis_workspace_verified(
+        db_path_str,
+        source_path_str,
+        game_id + 100,
+        "Missing Game",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect_err("save should fail for a missing game");
+    assert!(
+        matches!(err, AnalysisWorkspaceError::InvalidInput(message) if message.contains("not found"))
+    );
+
+    let missing_source = unique_temp_db_path();
+    let err = save_analysis_workspace_verified(
+        db_path_str,
+        missing_source.to_str().expect("path should be valid utf-8"),
+        game_id,
+        "Missing Source",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect_err("save should fail for a missing source db");
+    assert!(matches!(err, AnalysisWorkspaceError::InvalidInput(_)));
+    assert!(
+        !missing_source.exists(),
+        "verification must not create the source db"
+    );
+
+    let list =
+        list_analysis_workspaces(db_path_str, source_path_str, game_id).expect("list should work");
+    assert_eq!(list.len(), 1);
+
+    fs::remove_file(db_path).expect("cleanup should work");
+    fs::remove_file(source_path).expect("cleanup should work");
+}
+
+#[test]
+fn dangling_workspaces_are_those_whose_game_was_deleted() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("path should be valid utf-8");
+    let source_path = unique_temp_db_path();
+    let source_path_str = source_path.to_str().expect("path should be valid utf-8");
+
+    init_db(source_path_str).expect("source db init should succeed");
+    let conn = Connection::open(source_path_str).expect("should open source db");
+    let mut game_ids = Vec::new();
+    for white in ["Alice", "Carol"] {
+        conn.execute(
+            "INSERT INTO games (white, black, result) VALUES (?1, 'Bob', '1-0')",
+            [white],
+        )
+        .expect("should insert source game");
+        game_ids.push(conn.last_insert_rowid());
+    }
+    drop(conn);
+
+    let nodes = vec![AnalysisWorkspaceNode {
+        id: "root".to_string(),
+        parent_id: None,
+        san: None,
+        uci: None,
+        fen: "startfen".to_string(),
+        comment: String::new(),
+        nags: vec![],
+        sort_index: 0,
+        metadata: None,
+    }];
+    save_analysis_workspace(
+        db_path_str,
+        source_path_str,
+        game_ids[0],
+        "Kept",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect("save should succeed");
+    let dangling = save_analysis_workspace(
+        db_path_str,
+        source_path_str,
+        game_ids[1],
+        "Dangling",
+        "root",
+        None,
+        &nodes,
+    )
+    .expect("save should succeed");
+
+    let list = list_dangling_workspaces(db_path_str, source_path_str).expect("list should work");
+    assert!(list.is_empty());
+
+    let conn = Connection::open(source_path_str).expect("should open source db");
+    conn.execute("DELETE FROM games WHERE rowid = ?1", [game_ids[1]])
+        .expect("should delete source game");
+    drop(conn);
+
+    let list = list_dangling_workspaces(db_path_str, source_path_str).expect("list should work");
+    let ids: Vec<i64> = list.iter().map(|workspace| workspace.id).collect();
+    assert_eq!(ids, vec![dangling]);
+
+    let missing_source = unique_temp_db_path();
+    let err = list_dangling_workspaces(
+        db_path_str,
+        missing_source.to_str().expect("path should be valid utf-8"),
+    )
+    .expect_err("listing should fail for a missing source db");
+    assert!(matches!(err, AnalysisWorkspaceError::InvalidInput(_)));
+    assert!(
+        !missing_source.exists(),
+        "listing must not create the source db"
+    );
+
+    fs::remove_file(db_path).expect("cleanup should work");
+    fs::remove_file(source_path).expect("cleanup should work");
+}
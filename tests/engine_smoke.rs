@@ -0,0 +1,547 @@
+#![cfg(unix)]
+
+use chess_prep::{Direction, EngineError, EngineSession, EngineSessionOptions, analyze_positions};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unique_temp_path(stem: &str, ext: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after UNIX_EPOCH")
+        .as_nanos();
+    let pid = std::process::id();
+    let counter = UNIQUE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    std::env::temp_dir().join(format!("{stem}_{pid}_{nanos}_{counter}.{ext}"))
+}
+
+fn write_fake_engine(script: &str) -> PathBuf {
+    let path = unique_temp_path("chess_prep_fake_engine", "sh");
+    fs::write(&path, script).expect("should write fake engine");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+        .expect("should mark fake engine executable");
+    path
+}
+
+#[test]
+fn captured_stderr_is_included_in_handshake_errors() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+read -r line
+echo "ERROR: NNUE file nn-test.nnue was not found" >&2
+exit 1
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let result = EngineSession::start_with_options(
+        engine_path_str,
+        EngineSessionOptions {
+            capture_stderr: true,
+            ..EngineSessionOptions::default()
+        },
+    );
+
+    match result {
+        Err(EngineError::Protocol(message)) => {
+            assert!(message.contains("uciok"), "{message}");
+            assert!(
+                message.contains("NNUE file nn-test.nnue was not found"),
+                "{message}"
+            );
+        }
+        Err(other) => panic!("unexpected error: {other:?}"),
+        Ok(_) => panic!("handshake should fail"),
+    }
+
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn analyze_positions_reuses_one_session_for_a_batch() {
+    let spawn_log = unique_temp_path("chess_prep_fake_engine_spawns", "log");
+    let engine_path = write_fake_engine(&format!(
+        r#"#!/bin/sh
+echo spawned >> "{}"
+side=w
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        isready) echo "readyok" ;;
+        "position fen "*)
+            set -- $line
+            side="$4"
+            ;;
+        "go depth "*)
+            if [ "$side" = "b" ]; then
+                echo "info depth 8 score cp -20 pv e7e5"
+                echo "bestmove e7e5"
+            else
+                echo "info depth 8 score cp 25 pv e2e4"
+                echo "bestmove e2e4"
+            fi
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+        spawn_log.display()
+    ));
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+    ];
+    let results = analyze_positions(engine_path_str, &fens, 8, 1);
+
+    assert_eq!(results.len(), 2);
+    let first = results[0].as_ref().expect("first position should analyze");
+    let second = results[1].as_ref().expect("second position should analyze");
+    assert_eq!(first.bestmove_san.as_deref(), Some("e4"));
+    assert_eq!(second.bestmove_san.as_deref(), Some("e5"));
+
+    let spawns = fs::read_to_string(&spawn_log).expect("should read spawn log");
+    assert_eq!(spawns.lines().count(), 1, "engine should be spawned once");
+
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+    fs::remove_file(spawn_log).expect("should clean up spawn log");
+}
+
+#[test]
+fn handshake_ignores_chatty_info_lines_before_readyok() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "id name ChattyEngine"; echo "uciok" ;;
+        isready)
+            i=0
+            while [ "$i" -lt 25000 ]; do
+                echo "info string loading network shard $i"
+                i=$((i + 1))
+            done
+            echo "readyok"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    EngineSession::start(engine_path_str)
+        .expect("info lines should not exhaust the handshake budget");
+
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn handshake_gives_up_on_an_engine_that_only_prints_info_lines() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+read -r line
+while :; do
+    echo "info string still loading"
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let started = Instant::now();
+    let result = EngineSession::start_with_options(
+        engine_path_str,
+        EngineSessionOptions {
+            ready_timeout: Duration::from_millis(300),
+            ..EngineSessionOptions::default()
+        },
+    );
+    match result {
+        Err(EngineError::Protocol(message)) => assert!(message.contains("uciok"), "{message}"),
+        Err(other) => panic!("unexpected error: {other:?}"),
+        Ok(_) => panic!("an engine that never sends uciok should not start"),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn spawn_errors_distinguish_missing_and_non_executable_engines() {
+    let missing = unique_temp_path("chess_prep_missing_engine", "sh");
+    match EngineSession::start(missing.to_str().expect("path should be valid UTF-8")) {
+        Err(EngineError::Spawn(message)) => assert!(message.contains("was not found"), "{message}"),
+        Err(other) => panic!("unexpected error: {other:?}"),
+        Ok(_) => panic!("missing engine should not start"),
+    }
+
+    let engine_path = unique_temp_path("chess_prep_non_executable_engine", "sh");
+    fs::write(&engine_path, "#!/bin/sh\n").expect("should write engine file");
+    fs::set_permissions(&engine_path, fs::Permissions::from_mode(0o644))
+        .expect("should clear execute bit");
+    match EngineSession::start(engine_path.to_str().expect("path should be valid UTF-8")) {
+        Err(EngineError::Spawn(message)) => {
+            assert!(message.contains("is not executable"), "{message}")
+        }
+        Err(other) => panic!("unexpected error: {other:?}"),
+        Ok(_) => panic!("non-executable engine should not start"),
+    }
+
+    fs::remove_file(engine_path).expect("should clean up engine file");
+}
+
+#[test]
+fn multipv_is_limited_by_the_engine_advertised_maximum() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+lines=1
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "option name MultiPV type spin default 1 min 1 max 20"
+            echo "uciok"
+            ;;
+        "setoption name MultiPV value "*) lines="${line##* }" ;;
+        isready) echo "readyok" ;;
+        "go depth "*)
+            i=1
+            while [ "$i" -le "$lines" ]; do
+                echo "info depth 10 multipv $i score cp $((50 - i)) pv e2e4"
+                i=$((i + 1))
+            done
+            echo "bestmove e2e4"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let mut session = EngineSession::start(engine_path_str).expect("engine should start");
+    assert_eq!(session.max_multipv(), 20);
+
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let analysis = session
+        .analyze_multipv(start, 10, 12)
+        .expect("analysis should work");
+    assert_eq!(analysis.lines.len(), 12);
+    assert_eq!(
+        analysis.lines.last().map(|line| line.multipv_rank),
+        Some(12)
+    );
+
+    let clamped = session
+        .analyze_multipv(start, 10, 40)
+        .expect("analysis should work");
+    assert_eq!(clamped.lines.len(), 20);
+
+    drop(session);
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn handshake_collects_advertised_spin_and_combo_options() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Fake"
+            echo "option name Hash type spin default 16 min 1 max 33554432"
+            echo "option name Analysis Contempt type combo default Both var Off var White var Black var Both"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let session = EngineSession::start(engine_path_str).expect("engine should start");
+    let options = session.options();
+    assert_eq!(options.len(), 2);
+
+    assert_eq!(options[0].name, "Hash");
+    assert_eq!(options[0].option_type, "spin");
+    assert_eq!(options[0].default.as_deref(), Some("16"));
+    assert_eq!(options[0].min, Some(1));
+    assert_eq!(options[0].max, Some(33_554_432));
+    assert!(options[0].vars.is_empty());
+
+    assert_eq!(options[1].name, "Analysis Contempt");
+    assert_eq!(options[1].option_type, "combo");
+    assert_eq!(options[1].default.as_deref(), Some("Both"));
+    assert_eq!(options[1].min, None);
+    assert_eq!(options[1].vars, vec!["Off", "White", "Black", "Both"]);
+    assert_eq!(session.max_multipv(), chess_prep::DEFAULT_MAX_MULTIPV);
+
+    drop(session);
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn multipv_reports_available_lines_when_few_moves_are_legal() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "option name MultiPV type spin default 1 min 1 max 20"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        "go depth "*)
+            echo "info depth 8 multipv 1 score cp 0 pv h8g8"
+            echo "info depth 8 multipv 2 score cp 0 pv h8g7"
+            echo "info depth 8 multipv 3 score cp 0 pv h8h7"
+            echo "bestmove h8g8"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let mut session = EngineSession::start(engine_path_str).expect("engine should start");
+    let bare_kings = "7k/8/8/8/8/8/8/K7 b - - 0 1";
+    let analysis = session
+        .analyze_multipv(bare_kings, 8, 5)
+        .expect("analysis should work");
+    assert_eq!(analysis.lines.len(), 3);
+    assert_eq!(analysis.requested_lines, 5);
+    assert_eq!(analysis.available_lines, Some(3));
+
+    drop(session);
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn ping_succeeds_while_alive_and_fails_once_the_engine_exits() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+pings=0
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        isready)
+            pings=$((pings + 1))
+            if [ "$pings" -gt 2 ]; then
+                exit 0
+            fi
+            echo "readyok"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let mut session = EngineSession::start(engine_path_str).expect("engine should start");
+    session.ping().expect("live engine should answer ping");
+    assert!(session.ping().is_err(), "exited engine should fail ping");
+
+    drop(session);
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn ping_times_out_when_the_engine_never_answers() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+pings=0
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        isready)
+            pings=$((pings + 1))
+            if [ "$pings" -eq 1 ]; then
+                echo "readyok"
+            fi
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let mut session = EngineSession::start_with_options(
+        engine_path_str,
+        EngineSessionOptions {
+            ready_timeout: Duration::from_millis(200),
+            ..EngineSessionOptions::default()
+        },
+    )
+    .expect("engine should start");
+    let started = Instant::now();
+    match session.ping() {
+        Err(EngineError::Protocol(message)) => assert!(message.contains("readyok"), "{message}"),
+        other => panic!("unresponsive engine should fail ping: {other:?}"),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(
+        session.ping().is_err(),
+        "a timed-out session should stay failed"
+    );
+    assert!(
+        session
+            .analyze(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                1
+            )
+            .is_err(),
+        "a timed-out session should not analyze"
+    );
+
+    drop(session);
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn shutdown_reports_the_engine_exit_status() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        isready) echo "readyok" ;;
+        quit) exit 0 ;;
+    esac
+done
+exit 3
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let session = EngineSession::start(engine_path_str).expect("engine should start");
+    let status = session.shutdown().expect("shutdown should work");
+    assert!(status.success(), "{status:?}");
+
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn analyze_until_stable_stops_once_the_best_move_settles() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        isready) echo "readyok" ;;
+        "go depth "*)
+            echo "info depth 1 score cp 10 pv d2d4"
+            echo "info depth 2 score cp 20 pv e2e4"
+            echo "info depth 3 score cp 25 pv e2e4 e7e5"
+            echo "info depth 4 score cp 22 pv e2e4 e7e5 g1f3"
+            read -r command
+            if [ "$command" = "stop" ]; then
+                echo "bestmove e2e4"
+            else
+                echo "bestmove d2d4"
+            fi
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let mut session = EngineSession::start(engine_path_str).expect("engine should start");
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let analysis = session
+        .analyze_until_stable(start, 30, 3)
+        .expect("analysis should stop early");
+    assert_eq!(analysis.depth, 4);
+    assert_eq!(analysis.bestmove_uci.as_deref(), Some("e2e4"));
+    assert_eq!(analysis.score_cp, Some(22));
+
+    drop(session);
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn io_hook_sees_the_handshake_in_order() {
+    let engine_path = write_fake_engine(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Fake"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let hook_events = Arc::clone(&events);
+    let session = EngineSession::start_with_io_hook(
+        engine_path_str,
+        EngineSessionOptions::default(),
+        Box::new(move |direction, line| {
+            hook_events
+                .lock()
+                .expect("hook events should lock")
+                .push((direction, line.to_string()));
+        }),
+    )
+    .expect("engine should start");
+    session.shutdown().expect("shutdown should work");
+
+    let events = events.lock().expect("events should lock").clone();
+    let expected = [
+        (Direction::Sent, "uci"),
+        (Direction::Received, "id name Fake"),
+        (Direction::Received, "uciok"),
+        (Direction::Sent, "isready"),
+        (Direction::Received, "readyok"),
+        (Direction::Sent, "quit"),
+    ];
+    let actual: Vec<_> = events
+        .iter()
+        .map(|(direction, line)| (*direction, line.as_str()))
+        .collect();
+    assert_eq!(actual, expected);
+
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
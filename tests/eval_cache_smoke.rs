@@ -0,0 +1,177 @@
+#![cfg(unix)]
+
+use chess_prep::{
+    EngineVariant, EvalCacheError, cached_analyze, cached_analyze_with_variant, deepen_analysis,
+};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+fn unique_temp_path(stem: &str, ext: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after UNIX_EPOCH")
+        .as_nanos();
+    let pid = std::process::id();
+    let counter = UNIQUE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    std::env::temp_dir().join(format!("{stem}_{pid}_{nanos}_{counter}.{ext}"))
+}
+
+fn write_engine_script(script: &str) -> PathBuf {
+    let path = unique_temp_path("chess_prep_eval_cache_engine", "sh");
+    fs::write(&path, script).expect("should write fake engine");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+        .expect("should mark fake engine executable");
+    path
+}
+
+fn write_fake_engine() -> PathBuf {
+    write_engine_script(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "id name FakeEngine"; echo "uciok" ;;
+        isready) echo "readyok" ;;
+        "go depth "*)
+            depth="${line##* }"
+            echo "info depth $depth score cp 31 pv e2e4 e7e5"
+            echo "bestmove e2e4 ponder e7e5"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    )
+}
+
+#[test]
+fn cached_analyze_reuses_stored_eval_without_spawning_engine() {
+    let db_path = unique_temp_path("chess_prep_eval_cache_test", "sqlite");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let engine_path = write_fake_engine();
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+    let sentinel_engine = "/nonexistent/chess_prep_sentinel_engine";
+
+    let first = cached_analyze(db_path_str, engine_path_str, START_FEN, 12)
+        .expect("first analysis should run the engine");
+    assert_eq!(first.depth, 12);
+    assert_eq!(first.score_cp, Some(31));
+    assert_eq!(first.pv, vec!["e2e4", "e7e5"]);
+
+    let second = cached_analyze(db_path_str, sentinel_engine, START_FEN, 12)
+        .expect("second analysis should be served from the cache");
+    assert_eq!(second.depth, first.depth);
+    assert_eq!(second.score_cp, first.score_cp);
+    assert_eq!(second.bestmove_uci, first.bestmove_uci);
+    assert_eq!(second.bestmove_san.as_deref(), Some("e4"));
+    assert_eq!(second.pv, first.pv);
+    assert_eq!(second.ponder.as_deref(), Some("e7e5"));
+
+    let shallower = cached_analyze(db_path_str, sentinel_engine, START_FEN, 8)
+        .expect("shallower request should be served from the deeper cache entry");
+    assert_eq!(shallower.depth, 12);
+
+    let err = cached_analyze(db_path_str, sentinel_engine, START_FEN, 20)
+        .expect_err("deeper request should need the engine");
+    assert!(matches!(err, EvalCacheError::Engine(_)));
+
+    let err = cached_analyze_with_variant(
+        db_path_str,
+        sentinel_engine,
+        START_FEN,
+        12,
+        EngineVariant::Chess960,
+    )
+    .expect_err("an eval cached under standard rules should not answer Chess960");
+    assert!(matches!(err, EvalCacheError::Engine(_)));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn deepen_analysis_reruns_only_when_cache_is_too_shallow() {
+    let db_path = unique_temp_path("chess_prep_eval_cache_test", "sqlite");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let engine_path = write_fake_engine();
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+    let sentinel_engine = "/nonexistent/chess_prep_sentinel_engine";
+
+    let shallow = cached_analyze(db_path_str, engine_path_str, START_FEN, 6)
+        .expect("shallow analysis should run the engine");
+    assert_eq!(shallow.depth, 6);
+
+    let err = deepen_analysis(db_path_str, sentinel_engine, START_FEN, 14)
+        .expect_err("a shallow cache entry should trigger a re-run");
+    assert!(matches!(err, EvalCacheError::Engine(_)));
+
+    let deeper = deepen_analysis(db_path_str, engine_path_str, START_FEN, 14)
+        .expect("deepening should run the engine");
+    assert_eq!(deeper.depth, 14);
+
+    let cached = deepen_analysis(db_path_str, sentinel_engine, START_FEN, 10)
+        .expect("a deep enough cache entry should not re-run the engine");
+    assert_eq!(cached.depth, 14);
+    assert_eq!(cached.pv, deeper.pv);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
+
+#[test]
+fn an_engine_that_stops_early_still_fills_the_cache() {
+    let db_path = unique_temp_path("chess_prep_eval_cache_test", "sqlite");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let engine_path = write_engine_script(
+        r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        isready) echo "readyok" ;;
+        "go depth "*)
+            echo "info depth 3 score mate 2 pv d1h5 b8c6"
+            echo "bestmove d1h5"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    );
+    let engine_path_str = engine_path
+        .to_str()
+        .expect("engine path should be valid UTF-8");
+    let sentinel_engine = "/nonexistent/chess_prep_sentinel_engine";
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/8/PPPP1PPP/RNBQK1NR w KQkq - 2 3";
+
+    let first = deepen_analysis(db_path_str, engine_path_str, fen, 20)
+        .expect("analysis should run the engine");
+    assert_eq!(first.depth, 3);
+    assert_eq!(first.score_mate, Some(2));
+
+    let cached = deepen_analysis(db_path_str, sentinel_engine, fen, 20)
+        .expect("an early-stopping result should answer the same request from the cache");
+    assert_eq!(
+        cached.depth, 3,
+        "the cache should report the depth searched"
+    );
+    assert_eq!(cached.score_mate, Some(2));
+    assert_eq!(cached.bestmove_san.as_deref(), Some("Qh5"));
+
+    let err = deepen_analysis(db_path_str, sentinel_engine, fen, 24)
+        .expect_err("a deeper request than the one answered should re-run");
+    assert!(matches!(err, EvalCacheError::Engine(_)));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(engine_path).expect("should clean up fake engine");
+}
@@ -0,0 +1,263 @@
+use chess_prep::{
+    AnalysisWorkspaceNode, GameFilter, export_game_pgn, export_games_pgn, export_studied_games_pgn,
+    import_pgn_file, init_db, save_analysis_workspace, split_pgn, validate_pgn,
+};
+use rusqlite::Connection;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unique_temp_path(stem: &str, ext: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after UNIX_EPOCH")
+        .as_nanos();
+    let pid = std::process::id();
+    let counter = UNIQUE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    std::env::temp_dir().join(format!("{stem}_{pid}_{nanos}_{counter}.{ext}"))
+}
+
+type StoredGame = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn stored_games(db_path: &str) -> Vec<StoredGame> {
+    let conn = Connection::open(db_path).expect("should open db");
+    let mut stmt = conn
+        .prepare(
+            "SELECT event, site, date, white, black, result, eco, pgn FROM games ORDER BY rowid",
+        )
+        .expect("should prepare games query");
+    stmt.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+            row.get(5)?,
+            row.get(6)?,
+            row.get(7)?,
+        ))
+    })
+    .expect("should query games")
+    .collect::<Result<_, _>>()
+    .expect("should read games")
+}
+
+#[test]
+fn exported_pgn_reimports_to_identical_games() {
+    let source_db = unique_temp_path("chess_prep_export_source", "sqlite");
+    let target_db = unique_temp_path("chess_prep_export_target", "sqlite");
+    let source_pgn = unique_temp_path("chess_prep_export_source", "pgn");
+    let exported_pgn = unique_temp_path("chess_prep_export_out", "pgn");
+
+    let pgn = r#"[Event "Export Open"]
+[Site "Berlin"]
+[Date "2024.06.01"]
+[Round "1"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[ECO "C50"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. c3 Nf6 5. d4 exd4 6. cxd4 Bb4+ 7. Bd2 Bxd2+
+8. Nbxd2 d5 9. exd5 Nxd5 10. Qb3 Na5 11. Qa4+ c6 12. Bxd5 Qxd5 13. O-O O-O 1-0
+
+[Event "Export Open"]
+[Site "Berlin"]
+[Date "2024.06.02"]
+[Round "2"]
+[White "Carol"]
+[Black "Dave"]
+[Result "1/2-1/2"]
+[ECO "D30"]
+
+1. d4 d5 2. c4 e6 1/2-1/2
+"#;
+    fs::write(&source_pgn, pgn).expect("should write source PGN");
+
+    let source_db_str = source_db.to_str().expect("path should be valid UTF-8");
+    let target_db_str = target_db.to_str().expect("path should be valid UTF-8");
+
+    init_db(source_db_str).expect("init source");
+    import_pgn_file(
+        source_db_str,
+        source_pgn.to_str().expect("path should be valid UTF-8"),
+    )
+    .expect("source import should work");
+
+    let mut buffer = Vec::new();
+    let written = export_games_pgn(source_db_str, &GameFilter::default(), &mut buffer)
+        .expect("export should work");
+    assert_eq!(written, 2);
+
+    let exported = String::from_utf8(buffer).expect("export should be UTF-8");
+    assert!(exported.contains("[Round \"2\"]"));
+    assert!(exported.contains("1. d4 d5 2. c4 e6 1/2-1/2"));
+    let second_game = export_game_pgn(source_db_str, 2).expect("single export should work");
+    assert!(exported.ends_with(&second_game));
+    fs::write(&exported_pgn, &exported).expect("should write exported PGN");
+
+    init_db(target_db_str).expect("init target");
+    let summary = import_pgn_file(
+        target_db_str,
+        exported_pgn.to_str().expect("path should be valid UTF-8"),
+    )
+    .expect("re-import should work");
+    assert_eq!(summary.inserted, 2);
+
+    assert_eq!(stored_games(source_db_str), stored_games(target_db_str));
+
+    for path in [source_db, target_db, source_pgn, exported_pgn] {
+        fs::remove_file(path).expect("should clean up temp file");
+    }
+}
+
+fn workspace_node(
+    id: &str,
+    parent_id: Option<&str>,
+    san: Option<&str>,
+    fen: &str,
+    comment: &str,
+    nags: &[&str],
+    sort_index: i32,
+) -> AnalysisWorkspaceNode {
+    AnalysisWorkspaceNode {
+        id: id.to_string(),
+        parent_id: parent_id.map(str::to_string),
+        san: san.map(str::to_string),
+        uci: None,
+        fen: fen.to_string(),
+        comment: comment.to_string(),
+        nags: nags.iter().map(|nag| nag.to_string()).collect(),
+        sort_index,
+        metadata: None,
+    }
+}
+
+#[test]
+fn studied_export_uses_workspace_variations_and_keeps_plain_games() {
+    let games_db = unique_temp_path("chess_prep_export_studied", "sqlite");
+    let analysis_db = unique_temp_path("chess_prep_export_analysis", "sqlite");
+    let source_pgn = unique_temp_path("chess_prep_export_studied", "pgn");
+    let games_db_str = games_db.to_str().expect("path should be valid UTF-8");
+    let analysis_db_str = analysis_db.to_str().expect("path should be valid UTF-8");
+
+    let pgn = r#"[Event "Studied"]
+[White "Alice"]
+[Black "Bob"]
+[Result "*"]
+
+1. e4 e5 2. Nf3 *
+
+[Event "Unstudied"]
+[White "Carol"]
+[Black "Dave"]
+[Result "1/2-1/2"]
+
+1. d4 d5 1/2-1/2
+"#;
+    fs::write(&source_pgn, pgn).expect("should write source PGN");
+    init_db(games_db_str).expect("init games db");
+    import_pgn_file(
+        games_db_str,
+        source_pgn.to_str().expect("path should be valid UTF-8"),
+    )
+    .expect("import should work");
+
+    let nodes = vec![
+        workspace_node(
+            "root",
+            None,
+            None,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "",
+            &[],
+            0,
+        ),
+        workspace_node(
+            "e4",
+            Some("root"),
+            Some("e4"),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            "Best by test",
+            &["!"],
+            0,
+        ),
+        workspace_node(
+            "e5",
+            Some("e4"),
+            Some("e5"),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+            "",
+            &[],
+            0,
+        ),
+        workspace_node(
+            "c5",
+            Some("e4"),
+            Some("c5"),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+            "Sicilian",
+            &[],
+            1,
+        ),
+        workspace_node(
+            "nf3",
+            Some("e5"),
+            Some("Nf3"),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+            "",
+            &[],
+            0,
+        ),
+    ];
+    save_analysis_workspace(
+        analysis_db_str,
+        games_db_str,
+        1,
+        "Open games",
+        "root",
+        Some("nf3"),
+        &nodes,
+    )
+    .expect("workspace save should work");
+
+    let mut buffer = Vec::new();
+    let written = export_studied_games_pgn(
+        games_db_str,
+        analysis_db_str,
+        &GameFilter::default(),
+        &mut buffer,
+    )
+    .expect("studied export should work");
+    assert_eq!(written, 2);
+
+    let exported = String::from_utf8(buffer).expect("export should be UTF-8");
+    let games = split_pgn(&exported);
+    assert_eq!(games.len(), 2);
+    assert!(games[0].contains("[White \"Alice\"]"));
+    assert!(games[0].contains("1. e4 $1 {Best by test} e5 (1... c5 {Sicilian}) 2. Nf3 *"));
+    assert!(games[1].contains("[White \"Carol\"]"));
+    assert!(games[1].contains("1. d4 d5 1/2-1/2"));
+
+    let report = validate_pgn(&exported);
+    assert_eq!(report.games.len(), 2);
+    assert_eq!(report.valid, 2);
+
+    for path in [games_db, analysis_db, source_pgn] {
+        fs::remove_file(path).expect("should clean up temp file");
+    }
+}
@@ -1,10 +1,14 @@
-use chess_prep::{import_pgn_file, init_db};
+use chess_prep::{
+    ImportError, ImportOptions, Locale, PgnGameStatus, backup_db, check_schema, import_pgn_file,
+    import_pgn_file_filtered, import_pgn_file_returning_ids, import_pgn_file_with_options, init_db,
+    merge_databases, normalize_headers, rename_player, split_pgn, validate_pgn,
+};
 use rusqlite::{Connection, params};
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
 
@@ -461,3 +465,1201 @@ fn import_skips_malformed_game_and_continues() {
     fs::remove_file(db_path).expect("should clean up temp db file");
     fs::remove_file(pgn_path).expect("should clean up temp PGN file");
 }
+
+#[test]
+fn import_tolerates_bom_and_preamble_before_first_game() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = "\u{feff}Exported by SomeTool v1.2\n\n[Event \"BOM Game\"]\n[Site \"Online\"]\n[Date \"2024.06.01\"]\n[White \"Alice\"]\n[Black \"Bob\"]\n[Result \"1-0\"]\n[ECO \"C20\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n[Event \"Second Game\"]\n[Site \"Online\"]\n[Date \"2024.06.02\"]\n[White \"Carol\"]\n[Black \"Dave\"]\n[Result \"0-1\"]\n[ECO \"B01\"]\n\n1. e4 d5 0-1\n";
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.inserted, 2);
+    assert_eq!(summary.errors, 0);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let (event, site, white, movetext): (String, String, String, String) = conn
+        .query_row(
+            "SELECT event, site, white, pgn FROM games WHERE black = 'Bob'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .expect("first game should be stored with its tags");
+    assert_eq!(event, "BOM Game");
+    assert_eq!(site, "Online");
+    assert_eq!(white, "Alice");
+    assert_eq!(movetext, "e4 e5 Nf3 Nc6");
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn merge_databases_skips_games_already_in_destination() {
+    let dest_path = unique_temp_db_path();
+    let src_path = unique_temp_db_path();
+    let dest_pgn_path = unique_temp_pgn_path();
+    let src_pgn_path = unique_temp_pgn_path();
+
+    let shared_game = r#"[Event "Shared"]
+[Site "Berlin"]
+[Date "2024.07.01"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[ECO "C20"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+"#;
+    let dest_only = r#"[Event "Dest Only"]
+[Site "Berlin"]
+[Date "2024.07.02"]
+[White "Carol"]
+[Black "Dave"]
+[Result "0-1"]
+[ECO "B01"]
+
+1. e4 d5 0-1
+"#;
+    let src_only = r#"[Event "Source Only"]
+[Site "Paris"]
+[Date "2024.07.03"]
+[White "Erin"]
+[Black "Frank"]
+[Result "1/2-1/2"]
+[ECO "D00"]
+
+1. d4 d5 1/2-1/2
+"#;
+
+    fs::write(&dest_pgn_path, format!("{shared_game}\n{dest_only}"))
+        .expect("should write dest PGN");
+    fs::write(&src_pgn_path, format!("{shared_game}\n{src_only}"))
+        .expect("should write source PGN");
+
+    let dest_path_str = dest_path.to_str().expect("dest path should be valid UTF-8");
+    let src_path_str = src_path.to_str().expect("src path should be valid UTF-8");
+
+    init_db(dest_path_str).expect("init dest");
+    init_db(src_path_str).expect("init src");
+    import_pgn_file(
+        dest_path_str,
+        dest_pgn_path
+            .to_str()
+            .expect("PGN path should be valid UTF-8"),
+    )
+    .expect("dest import should work");
+    import_pgn_file(
+        src_path_str,
+        src_pgn_path
+            .to_str()
+            .expect("PGN path should be valid UTF-8"),
+    )
+    .expect("src import should work");
+
+    let summary = merge_databases(dest_path_str, src_path_str).expect("merge should work");
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.skipped, 1);
+    assert_eq!(summary.errors, 0);
+
+    let conn = Connection::open(dest_path_str).expect("should open dest db");
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+        .expect("should count games");
+    assert_eq!(count, 3, "overlapping game should be stored once");
+
+    let merged_eco: String = conn
+        .query_row(
+            "
+            SELECT value FROM game_tags
+            JOIN games ON games.rowid = game_tags.game_id
+            WHERE games.white = 'Erin' AND game_tags.name = 'ECO'
+            ",
+            [],
+            |row| row.get(0),
+        )
+        .expect("merged game should carry its tags");
+    assert_eq!(merged_eco, "D00");
+
+    fs::remove_file(dest_path).expect("should clean up dest db");
+    fs::remove_file(src_path).expect("should clean up src db");
+    fs::remove_file(dest_pgn_path).expect("should clean up dest PGN");
+    fs::remove_file(src_pgn_path).expect("should clean up src PGN");
+}
+
+#[test]
+fn import_infers_result_from_final_checkmate_when_requested() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = r#"[Event "Mate Without Result"]
+[Site "Local"]
+[Date "2024.03.01"]
+[White "Frank"]
+[Black "Grace"]
+
+1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# *
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init should work");
+    let summary = import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            infer_result_from_checkmate: true,
+            ..ImportOptions::default()
+        },
+        |_| {},
+    )
+    .expect("import should work");
+    assert_eq!(summary.inserted, 1);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let result: Option<String> = conn
+        .query_row(
+            "SELECT result FROM games WHERE white = 'Frank'",
+            [],
+            |row| row.get(0),
+        )
+        .expect("should read result");
+    assert_eq!(result.as_deref(), Some("1-0"));
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+fn assert_compressed_bin_imports(program: &str) {
+    if Command::new(program).arg("--version").output().is_err() {
+        eprintln!("{program} binary not available; skipping compressed .bin import test");
+        return;
+    }
+
+    let db_path = unique_temp_db_path();
+    let plain_pgn_path = unique_temp_pgn_path();
+    let bin_path = unique_temp_path("chess_prep_test_compressed", "bin");
+
+    let pgn = r#"[Event "Served Without Extension"]
+[Site "Online"]
+[Date "2024.04.05"]
+[White "Gamma"]
+[Black "Delta"]
+[Result "0-1"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+"#;
+    fs::write(&plain_pgn_path, pgn).expect("should write plain PGN");
+    let output = Command::new(program)
+        .arg("-c")
+        .arg(&plain_pgn_path)
+        .output()
+        .expect("should run compressor");
+    assert!(output.status.success(), "{program} should compress PGN");
+    fs::write(&bin_path, output.stdout).expect("should write compressed .bin file");
+
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let bin_path_str = bin_path
+        .to_str()
+        .expect("temp .bin path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary =
+        import_pgn_file(db_path_str, bin_path_str).expect("compressed .bin import should work");
+
+    assert_eq!(summary.total, 1, "{program}");
+    assert_eq!(summary.inserted, 1, "{program}");
+    assert_eq!(summary.errors, 0, "{program}");
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(plain_pgn_path).expect("should clean up plain PGN file");
+    fs::remove_file(bin_path).expect("should clean up compressed .bin file");
+}
+
+#[test]
+fn import_detects_zstd_by_magic_bytes() {
+    assert_compressed_bin_imports("zstd");
+}
+
+#[test]
+fn import_detects_gzip_by_magic_bytes() {
+    assert_compressed_bin_imports("gzip");
+}
+
+#[test]
+fn import_classifies_missing_eco_when_requested() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = r#"[Event "No ECO"]
+[Site "Local"]
+[Date "2024.03.02"]
+[White "Heidi"]
+[Black "Ivan"]
+[Result "1/2-1/2"]
+
+1. e4 c5 2. Nf3 d6 1/2-1/2
+
+[Event "Composed Start"]
+[White "Mallory"]
+[Black "Ivan"]
+[Result "*"]
+[SetUp "1"]
+[FEN "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"]
+
+1. e4 c5 *
+
+[Event "Atomic"]
+[White "Niaj"]
+[Black "Ivan"]
+[Result "*"]
+[Variant "Atomic"]
+
+1. e4 c5 *
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init should work");
+    import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            classify_missing_eco: true,
+            ..ImportOptions::default()
+        },
+        |_| {},
+    )
+    .expect("import should work");
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let eco: Option<String> = conn
+        .query_row("SELECT eco FROM games WHERE white = 'Heidi'", [], |row| {
+            row.get(0)
+        })
+        .expect("should read eco");
+    assert_eq!(eco.as_deref(), Some("B50"));
+
+    for white in ["Mallory", "Niaj"] {
+        let eco: Option<String> = conn
+            .query_row("SELECT eco FROM games WHERE white = ?1", [white], |row| {
+                row.get(0)
+            })
+            .expect("should read eco");
+        assert_eq!(eco, None, "{white}'s game should not be classified");
+    }
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn import_flags_unreplayable_movetext_and_keeps_raw_text() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = r#"[Event "Illegal King Walk"]
+[Site "Local"]
+[Date "2024.03.03"]
+[White "Judy"]
+[Black "Karl"]
+[Result "1-0"]
+
+1. e4 e5 2. Ke3 Nc6 1-0
+
+[Event "Legal Game"]
+[Site "Local"]
+[Date "2024.03.03"]
+[White "Judy"]
+[Black "Karl"]
+[Result "0-1"]
+
+1. e4 e5 2. Ke2 Nc6 0-1
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init should work");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.errors, 0);
+    assert_eq!(summary.invalid, 1);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let (pgn_text, replay_error, ply_count): (String, Option<String>, Option<i64>) = conn
+        .query_row(
+            "SELECT pgn, replay_error, ply_count FROM games WHERE event = 'Illegal King Walk'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .expect("flagged game should be stored");
+    assert_eq!(pgn_text, "1. e4 e5 2. Ke3 Nc6 1-0");
+    assert_eq!(replay_error.as_deref(), Some("illegal SAN 'Ke3' at ply 3"));
+    assert_eq!(ply_count, None);
+
+    let (legal_pgn, legal_error, legal_ply_count): (String, Option<String>, Option<i64>) = conn
+        .query_row(
+            "SELECT pgn, replay_error, ply_count FROM games WHERE event = 'Legal Game'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .expect("legal game should be stored");
+    assert_eq!(legal_pgn, "e4 e5 Ke2 Nc6");
+    assert_eq!(legal_error, None);
+    assert_eq!(legal_ply_count, Some(4));
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn import_splits_crlf_games_without_blank_separators() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = [
+        "[Event \"CRLF One\"]",
+        "[White \"Alice\"]",
+        "[Black \"Bob\"]",
+        "[Result \"1-0\"]",
+        "",
+        "1. e4 e5 2. Nf3 Nc6",
+        "1-0",
+        "[Site \"No Event Tag\"]",
+        "[White \"Carol\"]",
+        "[Black \"Dave\"]",
+        "[Result \"0-1\"]",
+        "",
+        "1. d4 d5 0-1 [Event \"CRLF Three\"]",
+        "[White \"Erin\"]",
+        "[Black \"Frank\"]",
+        "[Result \"1/2-1/2\"]",
+        "",
+        "1. c4 c5 1/2-1/2",
+        "",
+    ]
+    .join("\r\n");
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+
+    assert_eq!(summary.total, 3);
+    assert_eq!(summary.inserted, 3);
+    assert_eq!(summary.errors, 0);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let mut stmt = conn
+        .prepare("SELECT white, result, pgn FROM games ORDER BY rowid")
+        .expect("should prepare games query");
+    let games: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read games");
+    assert_eq!(
+        games,
+        vec![
+            (
+                "Alice".to_string(),
+                "1-0".to_string(),
+                "e4 e5 Nf3 Nc6".to_string()
+            ),
+            ("Carol".to_string(), "0-1".to_string(), "d4 d5".to_string()),
+            (
+                "Erin".to_string(),
+                "1/2-1/2".to_string(),
+                "c4 c5".to_string()
+            ),
+        ]
+    );
+    assert!(
+        games
+            .iter()
+            .all(|(_, _, movetext)| !movetext.contains('\r'))
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn import_progress_games_interval_is_configurable() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn: String = (1..=5)
+        .map(|round| {
+            format!(
+                "[Event \"Progress\"]\n[Round \"{round}\"]\n[White \"White {round}\"]\n[Black \"Black\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n\n"
+            )
+        })
+        .collect();
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+    init_db(db_path_str).expect("init should work");
+
+    let quiet_timer = Duration::from_secs(3_600);
+    let mut default_reports = Vec::new();
+    import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            progress_time_interval: quiet_timer,
+            ..ImportOptions::default()
+        },
+        |summary| default_reports.push(summary.total),
+    )
+    .expect("import should work");
+    assert_eq!(default_reports, vec![0, 5]);
+
+    let mut frequent_reports = Vec::new();
+    import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            progress_games_interval: 2,
+            progress_time_interval: quiet_timer,
+            ..ImportOptions::default()
+        },
+        |summary| frequent_reports.push(summary.total),
+    )
+    .expect("re-import should work");
+    assert_eq!(frequent_reports, vec![0, 2, 4, 5]);
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn backup_db_copies_games_while_source_is_open() {
+    let db_path = unique_temp_db_path();
+    let backup_path = unique_temp_db_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let backup_path_str = backup_path
+        .to_str()
+        .expect("temp backup path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init should work");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for white in ["Alice", "Carol", "Erin"] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, pgn) VALUES (?1, 'Bob', '1-0', 'e4 e5')",
+            params![white],
+        )
+        .expect("should insert game");
+    }
+
+    backup_db(db_path_str, backup_path_str).expect("backup should work with the source open");
+    drop(conn);
+
+    let backup = Connection::open(backup_path_str).expect("should open backup");
+    let count: i64 = backup
+        .query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+        .expect("backup should contain games");
+    assert_eq!(count, 3);
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(backup_path).expect("should clean up backup file");
+}
+
+#[test]
+fn import_can_skip_games_with_illegal_continuations() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = r#"[Event "Illegal Continuation"]
+[White "Liam"]
+[Black "Mia"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6 Bxc6 1-0
+
+[Event "Legal Continuation"]
+[White "Liam"]
+[Black "Mia"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6 dxc6 1-0
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init should work");
+    let summary = import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            skip_invalid_games: true,
+            ..ImportOptions::default()
+        },
+        |_| {},
+    )
+    .expect("import should work");
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.errors, 0);
+    assert_eq!(summary.invalid, 1);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let events: Vec<String> = conn
+        .prepare("SELECT event FROM games")
+        .expect("should prepare query")
+        .query_map([], |row| row.get(0))
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read games");
+    assert_eq!(events, vec!["Legal Continuation"]);
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn import_returning_ids_reports_only_newly_inserted_rows() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+    init_db(db_path_str).expect("init should work");
+
+    let first_batch = r#"[Event "Batch"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 1-0
+
+[Event "Batch"]
+[White "Carol"]
+[Black "Dave"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+"#;
+    fs::write(&pgn_path, first_batch).expect("should write temp PGN");
+    let (summary, ids) =
+        import_pgn_file_returning_ids(db_path_str, pgn_path_str, ImportOptions::default())
+            .expect("import should work");
+    assert_eq!(summary.inserted, 2);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let stored: Vec<i64> = conn
+        .prepare("SELECT rowid FROM games ORDER BY rowid")
+        .expect("should prepare query")
+        .query_map([], |row| row.get(0))
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read rowids");
+    assert_eq!(ids, stored);
+
+    let second_batch = format!(
+        "{first_batch}
+[Event \"Batch\"]
+[White \"Erin\"]
+[Black \"Frank\"]
+[Result \"1/2-1/2\"]
+
+1. c4 c5 1/2-1/2
+"
+    );
+    fs::write(&pgn_path, second_batch).expect("should write temp PGN");
+    let (summary, ids) =
+        import_pgn_file_returning_ids(db_path_str, pgn_path_str, ImportOptions::default())
+            .expect("import should work");
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.skipped, 2);
+    assert_eq!(ids.len(), 1);
+    let white: String = conn
+        .query_row(
+            "SELECT white FROM games WHERE rowid = ?1",
+            [ids[0]],
+            |row| row.get(0),
+        )
+        .expect("returned id should exist");
+    assert_eq!(white, "Erin");
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn rename_player_merges_spellings_and_dedupes() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    init_db(db_path_str).expect("init should work");
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    for (white, black) in [
+        ("Carlsen, Magnus", "Nakamura, Hikaru"),
+        ("Magnus Carlsen", "Nakamura, Hikaru"),
+        ("Caruana, Fabiano", " magnus carlsen "),
+    ] {
+        conn.execute(
+            "INSERT INTO games (event, white, black, result, pgn) VALUES ('Blitz', ?1, ?2, '1-0', 'e4 e5')",
+            params![white, black],
+        )
+        .expect("should insert game");
+    }
+
+    let changed =
+        rename_player(db_path_str, "Magnus Carlsen", "Carlsen, Magnus").expect("rename works");
+    assert_eq!(changed, 2);
+
+    let games: Vec<(String, String)> = conn
+        .prepare("SELECT white, black FROM games ORDER BY rowid")
+        .expect("should prepare query")
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read games");
+    assert_eq!(
+        games,
+        vec![
+            (
+                "Carlsen, Magnus".to_string(),
+                "Nakamura, Hikaru".to_string()
+            ),
+            (
+                "Caruana, Fabiano".to_string(),
+                "Carlsen, Magnus".to_string()
+            ),
+        ]
+    );
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+}
+
+#[test]
+fn check_schema_accepts_initialized_db_and_flags_unrelated_files() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    init_db(db_path_str).expect("init should work");
+
+    let status = check_schema(db_path_str).expect("schema check should work");
+    assert!(status.is_usable());
+    assert!(status.has_games_table);
+    assert!(status.missing_columns.is_empty());
+    assert!(status.missing_migrated_columns.is_empty());
+
+    let other_path = unique_temp_db_path();
+    let other_path_str = other_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let conn = Connection::open(other_path_str).expect("should create unrelated db");
+    conn.execute_batch("CREATE TABLE notes (body TEXT);")
+        .expect("should create unrelated table");
+    drop(conn);
+
+    let status = check_schema(other_path_str).expect("schema check should work");
+    assert!(!status.is_usable());
+    assert!(!status.has_games_table);
+    assert!(status.missing_columns.contains(&"pgn".to_string()));
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(other_path).expect("should clean up unrelated db file");
+}
+
+#[test]
+fn normalize_headers_trims_whitespace_and_dedupes() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    init_db(db_path_str).expect("init should work");
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    for (event, white) in [
+        ("Club  Open", "Carlsen, Magnus"),
+        ("Club Open ", " Carlsen,   Magnus "),
+        ("Club Open", "Nakamura,\tHikaru  "),
+    ] {
+        conn.execute(
+            "INSERT INTO games (event, white, black, result, pgn) VALUES (?1, ?2, 'Caruana, Fabiano', '1-0 ', 'e4 e5')",
+            params![event, white],
+        )
+        .expect("should insert game");
+    }
+
+    let changed = normalize_headers(db_path_str).expect("normalize should work");
+    assert_eq!(changed, 3);
+
+    let games: Vec<(String, String, String)> = conn
+        .prepare("SELECT event, white, result FROM games ORDER BY rowid")
+        .expect("should prepare query")
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read games");
+    assert_eq!(
+        games,
+        vec![
+            (
+                "Club Open".to_string(),
+                "Carlsen, Magnus".to_string(),
+                "1-0".to_string()
+            ),
+            (
+                "Club Open".to_string(),
+                "Nakamura, Hikaru".to_string(),
+                "1-0".to_string()
+            ),
+        ]
+    );
+
+    assert_eq!(
+        normalize_headers(db_path_str).expect("normalize should work"),
+        0
+    );
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+}
+
+#[test]
+fn import_keeps_a_final_tags_only_game_without_trailing_newline() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    let pgn = "[Event \"Header Only\"]\n[White \"Alice\"]\n[Black \"Bob\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n[Event \"Header Only\"]\n[White \"Carol\"]\n[Black \"Dave\"]\n[Result \"0-1\"]";
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init should work");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.inserted, 2);
+    assert_eq!(summary.errors, 0);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let (result, pgn_text): (String, Option<String>) = conn
+        .query_row(
+            "SELECT result, pgn FROM games WHERE white = 'Carol'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .expect("tags-only game should be stored");
+    assert_eq!(result, "0-1");
+    assert_eq!(pgn_text, None);
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn validate_pgn_reports_each_game_without_a_database() {
+    let pgn = r#"[Event "Good"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+[Event "Malformed"]
+[White "Carol"]
+[Black "Dave"]
+[Result "*"]
+
+1. e4 {unclosed comment
+
+[Event "Illegal"]
+[White "Erin"]
+[Black "Frank"]
+[Result "*"]
+
+1. e4 e5 2. Ke3 *
+"#;
+
+    let report = validate_pgn(pgn);
+    assert_eq!(report.games.len(), 3);
+    assert_eq!(report.valid, 1);
+    assert_eq!(report.parse_errors, 1);
+    assert_eq!(report.invalid, 1);
+
+    let lines: Vec<usize> = report.games.iter().map(|game| game.line).collect();
+    assert_eq!(lines, vec![1, 8, 15]);
+    assert_eq!(report.games[0].status, PgnGameStatus::Valid);
+    assert!(matches!(
+        report.games[1].status,
+        PgnGameStatus::ParseError(_)
+    ));
+    assert!(
+        matches!(&report.games[2].status, PgnGameStatus::InvalidMoves(reason) if reason.contains("Ke3"))
+    );
+}
+
+#[test]
+fn split_pgn_returns_each_game_verbatim() {
+    let pgn = r#"[Event "First"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+[Event "Second"]
+[White "Carol"]
+[Black "Dave"]
+[Result "*"]
+
+1. d4 d5 (1... Nf6) 2. c4 *
+"#;
+
+    let games = split_pgn(pgn);
+    assert_eq!(games.len(), 2);
+    assert!(games[0].starts_with("[Event \"First\"]"));
+    assert!(games[0].contains("{best by test}"));
+    assert!(games[1].starts_with("[Event \"Second\"]"));
+    assert!(games[1].contains("(1... Nf6)"));
+
+    for game in &games {
+        let report = validate_pgn(game);
+        assert_eq!(report.games.len(), 1);
+        assert_eq!(report.valid, 1);
+    }
+}
+
+#[test]
+fn filtered_import_keeps_only_games_matching_the_header_predicate() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+    init_db(db_path_str).expect("init should work");
+
+    let pgn = r#"[Event "Dump"]
+[White "Carlsen, Magnus"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 1-0
+
+[Event "Dump"]
+[White "Carol"]
+[Black "Carlsen, Magnus"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+
+[Event "Dump"]
+[White "Magnus Carlsen"]
+[Black "Dave"]
+[Result "1/2-1/2"]
+
+1. c4 c5 1/2-1/2
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+
+    let summary = import_pgn_file_filtered(
+        db_path_str,
+        pgn_path_str,
+        |headers| {
+            headers
+                .white
+                .as_deref()
+                .is_some_and(|white| white.contains("Carlsen"))
+        },
+        |_| {},
+    )
+    .expect("import should work");
+    assert_eq!(summary.total, 3);
+    assert_eq!(summary.inserted, 2);
+    assert_eq!(summary.filtered, 1);
+    assert_eq!(summary.errors, 0);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let whites: Vec<String> = conn
+        .prepare("SELECT white FROM games ORDER BY rowid")
+        .expect("should prepare query")
+        .query_map([], |row| row.get(0))
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read whites");
+    assert_eq!(whites, vec!["Carlsen, Magnus", "Magnus Carlsen"]);
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn commit_every_keeps_committed_batches_when_an_import_aborts() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    let pgn = ["Alice", "Bob", "Carol", "Dave"]
+        .iter()
+        .map(|white| {
+            format!(
+                "[Event \"Batch\"]\n[White \"{white}\"]\n[Black \"Zed\"]\n[Result \"*\"]\n\n1. e4 e5 *\n\n"
+            )
+        })
+        .collect::<String>();
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    let options = ImportOptions {
+        commit_every: Some(2),
+        progress_games_interval: 1,
+        progress_time_interval: Duration::from_secs(3600),
+        ..ImportOptions::default()
+    };
+    let stored_count = || -> i64 {
+        Connection::open(&db_path)
+            .expect("should open db")
+            .query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+            .expect("should count games")
+    };
+
+    init_db(db_path_str).expect("init should work");
+    let aborted = std::panic::catch_unwind(|| {
+        let _ = import_pgn_file_with_options(db_path_str, pgn_path_str, options, |summary| {
+            if summary.total == 3 {
+                panic!("simulated crash");
+            }
+        });
+    });
+    assert!(aborted.is_err());
+    assert_eq!(stored_count(), 2);
+
+    fs::remove_file(&db_path).expect("should clean up temp db file");
+    init_db(db_path_str).expect("init should work");
+    let summary = import_pgn_file_with_options(db_path_str, pgn_path_str, options, |_| {})
+        .expect("import should work");
+    assert_eq!(summary.inserted, 4);
+    assert_eq!(stored_count(), 4);
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn commit_every_fails_the_import_when_a_batch_commit_fails() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    let pgn = ["Alice", "Bob", "Carol", "Dave"]
+        .iter()
+        .map(|white| {
+            format!(
+                "[Event \"Batch\"]\n[White \"{white}\"]\n[Black \"Zed\"]\n[Result \"*\"]\n\n1. e4 e5 *\n\n"
+            )
+        })
+        .collect::<String>();
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init should work");
+
+    // An open read transaction keeps the importer from committing its
+    // first batch.
+    let reader = Connection::open(&db_path).expect("should open db");
+    reader
+        .busy_timeout(Duration::from_millis(50))
+        .expect("should set busy timeout");
+    reader
+        .execute_batch("BEGIN; SELECT COUNT(*) FROM games;")
+        .expect("should start a read transaction");
+    let result = import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            commit_every: Some(2),
+            ..ImportOptions::default()
+        },
+        |_| {},
+    );
+    assert!(matches!(result, Err(ImportError::Sql(_))), "{result:?}");
+    reader
+        .execute_batch("COMMIT")
+        .expect("should end the read transaction");
+
+    let stored: i64 = reader
+        .query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+        .expect("should count games");
+    assert_eq!(stored, 0);
+
+    drop(reader);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn san_locale_translates_german_piece_letters_on_import() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    let pgn = r#"[Event "Deutsche Meisterschaft"]
+[White "Schmidt"]
+[Black "Turm"]
+[Result "*"]
+
+1. e4 e5 2. Sf3 Sc6 {Der Springer} 3. Lb5 a6 4. Lxc6 dxc6 5. O-O Dd6
+6. Te1 (6. d3 Le6) Lg4 7. h3 Lxf3 8. Dxf3 *
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+
+    init_db(db_path_str).expect("init should work");
+    let summary = import_pgn_file_with_options(
+        db_path_str,
+        pgn_path_str,
+        ImportOptions {
+            san_locale: Some(Locale::German),
+            ..ImportOptions::default()
+        },
+        |_| {},
+    )
+    .expect("import should work");
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.invalid, 0);
+
+    let conn = Connection::open(&db_path).expect("should open db");
+    let (black, movetext, replay_error): (String, String, Option<String>) = conn
+        .query_row("SELECT black, pgn, replay_error FROM games", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .expect("should read game");
+    assert_eq!(black, "Turm");
+    assert_eq!(
+        movetext,
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6 dxc6 O-O Qd6 Re1 Bg4 h3 Bxf3 Qxf3"
+    );
+    assert_eq!(replay_error, None);
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn import_keeps_a_tagless_game() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+
+    fs::write(&pgn_path, "\u{feff}1. e4 e5 2. Nf3 *\n").expect("should write temp PGN");
+    let db_path_str = db_path
+        .to_str()
+        .expect("temp db path should be valid UTF-8");
+    let pgn_path_str = pgn_path
+        .to_str()
+        .expect("temp PGN path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+    assert_eq!(summary.total, 1);
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.errors, 0);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let movetext: String = conn
+        .query_row("SELECT pgn FROM games", [], |row| row.get(0))
+        .expect("tagless game should be stored");
+    assert_eq!(movetext, "e4 e5 Nf3");
+
+    fs::remove_file(db_path).expect("should clean up temp db file");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN file");
+}
+
+#[test]
+fn merge_databases_rejects_a_missing_source_without_creating_it() {
+    let dest_path = unique_temp_db_path();
+    let src_path = unique_temp_db_path();
+    let dest_path_str = dest_path.to_str().expect("db path should be valid UTF-8");
+    let src_path_str = src_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(dest_path_str).expect("init_db should create schema");
+    let err = merge_databases(dest_path_str, src_path_str).expect_err("merge should fail");
+    assert!(
+        matches!(err, ImportError::Io(ref io_err) if io_err.kind() == std::io::ErrorKind::NotFound),
+        "{err:?}"
+    );
+    assert!(!src_path.exists(), "merge must not create the source db");
+
+    fs::remove_file(dest_path).expect("should clean up temp db file");
+}
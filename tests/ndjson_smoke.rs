@@ -0,0 +1,122 @@
+#![cfg(feature = "serde")]
+
+use chess_prep::{GameFilter, export_games_ndjson, import_games_ndjson, import_pgn_file, init_db};
+use rusqlite::Connection;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unique_temp_path(stem: &str, ext: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after UNIX_EPOCH")
+        .as_nanos();
+    let pid = std::process::id();
+    let counter = UNIQUE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    std::env::temp_dir().join(format!("{stem}_{pid}_{nanos}_{counter}.{ext}"))
+}
+
+type StoredGame = (Vec<Option<String>>, Vec<(String, String)>);
+
+fn stored_games(db_path: &str) -> Vec<StoredGame> {
+    let conn = Connection::open(db_path).expect("should open db");
+    let mut stmt = conn
+        .prepare(
+            "SELECT rowid, event, site, date, white, black, result, eco, pgn, setup_fen, opening, variation FROM games ORDER BY rowid",
+        )
+        .expect("should prepare games query");
+    let games: Vec<(i64, Vec<Option<String>>)> = stmt
+        .query_map([], |row| {
+            let columns = (1..=11)
+                .map(|index| row.get(index))
+                .collect::<Result<_, _>>()?;
+            Ok((row.get(0)?, columns))
+        })
+        .expect("should query games")
+        .collect::<Result<_, _>>()
+        .expect("should read games");
+
+    games
+        .into_iter()
+        .map(|(id, columns)| {
+            let mut tags_stmt = conn
+                .prepare("SELECT name, value FROM game_tags WHERE game_id = ?1 ORDER BY rowid")
+                .expect("should prepare tags query");
+            let tags = tags_stmt
+                .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .expect("should query tags")
+                .collect::<Result<_, _>>()
+                .expect("should read tags");
+            (columns, tags)
+        })
+        .collect()
+}
+
+#[test]
+fn ndjson_export_reimports_to_identical_games() {
+    let source_db = unique_temp_path("chess_prep_ndjson_source", "sqlite");
+    let target_db = unique_temp_path("chess_prep_ndjson_target", "sqlite");
+    let source_pgn = unique_temp_path("chess_prep_ndjson_source", "pgn");
+
+    let pgn = r#"[Event "NDJSON Open"]
+[Site "Prague"]
+[Date "2024.03.01"]
+[Round "1"]
+[White "Alice \"The Rook\""]
+[Black "Bob"]
+[Result "1-0"]
+[Opening "Sicilian"]
+
+1. e4 c5 2. Nf3 d6 1-0
+
+[Event "NDJSON Open"]
+[Site "Prague"]
+[Date "2024.03.02"]
+[Round "2"]
+[White "Carol"]
+[Black "Dave"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/4K2R b K - 0 40"]
+
+40... Kd7 41. O-O *
+"#;
+    fs::write(&source_pgn, pgn).expect("should write source PGN");
+
+    let source_db_str = source_db.to_str().expect("path should be valid UTF-8");
+    let target_db_str = target_db.to_str().expect("path should be valid UTF-8");
+    init_db(source_db_str).expect("init source");
+    import_pgn_file(
+        source_db_str,
+        source_pgn.to_str().expect("path should be valid UTF-8"),
+    )
+    .expect("source import should work");
+
+    let mut buffer = Vec::new();
+    let written = export_games_ndjson(source_db_str, &GameFilter::default(), &mut buffer)
+        .expect("export should work");
+    assert_eq!(written, 2);
+    let exported = String::from_utf8(buffer).expect("export should be UTF-8");
+    assert_eq!(exported.lines().count(), 2);
+
+    init_db(target_db_str).expect("init target");
+    let summary =
+        import_games_ndjson(target_db_str, exported.as_bytes()).expect("import should work");
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.inserted, 2);
+    assert_eq!(summary.errors, 0);
+    assert_eq!(stored_games(source_db_str), stored_games(target_db_str));
+
+    let again = import_games_ndjson(target_db_str, format!("{exported}\nnot json\n").as_bytes())
+        .expect("re-import should work");
+    assert_eq!(again.skipped, 2);
+    assert_eq!(again.errors, 1);
+
+    for path in [source_db, target_db, source_pgn] {
+        fs::remove_file(path).expect("should clean up temp file");
+    }
+}
@@ -1,5 +1,9 @@
 use chess_prep::{
-    GameFilter, GameResultFilter, Pagination, QueryError, count_games, init_db, search_games,
+    DateFormatIssue, GameFilter, GameResultFilter, GamesSince, Page, Pagination, QueryError,
+    SortColumn, SortOptions, backfill_eco, count_games, database_stats, eco_coverage, eco_name,
+    games_per_day, games_since, import_pgn_file, init_db, length_histogram, list_ecos,
+    player_color_split, result_consistency_report, search_games, search_games_page,
+    search_games_sorted, unique_positions,
 };
 use rusqlite::{Connection, params};
 use std::fs;
@@ -150,6 +154,41 @@ fn result_filter_returns_expected_games() {
     });
 }
 
+#[test]
+fn unknown_and_missing_result_filters_are_distinct() {
+    with_seeded_db(|db_path| {
+        let conn = Connection::open(db_path).expect("should open db");
+        conn.execute(
+            "
+            INSERT INTO games (event, site, date, white, black, result, eco, pgn)
+            VALUES ('Archive', 'Unknown', '2023.05.05', 'No Result', 'Player', NULL, 'A00', NULL)
+            ",
+            [],
+        )
+        .expect("should insert game without result");
+
+        let filter = GameFilter {
+            result: GameResultFilter::Unknown,
+            ..GameFilter::default()
+        };
+        let games =
+            search_games(db_path, &filter, Pagination::default()).expect("search should work");
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].white.as_deref(), Some("Old Player"));
+        assert_eq!(games[0].result.as_deref(), Some("*"));
+
+        let filter = GameFilter {
+            result: GameResultFilter::Missing,
+            ..GameFilter::default()
+        };
+        let games =
+            search_games(db_path, &filter, Pagination::default()).expect("search should work");
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].white.as_deref(), Some("No Result"));
+        assert_eq!(games[0].result, None);
+    });
+}
+
 #[test]
 fn eco_filter_is_case_insensitive_substring() {
     with_seeded_db(|db_path| {
@@ -306,3 +345,1014 @@ fn invalid_date_format_returns_error() {
         ));
     });
 }
+
+#[test]
+fn invalid_date_format_reports_specific_reason() {
+    with_seeded_db(|db_path| {
+        let cases = [
+            ("2024.1.01", DateFormatIssue::Length { actual: 9 }),
+            ("2024-01.01", DateFormatIssue::Separator { position: 4 }),
+            ("2024.0a.01", DateFormatIssue::NonDigit { position: 6 }),
+        ];
+
+        for (date_to, expected) in cases {
+            let filter = GameFilter {
+                date_to: Some(date_to.to_string()),
+                ..GameFilter::default()
+            };
+            let err = count_games(db_path, &filter).expect_err("invalid date should fail count");
+            match err {
+                QueryError::InvalidDateFormat {
+                    field,
+                    value,
+                    reason,
+                } => {
+                    assert_eq!(field, "date_to");
+                    assert_eq!(value, date_to);
+                    assert_eq!(reason, expected, "unexpected reason for {date_to}");
+                }
+                other => panic!("expected InvalidDateFormat, got {other:?}"),
+            }
+        }
+    });
+}
+
+#[test]
+fn tag_filters_match_captured_pgn_tags() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = db_path.with_extension("pgn");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+
+    let pgn = r#"[Event "Blitz Arena"]
+[Site "Online"]
+[Date "2024.05.01"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[TimeControl "300+0"]
+[Annotator "GM X"]
+
+1. e4 e5 1-0
+
+[Event "Bullet Arena"]
+[Site "Online"]
+[Date "2024.05.02"]
+[White "Carol"]
+[Black "Dave"]
+[Result "0-1"]
+[TimeControl "60+0"]
+
+1. d4 d5 0-1
+"#;
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+
+    let mut filter = GameFilter {
+        tag_filters: vec![("TimeControl".to_string(), "300+0".to_string())],
+        ..GameFilter::default()
+    };
+    let games = search_games(db_path_str, &filter, Pagination::default()).expect("search");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].white.as_deref(), Some("Alice"));
+    assert_eq!(count_games(db_path_str, &filter).expect("count"), 1);
+
+    filter
+        .tag_filters
+        .push(("Annotator".to_string(), "GM Y".to_string()));
+    let games = search_games(db_path_str, &filter, Pagination::default()).expect("search");
+    assert!(games.is_empty(), "every tag pair should have to match");
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp pgn");
+}
+
+#[test]
+fn search_works_against_wal_database_with_open_writer() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let writer = Connection::open(db_path_str).expect("should open writer connection");
+    let journal_mode: String = writer
+        .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+        .expect("should switch to WAL");
+    assert_eq!(journal_mode, "wal");
+    seed_db(db_path_str);
+
+    let rows = search_games(
+        db_path_str,
+        &GameFilter {
+            search_text: Some("carlsen".to_string()),
+            ..GameFilter::default()
+        },
+        Pagination::default(),
+    )
+    .expect("read-only search should work on a WAL database");
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].white.as_deref(), Some("Magnus Carlsen"));
+    assert_eq!(
+        count_games(db_path_str, &GameFilter::default()).expect("count should work"),
+        7
+    );
+
+    drop(writer);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{db_path_str}{suffix}"));
+    }
+}
+
+#[test]
+fn length_histogram_buckets_games_by_ply_count() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, ply_count) in [("A", 12), ("B", 19), ("C", 20), ("D", 45), ("E", 47)] {
+        conn.execute(
+            "INSERT INTO games (white, result, ply_count) VALUES (?1, '1-0', ?2)",
+            params![white, ply_count],
+        )
+        .expect("should insert game");
+    }
+    conn.execute(
+        "INSERT INTO games (white, result, pgn) VALUES ('F', '0-1', 'e4 e5 Nf3')",
+        [],
+    )
+    .expect("should insert game without ply count");
+    drop(conn);
+    init_db(db_path_str).expect("init_db should backfill ply counts");
+
+    let histogram =
+        length_histogram(db_path_str, &GameFilter::default(), 20).expect("histogram should work");
+    assert_eq!(histogram, vec![(0, 3), (20, 1), (40, 2)]);
+
+    let white_wins = GameFilter {
+        result: GameResultFilter::WhiteWin,
+        ..GameFilter::default()
+    };
+    let histogram = length_histogram(db_path_str, &white_wins, 20).expect("filtered histogram");
+    assert_eq!(histogram, vec![(0, 2), (20, 1), (40, 2)]);
+
+    assert!(matches!(
+        length_histogram(db_path_str, &GameFilter::default(), 0),
+        Err(QueryError::InvalidBucketSize(0))
+    ));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn natural_collation_orders_rounds_numerically() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for round in ["Round 10", "Round 2", "Round 11"] {
+        conn.execute(
+            "INSERT INTO games (event, white, black, result) VALUES (?1, 'W', 'B', '1-0')",
+            params![round],
+        )
+        .expect("should insert game");
+        conn.execute(
+            "INSERT INTO game_tags (game_id, name, value) VALUES (?1, 'Round', ?2)",
+            params![conn.last_insert_rowid(), round.trim_start_matches("Round ")],
+        )
+        .expect("should insert round tag");
+    }
+    drop(conn);
+
+    for column in [SortColumn::Event, SortColumn::Round] {
+        let rows = search_games_sorted(
+            db_path_str,
+            &GameFilter::default(),
+            Pagination::default(),
+            SortOptions {
+                column,
+                descending: false,
+            },
+        )
+        .expect("sorted search should work");
+        let events: Vec<&str> = rows
+            .iter()
+            .map(|row| row.event.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            events,
+            vec!["Round 2", "Round 10", "Round 11"],
+            "{column:?}"
+        );
+    }
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn unique_positions_counts_transpositions_once() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, pgn, replay_error) in [
+        ("Alpha", "Nf3 Nf6 Nc3 d5", None),
+        ("Beta", "Nc3 Nf6 Nf3 e5", None),
+        ("Broken", "e4 Ke3", Some("illegal SAN 'Ke3' at ply 2")),
+    ] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, pgn, replay_error) VALUES (?1, 'Player', '*', ?2, ?3)",
+            params![white, pgn, replay_error],
+        )
+        .expect("should insert game");
+    }
+
+    let all = GameFilter::default();
+    assert_eq!(unique_positions(db_path_str, 0, &all).expect("count"), 1);
+    assert_eq!(unique_positions(db_path_str, 1, &all).expect("count"), 3);
+    // start, Nf3, Nf3 Nf6, Nc3, Nc3 Nf6, and the shared position after ply 3.
+    assert_eq!(unique_positions(db_path_str, 3, &all).expect("count"), 6);
+
+    let alpha_only = GameFilter {
+        white: Some("Alpha".to_string()),
+        ..GameFilter::default()
+    };
+    assert_eq!(
+        unique_positions(db_path_str, 3, &alpha_only).expect("count"),
+        4
+    );
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn eco_coverage_reports_zero_for_codes_without_games() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, eco) in [("One", "B20"), ("Two", " b20 "), ("Three", "Z99")] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, eco, pgn) VALUES (?1, 'Player', '*', ?2, 'e4 c5')",
+            params![white, eco],
+        )
+        .expect("should insert game");
+    }
+
+    let coverage = eco_coverage(db_path_str).expect("coverage should work");
+    let count_for = |code: &str| {
+        coverage
+            .iter()
+            .find(|(eco, _)| eco == code)
+            .map(|(_, count)| *count)
+    };
+    assert_eq!(count_for("B20"), Some(2));
+    assert_eq!(count_for("E70"), Some(0));
+    assert_eq!(count_for("Z99"), None);
+    assert!(coverage.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn backfill_eco_classifies_games_without_eco() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    conn.execute(
+        "INSERT INTO games (white, black, result, eco, pgn) VALUES ('Sicilian', 'Player', '0-1', NULL, 'e4 c5 Nf3 Nc6')",
+        [],
+    )
+    .expect("should insert unclassified game");
+    conn.execute(
+        "INSERT INTO games (white, black, result, eco, pgn) VALUES ('Tagged', 'Player', '1-0', 'A00', 'e4 e5')",
+        [],
+    )
+    .expect("should insert tagged game");
+    conn.execute_batch(
+        "
+        INSERT INTO games (white, black, result, pgn, setup_fen)
+        VALUES ('Composed', 'Player', '*', 'e4 c5', 'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1');
+        INSERT INTO games (white, black, result, pgn, variant)
+        VALUES ('Atomic', 'Player', '*', 'e4 c5', 'Atomic');
+        INSERT INTO games (white, black, result, pgn, replay_error)
+        VALUES ('Flagged', 'Player', '*', '1. e4 {best} c5 2. Ke3', 'illegal SAN');
+        ",
+    )
+    .expect("should insert games that must not be classified");
+
+    assert_eq!(
+        backfill_eco(db_path_str, false).expect("backfill should work"),
+        1
+    );
+    let unclassified: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM games WHERE white IN ('Composed', 'Atomic', 'Flagged') AND eco IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .expect("should count unclassified games");
+    assert_eq!(unclassified, 3);
+    let sicilian_eco: String = conn
+        .query_row(
+            "SELECT eco FROM games WHERE white = 'Sicilian'",
+            [],
+            |row| row.get(0),
+        )
+        .expect("should read eco");
+    assert!(sicilian_eco.starts_with('B'), "{sicilian_eco}");
+    let tagged_eco: String = conn
+        .query_row("SELECT eco FROM games WHERE white = 'Tagged'", [], |row| {
+            row.get(0)
+        })
+        .expect("should read eco");
+    assert_eq!(tagged_eco, "A00");
+
+    assert_eq!(
+        backfill_eco(db_path_str, true).expect("overwrite should work"),
+        1
+    );
+    let tagged_eco: String = conn
+        .query_row("SELECT eco FROM games WHERE white = 'Tagged'", [], |row| {
+            row.get(0)
+        })
+        .expect("should read eco");
+    assert_eq!(tagged_eco, "C20");
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn games_per_day_counts_full_dates_in_order() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, date, result) in [
+        ("A", "2024.03.02", "1-0"),
+        ("B", "2024.03.01", "0-1"),
+        ("C", "2024.03.02", "1/2-1/2"),
+        ("D", "2024.03.02", "1-0"),
+        ("E", "2024.??.??", "1-0"),
+        ("F", "????.??.??", "1-0"),
+        ("G", "2024.03.05", "1-0"),
+    ] {
+        conn.execute(
+            "INSERT INTO games (white, date, result) VALUES (?1, ?2, ?3)",
+            params![white, date, result],
+        )
+        .expect("should insert game");
+    }
+    conn.execute("INSERT INTO games (white, result) VALUES ('H', '1-0')", [])
+        .expect("should insert game without date");
+    drop(conn);
+
+    let days = games_per_day(db_path_str, &GameFilter::default()).expect("timeline should work");
+    assert_eq!(
+        days,
+        vec![
+            ("2024.03.01".to_string(), 1),
+            ("2024.03.02".to_string(), 3),
+            ("2024.03.05".to_string(), 1),
+        ]
+    );
+
+    let white_wins = GameFilter {
+        result: GameResultFilter::WhiteWin,
+        ..GameFilter::default()
+    };
+    let days = games_per_day(db_path_str, &white_wins).expect("filtered timeline should work");
+    assert_eq!(
+        days,
+        vec![("2024.03.02".to_string(), 2), ("2024.03.05".to_string(), 1)]
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn list_ecos_returns_distinct_codes_with_counts() {
+    with_seeded_db(|db_path| {
+        let conn = Connection::open(db_path).expect("should open db");
+        for (white, eco) in [
+            ("P1", Some("B90")),
+            ("P2", Some(" B90 ")),
+            ("P3", Some("")),
+            ("P4", None),
+        ] {
+            conn.execute(
+                "INSERT INTO games (white, black, result, eco) VALUES (?1, 'Y', '*', ?2)",
+                params![white, eco],
+            )
+            .expect("should insert game");
+        }
+        drop(conn);
+
+        let ecos = list_ecos(db_path).expect("list_ecos should work");
+        let expected: Vec<(String, u64)> = [
+            ("A00", 1),
+            ("B01", 1),
+            ("B90", 2),
+            ("C20", 1),
+            ("C84", 1),
+            ("D37", 1),
+            ("E00", 1),
+            ("E32", 1),
+        ]
+        .into_iter()
+        .map(|(code, count)| (code.to_string(), count))
+        .collect();
+        assert_eq!(ecos, expected);
+        assert_eq!(eco_name("B90"), Some("Sicilian Defense: Najdorf Variation"));
+        assert_eq!(eco_name("Z99"), None);
+    });
+}
+
+#[test]
+fn opening_filter_matches_opening_and_variation_tags() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = db_path.with_extension("pgn");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    let pgn = r#"[Event "Opening Tags"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[Opening "Sicilian"]
+[Variation "Najdorf, English Attack"]
+
+1. e4 c5 1-0
+
+[Event "Opening Tags"]
+[White "Carol"]
+[Black "Dave"]
+[Result "0-1"]
+[Opening "French"]
+
+1. e4 e6 0-1
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    import_pgn_file(
+        db_path_str,
+        pgn_path.to_str().expect("pgn path should be valid UTF-8"),
+    )
+    .expect("import should work");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let (opening, variation): (Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT opening, variation FROM games WHERE white = 'Alice'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .expect("should read opening columns");
+    assert_eq!(opening.as_deref(), Some("Sicilian"));
+    assert_eq!(variation.as_deref(), Some("Najdorf, English Attack"));
+
+    let mut filter = GameFilter {
+        opening: Some("sicilian".to_string()),
+        ..GameFilter::default()
+    };
+    let games =
+        search_games(db_path_str, &filter, Pagination::default()).expect("search should work");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].white.as_deref(), Some("Alice"));
+
+    filter.opening = Some("english attack".to_string());
+    assert_eq!(
+        count_games(db_path_str, &filter).expect("count should work"),
+        1
+    );
+
+    filter.opening = Some("Caro".to_string());
+    assert_eq!(
+        count_games(db_path_str, &filter).expect("count should work"),
+        0
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN");
+}
+
+#[test]
+fn contains_move_filter_matches_whole_san_tokens() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = db_path.with_extension("pgn");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    let pgn = r#"[Event "Contains Move"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0
+
+[Event "Contains Move"]
+[White "Carol"]
+[Black "Dave"]
+[Result "0-1"]
+
+1. e4 e5 2. Bc4 Nf6 3. Bxf7+ Kxf7 0-1
+"#;
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    import_pgn_file(
+        db_path_str,
+        pgn_path.to_str().expect("pgn path should be valid UTF-8"),
+    )
+    .expect("import should work");
+
+    let mut filter = GameFilter {
+        contains_move: Some("Qxf7".to_string()),
+        ..GameFilter::default()
+    };
+    let games =
+        search_games(db_path_str, &filter, Pagination::default()).expect("search should work");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].white.as_deref(), Some("Alice"));
+
+    filter.contains_move = Some("Bxf7+".to_string());
+    let games =
+        search_games(db_path_str, &filter, Pagination::default()).expect("search should work");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].white.as_deref(), Some("Carol"));
+
+    filter.contains_move = Some("Qz9".to_string());
+    assert!(matches!(
+        count_games(db_path_str, &filter),
+        Err(QueryError::InvalidSan(value)) if value == "Qz9"
+    ));
+
+    filter.contains_move = Some("f7".to_string());
+    assert_eq!(
+        count_games(db_path_str, &filter).expect("count should work"),
+        0
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN");
+}
+
+#[test]
+fn database_stats_sums_and_averages_known_ply_counts() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let empty = database_stats(db_path_str).expect("stats should work");
+    assert_eq!(empty.total_games, 0);
+    assert_eq!(empty.total_plies, 0);
+    assert_eq!(empty.avg_plies, None);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, ply_count) in [("Alice", Some(40)), ("Bob", Some(61)), ("Carol", None)] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, ply_count) VALUES (?1, 'Opponent', '*', ?2)",
+            params![white, ply_count],
+        )
+        .expect("should insert game");
+    }
+
+    let stats = database_stats(db_path_str).expect("stats should work");
+    assert_eq!(stats.total_games, 3);
+    assert_eq!(stats.total_plies, 101);
+    assert_eq!(stats.avg_plies, Some(50.5));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn games_since_returns_only_later_batches() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = db_path.with_extension("pgn");
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let batch = |white: &str, black: &str| {
+        format!(
+            "[Event \"Sync\"]\n[White \"{white}\"]\n[Black \"{black}\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n\n"
+        )
+    };
+    fs::write(&pgn_path, batch("Alice", "Bob") + &batch("Carol", "Dave"))
+        .expect("should write temp PGN");
+    import_pgn_file(db_path_str, pgn_path_str).expect("first import should work");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let (last_rowid, last_imported_at): (i64, i64) = conn
+        .query_row(
+            "SELECT MAX(rowid), MAX(imported_at) FROM games",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .expect("first batch should have rowids and timestamps");
+    drop(conn);
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    fs::write(&pgn_path, batch("Erin", "Frank")).expect("should write temp PGN");
+    import_pgn_file(db_path_str, pgn_path_str).expect("second import should work");
+
+    for since in [
+        GamesSince::AfterRowid(last_rowid),
+        GamesSince::ImportedAfter(last_imported_at),
+    ] {
+        let games = games_since(db_path_str, since).expect("games_since should work");
+        let whites: Vec<_> = games.iter().map(|game| game.white.as_deref()).collect();
+        assert_eq!(whites, vec![Some("Erin")], "{since:?}");
+    }
+
+    let all = games_since(db_path_str, GamesSince::AfterRowid(0)).expect("should work");
+    assert_eq!(all.len(), 3);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp PGN");
+}
+
+#[test]
+fn eco_in_filter_matches_any_listed_code() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, eco) in [
+        ("Alice", "B90"),
+        ("Bob", "b96"),
+        ("Carol", "B99 "),
+        ("Dave", "B33"),
+        ("Erin", "C42"),
+    ] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, eco) VALUES (?1, 'Opponent', '*', ?2)",
+            params![white, eco],
+        )
+        .expect("should insert game");
+    }
+    drop(conn);
+
+    let filter = GameFilter {
+        eco_in: vec!["b90".to_string(), "B96".to_string(), "B99".to_string()],
+        ..GameFilter::default()
+    };
+    let mut whites: Vec<String> = search_games(db_path_str, &filter, Pagination::default())
+        .expect("search should work")
+        .into_iter()
+        .filter_map(|game| game.white)
+        .collect();
+    whites.sort();
+    assert_eq!(whites, vec!["Alice", "Bob", "Carol"]);
+
+    let filter = GameFilter {
+        eco_in: vec!["C42".to_string()],
+        ..GameFilter::default()
+    };
+    assert_eq!(
+        count_games(db_path_str, &filter).expect("count should work"),
+        1
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn search_games_page_reports_navigation_flags() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+    seed_db(db_path_str);
+    let total = count_games(db_path_str, &GameFilter::default()).expect("count should work");
+    assert!(total > 2, "seed data should span several pages");
+
+    let first: Page<_> = search_games_page(
+        db_path_str,
+        &GameFilter::default(),
+        Pagination {
+            limit: 2,
+            offset: 0,
+        },
+    )
+    .expect("first page should load");
+    assert_eq!(first.items.len(), 2);
+    assert_eq!(first.total, total);
+    assert_eq!(first.page_index(), 0);
+    assert!(!first.has_prev);
+    assert!(first.has_next);
+
+    let last_offset = u32::try_from((total - 1) / 2 * 2).expect("offset fits in u32");
+    let last = search_games_page(
+        db_path_str,
+        &GameFilter::default(),
+        Pagination {
+            limit: 2,
+            offset: last_offset,
+        },
+    )
+    .expect("last page should load");
+    assert_eq!(last.page_index(), last_offset / 2);
+    assert!(last.has_prev);
+    assert!(!last.has_next);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn player_filters_distinguish_exact_from_substring_matches() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, black) in [("Ding", "Alice"), ("Dingledine", "Bob"), ("Carol", "ding")] {
+        conn.execute(
+            "INSERT INTO games (white, black, result) VALUES (?1, ?2, '*')",
+            params![white, black],
+        )
+        .expect("should insert game");
+    }
+    drop(conn);
+
+    let mut filter = GameFilter {
+        white: Some("ding".to_string()),
+        ..GameFilter::default()
+    };
+    assert_eq!(
+        count_games(db_path_str, &filter).expect("count should work"),
+        2
+    );
+
+    filter.player_exact = true;
+    let games =
+        search_games(db_path_str, &filter, Pagination::default()).expect("search should work");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].white.as_deref(), Some("Ding"));
+
+    let filter = GameFilter {
+        black: Some("DING".to_string()),
+        player_exact: true,
+        ..GameFilter::default()
+    };
+    let games =
+        search_games(db_path_str, &filter, Pagination::default()).expect("search should work");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].white.as_deref(), Some("Carol"));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn result_consistency_report_flags_mislabeled_mates() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let scholars_mate = "e4 e5 Bc4 Nc6 Qh5 Nf6 Qxf7#";
+    for (white, result, pgn) in [
+        ("Correct", "1-0", scholars_mate),
+        ("Mislabeled", "0-1", scholars_mate),
+        ("Drawn Mate", "1/2-1/2", scholars_mate),
+        ("Resigned", "0-1", "e4 e5 Nf3"),
+        ("Unfinished", "*", scholars_mate),
+    ] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, pgn) VALUES (?1, 'Opponent', ?2, ?3)",
+            params![white, result, pgn],
+        )
+        .expect("should insert game");
+    }
+    drop(conn);
+
+    let report = result_consistency_report(db_path_str).expect("report should work");
+    let flagged: Vec<(&str, &str)> = report
+        .iter()
+        .map(|mismatch| {
+            (
+                mismatch.declared_result.as_str(),
+                mismatch.expected_result.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(flagged, vec![("0-1", "1-0"), ("1/2-1/2", "1-0")]);
+    assert!(report[0].game_id < report[1].game_id);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn eco_missing_filter_returns_only_games_without_eco() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, eco) in [
+        ("Alice", Some("C20")),
+        ("Bob", None),
+        ("Carol", Some("  ")),
+        ("Dave", Some("B90")),
+    ] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, eco) VALUES (?1, 'Opponent', '*', ?2)",
+            params![white, eco],
+        )
+        .expect("should insert game");
+    }
+    drop(conn);
+
+    let filter = GameFilter {
+        eco_missing: true,
+        ..GameFilter::default()
+    };
+    let mut whites: Vec<String> = search_games(db_path_str, &filter, Pagination::default())
+        .expect("search should work")
+        .into_iter()
+        .filter_map(|game| game.white)
+        .collect();
+    whites.sort();
+    assert_eq!(whites, vec!["Bob", "Carol"]);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn zero_limit_means_the_default_page_size_not_unlimited() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    init_db(db_path_str).expect("init_db should create schema");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for index in 0..(Pagination::DEFAULT_LIMIT + 5) {
+        conn.execute(
+            "INSERT INTO games (white, black, result) VALUES (?1, 'Opponent', '*')",
+            params![format!("Player {index}")],
+        )
+        .expect("should insert game");
+    }
+    drop(conn);
+
+    let page = search_games_page(
+        db_path_str,
+        &GameFilter::default(),
+        Pagination {
+            limit: 0,
+            offset: 0,
+        },
+    )
+    .expect("search should work");
+    assert_eq!(page.limit, Pagination::DEFAULT_LIMIT);
+    assert_eq!(page.items.len(), Pagination::DEFAULT_LIMIT as usize);
+    assert!(page.has_next);
+
+    let page = search_games_page(
+        db_path_str,
+        &GameFilter::default(),
+        Pagination {
+            limit: u32::MAX,
+            offset: 0,
+        },
+    )
+    .expect("search should work");
+    assert_eq!(page.limit, Pagination::MAX_LIMIT);
+    assert_eq!(page.items.len(), (Pagination::DEFAULT_LIMIT + 5) as usize);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn partial_dates_match_ranges_that_cover_their_whole_span() {
+    with_seeded_db(|db_path| {
+        let year = GameFilter {
+            date_from: Some("2024.01.01".to_string()),
+            date_to: Some("2024.12.31".to_string()),
+            include_partial_dates: true,
+            ..GameFilter::default()
+        };
+        let games =
+            search_games(db_path, &year, Pagination::default()).expect("search should work");
+        assert_eq!(games.len(), 5);
+        assert!(
+            games
+                .iter()
+                .any(|g| g.date.as_deref() == Some("2024.??.??"))
+        );
+        assert!(
+            games
+                .iter()
+                .all(|g| g.date.as_deref() != Some("????.??.??"))
+        );
+
+        let autumn = GameFilter {
+            date_from: Some("2024.09.01".to_string()),
+            date_to: Some("2024.12.31".to_string()),
+            include_partial_dates: true,
+            ..GameFilter::default()
+        };
+        let games =
+            search_games(db_path, &autumn, Pagination::default()).expect("search should work");
+        assert!(
+            games
+                .iter()
+                .all(|g| g.date.as_deref() != Some("2024.??.??"))
+        );
+    });
+}
+
+#[test]
+fn player_color_split_counts_games_per_color() {
+    with_seeded_db(|db_path| {
+        let conn = Connection::open(db_path).expect("should open seeded db");
+        for (white, black) in [("Dave", "alice"), ("Erin", " Alice ")] {
+            conn.execute(
+                "INSERT INTO games (white, black, result) VALUES (?1, ?2, '1-0')",
+                params![white, black],
+            )
+            .expect("should insert game");
+        }
+        drop(conn);
+
+        assert_eq!(
+            player_color_split(db_path, "Alice").expect("split should work"),
+            (1, 2)
+        );
+        assert_eq!(
+            player_color_split(db_path, "Dave").expect("split should work"),
+            (1, 1)
+        );
+        assert_eq!(
+            player_color_split(db_path, "Nobody").expect("split should work"),
+            (0, 0)
+        );
+    });
+}
+
+#[test]
+fn queries_read_databases_created_before_later_columns() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let conn = Connection::open(db_path_str).expect("should create db");
+    conn.execute_batch(
+        "
+        CREATE TABLE games (
+            event TEXT, site TEXT, date TEXT, white TEXT, black TEXT,
+            result TEXT, eco TEXT, pgn TEXT
+        );
+        INSERT INTO games (event, white, black, result, pgn)
+        VALUES ('Old', 'Alice', 'Bob', '1-0', 'e4 e5');
+        ",
+    )
+    .expect("should create an unmigrated games table");
+    drop(conn);
+
+    let opening = GameFilter {
+        opening: Some("Sicilian".to_string()),
+        ..GameFilter::default()
+    };
+    assert_eq!(
+        count_games(db_path_str, &opening).expect("count should work"),
+        0
+    );
+
+    let tagged = GameFilter {
+        tag_filters: vec![("Round".to_string(), "1".to_string())],
+        ..GameFilter::default()
+    };
+    assert_eq!(
+        count_games(db_path_str, &tagged).expect("count should work"),
+        0
+    );
+
+    let by_round = search_games_sorted(
+        db_path_str,
+        &GameFilter::default(),
+        Pagination::default(),
+        SortOptions {
+            column: SortColumn::Round,
+            descending: false,
+        },
+    )
+    .expect("round sort should work");
+    assert_eq!(by_round.len(), 1);
+
+    assert!(
+        length_histogram(db_path_str, &GameFilter::default(), 10)
+            .expect("histogram should work")
+            .is_empty()
+    );
+    let stats = database_stats(db_path_str).expect("stats should work");
+    assert_eq!(stats.total_games, 1);
+    assert_eq!(stats.total_plies, 0);
+    assert_eq!(stats.avg_plies, None);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
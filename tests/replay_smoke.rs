@@ -1,4 +1,9 @@
-use chess_prep::{ReplayError, import_pgn_file, init_db, replay_game, replay_game_fens};
+use chess_prep::{
+    Color, DEFAULT_MAX_REPLAY_PLIES, REPLAY_PROGRESS_PLY_INTERVAL, ReplayError, board_timeline,
+    import_pgn_file, init_db, normalize_movetext, replay_frames, replay_game, replay_game_fens,
+    replay_game_with_max_plies, replay_game_with_progress, replay_previews, replay_sans_canonical,
+    side_to_move_at_ply,
+};
 use rusqlite::{Connection, params};
 use std::fs;
 use std::path::PathBuf;
@@ -78,6 +83,7 @@ fn replay_returns_fen_timeline_for_known_game() {
     assert_eq!(timeline.sans, vec!["e4", "e5", "Nf3"]);
     assert_eq!(timeline.ucis, vec!["e2e4", "e7e5", "g1f3"]);
     assert_eq!(timeline.fens, fens);
+    assert!(timeline.start_is_standard);
 
     fs::remove_file(db_path).expect("should clean up temp db");
     fs::remove_file(pgn_path).expect("should clean up temp pgn");
@@ -143,8 +149,540 @@ fn replay_returns_invalid_san_error_for_bad_movetext() {
     let err = replay_game_fens(db_path_str, game_id).expect_err("replay should fail");
     assert!(matches!(
         err,
-        ReplayError::InvalidSan { ply: 2, san } if san == "???"
+        ReplayError::InvalidSan { ply: 2, san, .. } if san == "???"
     ));
 
     fs::remove_file(db_path).expect("should clean up temp db");
 }
+
+#[test]
+fn board_timeline_starts_with_standard_back_ranks() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    conn.execute(
+        "
+        INSERT INTO games (event, site, date, white, black, result, eco, pgn)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+        params![
+            "Board Timeline",
+            "Nowhere",
+            "2024.01.01",
+            "Alice",
+            "Bob",
+            "1-0",
+            "C20",
+            "e4 e5",
+        ],
+    )
+    .expect("should insert game");
+    let game_id = conn.last_insert_rowid();
+
+    let boards = board_timeline(db_path_str, game_id).expect("board timeline should work");
+    assert_eq!(boards.len(), 3, "start + 2 plies expected");
+
+    let back_rank = |pieces: &str| pieces.chars().map(Some).collect::<Vec<_>>();
+    let start = &boards[0];
+    assert_eq!(start[0].to_vec(), back_rank("rnbqkbnr"));
+    assert_eq!(start[1].to_vec(), back_rank("pppppppp"));
+    assert_eq!(start[6].to_vec(), back_rank("PPPPPPPP"));
+    assert_eq!(start[7].to_vec(), back_rank("RNBQKBNR"));
+    assert!(start[2..6].iter().flatten().all(Option::is_none));
+
+    assert_eq!(boards[1][4][4], Some('P'), "white pawn should reach e4");
+    assert_eq!(boards[1][6][4], None, "e2 should be empty after e4");
+    assert_eq!(boards[2][3][4], Some('p'), "black pawn should reach e5");
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn replay_continues_move_counters_from_setup_fen() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+
+    let pgn = r#"[Event "Setup Replay"]
+[Site "Berlin"]
+[Date "2024.01.01"]
+[White "Alice"]
+[Black "Bob"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/4K2R w K - 5 20"]
+
+20. Kd2 Kd7 21. Rh7+ *
+"#;
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let game_id: i64 = conn
+        .query_row(
+            "SELECT rowid FROM games WHERE event = 'Setup Replay'",
+            [],
+            |row| row.get(0),
+        )
+        .expect("should fetch imported game rowid");
+
+    let timeline = replay_game(db_path_str, game_id).expect("replay should work");
+    assert_eq!(
+        timeline.fens,
+        vec![
+            "4k3/8/8/8/8/8/8/4K2R w K - 5 20",
+            "4k3/8/8/8/8/8/3K4/7R b - - 6 20",
+            "8/3k4/8/8/8/8/3K4/7R w - - 7 21",
+            "8/3k3R/8/8/8/8/3K4/8 b - - 8 21",
+        ]
+    );
+    assert_eq!(timeline.sans, vec!["Kd2", "Kd7", "Rh7+"]);
+    assert!(!timeline.start_is_standard);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp pgn");
+}
+
+#[test]
+fn replay_castles_from_a_chess960_setup_fen() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+
+    let pgn = r#"[Event "Chess960 Replay"]
+[White "Alice"]
+[Black "Bob"]
+[Result "*"]
+[Variant "Chess960"]
+[SetUp "1"]
+[FEN "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1"]
+
+1. O-O O-O *
+"#;
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+    assert_eq!(summary.inserted, 1);
+    assert_eq!(summary.invalid, 0);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let game_id: i64 = conn
+        .query_row(
+            "SELECT rowid FROM games WHERE event = 'Chess960 Replay'",
+            [],
+            |row| row.get(0),
+        )
+        .expect("should fetch imported game rowid");
+
+    let timeline = replay_game(db_path_str, game_id).expect("replay should work");
+    assert_eq!(timeline.sans, vec!["O-O", "O-O"]);
+    assert_eq!(
+        timeline.fens.last().map(String::as_str),
+        Some("bqnbrrkn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRRKN w - - 2 2")
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp pgn");
+}
+
+#[test]
+fn replay_invalid_san_error_carries_pre_move_fen() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    conn.execute(
+        "
+        INSERT INTO games (event, site, date, white, black, result, eco, pgn)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+        params![
+            "Replay Illegal",
+            "Nowhere",
+            "2024.01.01",
+            "Alice",
+            "Bob",
+            "1-0",
+            "C20",
+            "e4 Ke3",
+        ],
+    )
+    .expect("should insert game");
+    let game_id = conn.last_insert_rowid();
+
+    let err = replay_game(db_path_str, game_id).expect_err("replay should fail");
+    match err {
+        ReplayError::InvalidSan { ply, san, fen } => {
+            assert_eq!(ply, 2);
+            assert_eq!(san, "Ke3");
+            assert_eq!(
+                fen,
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+            );
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn replay_frames_lazily_match_timeline_prefix() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    conn.execute(
+        "
+        INSERT INTO games (event, site, date, white, black, result, eco, pgn)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+        params![
+            "Replay Frames",
+            "Nowhere",
+            "2024.01.01",
+            "Alice",
+            "Bob",
+            "1-0",
+            "C60",
+            "e4 e5 Nf3 Nc6 Bb5 a6",
+        ],
+    )
+    .expect("should insert game");
+    let game_id = conn.last_insert_rowid();
+
+    let timeline = replay_game(db_path_str, game_id).expect("timeline replay should work");
+    let frames = replay_frames(db_path_str, game_id)
+        .expect("frames should open")
+        .take(3)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("first frames should replay");
+
+    assert_eq!(frames.len(), 3);
+    for frame in &frames {
+        assert_eq!(frame.fen, timeline.fens[frame.ply]);
+    }
+    assert_eq!(frames[0].san, None);
+    assert_eq!(frames[0].uci, None);
+    assert_eq!(frames[1].san.as_deref(), Some("e4"));
+    assert_eq!(frames[2].uci.as_deref(), Some("e7e5"));
+
+    let total = replay_frames(db_path_str, game_id)
+        .expect("frames should open")
+        .count();
+    assert_eq!(total, timeline.fens.len());
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn replay_progress_fires_every_interval_for_long_games() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+
+    let cycle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+    let plies = 260usize;
+    let movetext = cycle
+        .iter()
+        .copied()
+        .cycle()
+        .take(plies)
+        .collect::<Vec<_>>()
+        .join(" ");
+    conn.execute(
+        "
+        INSERT INTO games (event, site, date, white, black, result, eco, pgn)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+        params![
+            "Replay Progress",
+            "Nowhere",
+            "2024.01.01",
+            "Alice",
+            "Bob",
+            "1/2-1/2",
+            "A04",
+            movetext,
+        ],
+    )
+    .expect("should insert game");
+    let game_id = conn.last_insert_rowid();
+
+    let mut reported = Vec::new();
+    let timeline = replay_game_with_progress(db_path_str, game_id, |ply| reported.push(ply))
+        .expect("replay should work");
+
+    assert_eq!(timeline.sans.len(), plies);
+    let expected: Vec<usize> = (1..=plies / REPLAY_PROGRESS_PLY_INTERVAL)
+        .map(|step| step * REPLAY_PROGRESS_PLY_INTERVAL)
+        .collect();
+    assert_eq!(reported, expected);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn canonical_sans_add_check_and_mate_suffixes() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    conn.execute(
+        "INSERT INTO games (white, black, result, pgn) VALUES ('Fool', 'Mate', '0-1', ?1)",
+        params!["f3 e5 g4 Qh4"],
+    )
+    .expect("should insert game with bare SAN tokens");
+    let game_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO games (white, black, result, pgn) VALUES ('Early', 'Check', '*', ?1)",
+        params!["e4 f5 Qh5"],
+    )
+    .expect("should insert game with a check missing its suffix");
+    let check_game_id = conn.last_insert_rowid();
+    drop(conn);
+
+    let sans = replay_sans_canonical(db_path_str, game_id).expect("canonical replay should work");
+    assert_eq!(sans, vec!["f3", "e5", "g4", "Qh4#"]);
+
+    let sans =
+        replay_sans_canonical(db_path_str, check_game_id).expect("canonical replay should work");
+    assert_eq!(sans, vec!["e4", "f5", "Qh5+"]);
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn side_to_move_follows_ply_parity_from_the_start_position() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    conn.execute(
+        "INSERT INTO games (white, black, result, pgn) VALUES ('Standard', 'Start', '*', 'e4 e5 Nf3')",
+        [],
+    )
+    .expect("should insert standard game");
+    let standard_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO games (white, black, result, pgn, setup_fen) VALUES ('Black', 'Start', '*', 'Ke7 Kd2', ?1)",
+        params!["4k3/8/8/8/8/8/8/4K3 b - - 0 40"],
+    )
+    .expect("should insert black-to-move game");
+    let black_start_id = conn.last_insert_rowid();
+    drop(conn);
+
+    let side = |game_id, ply| side_to_move_at_ply(db_path_str, game_id, ply);
+    assert_eq!(side(standard_id, 0).expect("ply 0"), Color::White);
+    assert_eq!(side(standard_id, 1).expect("ply 1"), Color::Black);
+    assert_eq!(side(standard_id, 3).expect("ply 3"), Color::Black);
+    assert_eq!(side(black_start_id, 0).expect("ply 0"), Color::Black);
+    assert_eq!(side(black_start_id, 1).expect("ply 1"), Color::White);
+    assert!(matches!(
+        side(standard_id, 4),
+        Err(ReplayError::PlyOutOfRange { ply: 4, plies: 3 })
+    ));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[test]
+fn normalize_movetext_strips_numbers_comments_and_variations() {
+    let raw = "1. e4 {king pawn} e5 2. Nf3!? (2. f4 exf4) Nc6 $1 3. Bb5 a6 4. Bxc6 dxc6 1-0";
+    assert_eq!(
+        normalize_movetext(raw).expect("movetext should normalize"),
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6 dxc6"
+    );
+
+    let err = normalize_movetext("1. e4 e5 2. Ke3").unwrap_err();
+    assert!(matches!(err, ReplayError::InvalidSan { ply: 3, .. }));
+}
+
+#[test]
+fn replay_rejects_movetext_longer_than_the_ply_cap() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let movetext = ["Nf3 Nf6 Ng1 Ng8"; 50].join(" ");
+    conn.execute(
+        "INSERT INTO games (white, black, result, pgn) VALUES ('Alice', 'Bob', '*', ?1)",
+        params![movetext],
+    )
+    .expect("should insert game");
+    let game_id = conn.last_insert_rowid();
+
+    let err =
+        replay_game_with_max_plies(db_path_str, game_id, 100).expect_err("replay should fail");
+    assert!(matches!(err, ReplayError::TooLong { plies: 200 }));
+
+    let timeline = replay_game_with_max_plies(db_path_str, game_id, DEFAULT_MAX_REPLAY_PLIES)
+        .expect("replay under the default cap should work");
+    assert_eq!(timeline.sans.len(), 200);
+
+    let oversized = ["Nf3 Nf6 Ng1 Ng8"; DEFAULT_MAX_REPLAY_PLIES / 4 + 1].join(" ");
+    conn.execute(
+        "INSERT INTO games (white, black, result, pgn) VALUES ('Alice', 'Bob', '*', ?1)",
+        params![oversized],
+    )
+    .expect("should insert oversized game");
+    let oversized_id = conn.last_insert_rowid();
+    assert!(matches!(
+        board_timeline(db_path_str, oversized_id),
+        Err(ReplayError::TooLong { .. })
+    ));
+    assert!(matches!(
+        replay_sans_canonical(db_path_str, oversized_id),
+        Err(ReplayError::TooLong { .. })
+    ));
+    assert!(matches!(
+        side_to_move_at_ply(db_path_str, oversized_id, 0),
+        Err(ReplayError::TooLong { .. })
+    ));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[cfg(not(feature = "variant"))]
+#[test]
+fn atomic_games_record_their_variant_and_are_not_replayed() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+
+    let pgn = r#"[Event "Rated Atomic game"]
+[Site "https://lichess.org/abcdefgh"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[Variant "Atomic"]
+
+1. Nf3 f6 2. Nd4 e5 3. Nb5 d6 4. Nxc7+ 1-0
+"#;
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+    assert_eq!(summary.invalid, 1);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let (game_id, variant, replay_error): (i64, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT rowid, variant, replay_error FROM games WHERE white = 'Alice'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .expect("should fetch imported game");
+    assert_eq!(variant.as_deref(), Some("Atomic"));
+    assert!(
+        replay_error
+            .as_deref()
+            .is_some_and(|error| error.contains("unsupported variant 'Atomic'"))
+    );
+
+    let err = replay_game(db_path_str, game_id).expect_err("replay should refuse atomic games");
+    assert!(matches!(err, ReplayError::UnsupportedVariant(ref variant) if variant == "Atomic"));
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp pgn");
+}
+
+#[test]
+fn replay_previews_return_opening_moves_for_a_page() {
+    let db_path = unique_temp_db_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+
+    init_db(db_path_str).expect("init_db should create schema");
+    let conn = Connection::open(db_path_str).expect("should open db");
+    for (white, pgn) in [
+        ("Alice", Some("e4 e5 Nf3 Nc6 Bb5")),
+        ("Bob", None),
+        ("Carol", Some("d4")),
+        ("Dave", Some("c4 Ke3 Nf3")),
+    ] {
+        conn.execute(
+            "INSERT INTO games (white, black, result, pgn) VALUES (?1, 'Player', '*', ?2)",
+            params![white, pgn],
+        )
+        .expect("should insert game");
+    }
+
+    let previews = replay_previews(db_path_str, &[3, 1, 2, 4, 99], 3).expect("previews");
+    assert_eq!(
+        previews,
+        vec![
+            (3, vec!["d4".to_string()]),
+            (
+                1,
+                vec!["e4".to_string(), "e5".to_string(), "Nf3".to_string()]
+            ),
+            (4, vec!["c4".to_string()]),
+        ]
+    );
+
+    drop(conn);
+    fs::remove_file(db_path).expect("should clean up temp db");
+}
+
+#[cfg(feature = "variant")]
+#[test]
+fn atomic_games_replay_under_atomic_rules() {
+    let db_path = unique_temp_db_path();
+    let pgn_path = unique_temp_pgn_path();
+    let db_path_str = db_path.to_str().expect("db path should be valid UTF-8");
+    let pgn_path_str = pgn_path.to_str().expect("pgn path should be valid UTF-8");
+
+    let pgn = r#"[Event "Rated Atomic game"]
+[Site "https://lichess.org/abcdefgh"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[Variant "Atomic"]
+
+1. Nf3 f6 2. Nd4 e5 3. Nb5 d6 4. Nxc7+ 1-0
+"#;
+
+    fs::write(&pgn_path, pgn).expect("should write temp PGN");
+    init_db(db_path_str).expect("init_db should create schema");
+    let summary = import_pgn_file(db_path_str, pgn_path_str).expect("import should work");
+    assert_eq!(summary.invalid, 0);
+
+    let conn = Connection::open(db_path_str).expect("should open db");
+    let (game_id, variant, replay_error): (i64, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT rowid, variant, replay_error FROM games WHERE white = 'Alice'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .expect("should fetch imported game");
+    assert_eq!(variant.as_deref(), Some("Atomic"));
+    assert_eq!(replay_error, None);
+
+    // The capture on c7 explodes the knight and every piece around c7 except
+    // pawns, so Black's queen, bishop and knight are gone.
+    let timeline = replay_game(db_path_str, game_id).expect("atomic replay should work");
+    assert!(
+        timeline
+            .fens
+            .last()
+            .is_some_and(|fen| fen.starts_with("r3kbnr/pp4pp/3p1p2/4p3/8/8/"))
+    );
+
+    fs::remove_file(db_path).expect("should clean up temp db");
+    fs::remove_file(pgn_path).expect("should clean up temp pgn");
+}